@@ -7,5 +7,6 @@ class BinderServer {
         }
 
         external fun loadService()
+        external fun unloadService()
     }
 }
\ No newline at end of file
@@ -29,6 +29,11 @@ class MainActivity : ComponentActivity() {
             }
         }
     }
+
+    override fun onDestroy() {
+        BinderServer.unloadService()
+        super.onDestroy()
+    }
 }
 
 @Composable
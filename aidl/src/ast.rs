@@ -0,0 +1,179 @@
+//! Syntax tree of an `.aidl` file, as written by the user.
+//! Names are resolved later by [`crate::resolve`].
+
+#[derive(Debug, Clone)]
+pub(crate) struct Document {
+    pub package: String,
+    pub imports: Vec<String>,
+    pub decls: Vec<Decl>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Annotation {
+    pub name: String,
+    pub params: Vec<(String, Expr)>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Decl {
+    pub name: String,
+    pub annotations: Vec<Annotation>,
+    pub kind: DeclKind,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum DeclKind {
+    /// `parcelable Foo;`, defined in another language
+    Unstructured,
+    Parcelable(Parcelable),
+    Union(Parcelable),
+    Enum(Enum),
+    Interface(Interface),
+}
+
+impl Decl {
+    pub fn nested(&self) -> &[Decl] {
+        match &self.kind {
+            DeclKind::Parcelable(p) | DeclKind::Union(p) => &p.nested,
+            DeclKind::Interface(i) => &i.nested,
+            DeclKind::Unstructured | DeclKind::Enum(_) => &[],
+        }
+    }
+
+    pub fn constants(&self) -> &[Constant] {
+        match &self.kind {
+            DeclKind::Parcelable(p) | DeclKind::Union(p) => &p.constants,
+            DeclKind::Interface(i) => &i.constants,
+            DeclKind::Unstructured | DeclKind::Enum(_) => &[],
+        }
+    }
+
+    pub fn annotation(&self, name: &str) -> Option<&Annotation> {
+        self.annotations.iter().find(|a| a.name == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Parcelable {
+    pub fields: Vec<Field>,
+    pub constants: Vec<Constant>,
+    pub nested: Vec<Decl>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Field {
+    pub ty: TypeRef,
+    pub name: String,
+    pub default: Option<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Constant {
+    pub ty: TypeRef,
+    pub name: String,
+    pub value: Expr,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Enum {
+    pub enumerators: Vec<(String, Option<Expr>)>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Interface {
+    pub oneway: bool,
+    pub methods: Vec<Method>,
+    pub constants: Vec<Constant>,
+    pub nested: Vec<Decl>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Method {
+    pub oneway: bool,
+    pub ret: TypeRef,
+    pub name: String,
+    pub args: Vec<Arg>,
+    pub id: Option<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    In,
+    Out,
+    InOut,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Arg {
+    pub direction: Direction,
+    pub ty: TypeRef,
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TypeRef {
+    /// dotted name as written, `int`, `List`, `Foo.Bar`, `a.b.Foo`
+    pub name: String,
+    pub generics: Vec<TypeRef>,
+    pub array: Option<ArrayKind>,
+    pub annotations: Vec<Annotation>,
+}
+
+impl TypeRef {
+    pub fn is_nullable(&self) -> bool {
+        self.annotations.iter().any(|a| a.name == "nullable")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum ArrayKind {
+    /// `T[]`
+    Dynamic,
+    /// `T[N]`, `T[N][M]`
+    Fixed(Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UnaryOp {
+    Neg,
+    Plus,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Expr {
+    Int(String),
+    Float(String),
+    Bool(bool),
+    Str(String),
+    Char(char),
+    /// reference to a constant or an enumerator, possibly qualified
+    Ident(String),
+    Array(Vec<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+}
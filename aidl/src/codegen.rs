@@ -0,0 +1,1007 @@
+//! Rust code emitted for the resolved declarations.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+    ast::*,
+    error::{Error, Result},
+    resolve::{Backing, ResolvedType, Resolver, Scope, Type, Value},
+};
+
+const ALLOWED_LINTS: &str = "#[allow(non_snake_case, non_upper_case_globals, non_camel_case_types, \
+     unused_imports, unused_variables, unused_mut, dead_code, clippy::all)]";
+
+pub(crate) struct Generator<'r, 'a> {
+    resolver: &'r Resolver<'a>,
+    krate: &'r str,
+}
+
+#[derive(Default)]
+struct ModuleTree {
+    children: BTreeMap<String, ModuleTree>,
+    items: Vec<String>,
+}
+
+impl ModuleTree {
+    fn render(&self, out: &mut String) {
+        for item in &self.items {
+            out.push_str(item);
+            out.push('\n');
+        }
+        for (name, child) in &self.children {
+            let _ = writeln!(out, "pub mod {} {{", ident(name));
+            child.render(out);
+            out.push_str("}\n");
+        }
+    }
+}
+
+impl<'r, 'a> Generator<'r, 'a> {
+    pub fn new(resolver: &'r Resolver<'a>, krate: &'r str) -> Self {
+        Self { resolver, krate }
+    }
+
+    pub fn generate(&self) -> Result<String> {
+        let mut tree = ModuleTree::default();
+
+        for (fqn, info) in &self.resolver.decls {
+            // nested declarations are rendered inside their parent
+            if info.scopes.len() > 1 || matches!(info.decl.kind, DeclKind::Unstructured) {
+                continue;
+            }
+
+            let package = &info.module[..info.module.len() - 1];
+            let node = package.iter().fold(&mut tree, |node, name| {
+                node.children.entry(name.clone()).or_default()
+            });
+            node.items.push(self.decl(fqn)?);
+        }
+
+        let mut out = String::from("// Generated by binder-rs-aidl, do not edit.\n\n");
+        tree.render(&mut out);
+        Ok(reindent(&out))
+    }
+
+    fn decl(&self, fqn: &str) -> Result<String> {
+        let info = self.resolver.decl(fqn);
+        let scope = Scope {
+            doc: info.doc,
+            scopes: &info.scopes,
+        };
+        let depth = info.module.len();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "{ALLOWED_LINTS}");
+        let _ = writeln!(out, "pub mod {} {{", info.decl.name);
+        let _ = writeln!(
+            out,
+            "use {krate}::{{error::BinderError, parcel::{{Parcel, parcelable::*}}, \
+             service::{{BinderService, CallingContext, Service, Transaction, TransactionFlag}}, \
+             status::Status}};",
+            krate = self.krate
+        );
+        out.push('\n');
+
+        for constant in info.decl.constants() {
+            let value = self.resolver.member_value(fqn, &constant.name)?;
+            let ty = self.resolver.resolve_type(&constant.ty, scope)?;
+            let (ty, value) = self.constant(&ty.ty, &value)?;
+            let _ = writeln!(out, "pub const {}: {ty} = {value};", ident(&constant.name));
+        }
+        if !info.decl.constants().is_empty() {
+            out.push('\n');
+        }
+
+        match &info.decl.kind {
+            DeclKind::Parcelable(p) => self.parcelable(&mut out, fqn, p, scope, depth)?,
+            DeclKind::Union(p) => self.union(&mut out, fqn, p, scope, depth)?,
+            DeclKind::Enum(e) => self.enumeration(&mut out, fqn, e)?,
+            DeclKind::Interface(i) => self.interface(&mut out, fqn, i, scope, depth)?,
+            DeclKind::Unstructured => {}
+        }
+
+        for nested in info.decl.nested() {
+            if !matches!(nested.kind, DeclKind::Unstructured) {
+                out.push('\n');
+                out.push_str(&self.decl(&format!("{fqn}.{}", nested.name))?);
+            }
+        }
+
+        out.push_str("}\n");
+        Ok(out)
+    }
+
+    /// Path of a declared type, from a module `depth` levels under the root.
+    fn path(&self, fqn: &str, depth: usize) -> String {
+        let info = self.resolver.decl(fqn);
+        let mut path = "super::".repeat(depth);
+        for name in &info.module {
+            path += &ident(name);
+            path += "::";
+        }
+        path + &info.decl.name
+    }
+
+    fn owned_type(&self, ty: &Type, depth: usize) -> String {
+        match ty {
+            Type::Void => "()".to_owned(),
+            Type::Boolean => "bool".to_owned(),
+            Type::Byte => "i8".to_owned(),
+            Type::Char => "u16".to_owned(),
+            Type::Int => "i32".to_owned(),
+            Type::Long => "i64".to_owned(),
+            Type::Float => "f32".to_owned(),
+            Type::Double => "f64".to_owned(),
+            Type::String => "String".to_owned(),
+            Type::Array(e) => format!("Vec<{}>", self.element_type(e, depth)),
+            Type::FixedArray(e, len) => format!("[{}; {len}]", self.element_type(e, depth)),
+            Type::List(e) => format!("Vec<{}>", self.rust_type(e, depth)),
+            Type::Map(v) => format!(
+                "std::collections::HashMap<String, {}>",
+                self.rust_type(v, depth)
+            ),
+            Type::Parcelable(fqn) | Type::Union(fqn) | Type::Enum(fqn, _) => self.path(fqn, depth),
+        }
+    }
+
+    /// Byte arrays are `u8` buffers.
+    fn element_type(&self, ty: &Type, depth: usize) -> String {
+        match ty {
+            Type::Byte => "u8".to_owned(),
+            ty => self.owned_type(ty, depth),
+        }
+    }
+
+    fn rust_type(&self, ty: &ResolvedType, depth: usize) -> String {
+        let owned = self.owned_type(&ty.ty, depth);
+        if ty.nullable {
+            format!("Option<{owned}>")
+        } else {
+            owned
+        }
+    }
+
+    /// Type of an `in` argument: primitives by value, the rest borrowed.
+    fn in_arg_type(&self, ty: &ResolvedType, depth: usize) -> String {
+        let borrowed = match &ty.ty {
+            t if t.is_primitive() => return self.owned_type(t, depth),
+            Type::String => "&str".to_owned(),
+            Type::Array(e) => format!("&[{}]", self.element_type(e, depth)),
+            Type::List(e) => format!("&[{}]", self.rust_type(e, depth)),
+            t => format!("&{}", self.owned_type(t, depth)),
+        };
+        if ty.nullable {
+            format!("Option<{borrowed}>")
+        } else {
+            borrowed
+        }
+    }
+
+    /// Borrow an owned value as an `in` argument.
+    fn borrow_in_arg(ty: &ResolvedType, var: &str) -> String {
+        match &ty.ty {
+            t if t.is_primitive() => var.to_owned(),
+            Type::String | Type::Array(_) | Type::List(_) if ty.nullable => {
+                format!("{var}.as_deref()")
+            }
+            _ if ty.nullable => format!("{var}.as_ref()"),
+            _ => format!("&{var}"),
+        }
+    }
+
+    fn default_value(ty: &Type, nullable: bool) -> String {
+        match ty {
+            _ if nullable => "None".to_owned(),
+            Type::FixedArray(e, _) => {
+                format!("std::array::from_fn(|_| {})", Self::default_value(e, false))
+            }
+            _ => "Default::default()".to_owned(),
+        }
+    }
+
+    /// Expression of the owned type of `ty` holding `value`.
+    fn literal(&self, value: &Value, ty: &Type, depth: usize, element: bool) -> Result<String> {
+        let mismatch = || Error::Invalid(format!("can't assign {value:?} to {ty:?}"));
+
+        Ok(match ty {
+            Type::Boolean => match value {
+                Value::Bool(b) => b.to_string(),
+                _ => return Err(mismatch()),
+            },
+            Type::Byte if element => (value.as_int()? as u8).to_string(),
+            Type::Byte => (value.as_int()? as i8).to_string(),
+            Type::Char => (value.as_int()? as u16).to_string(),
+            Type::Int => (value.as_int()? as i32).to_string(),
+            Type::Long => (value.as_int()? as i64).to_string(),
+            Type::Float => float_literal(value, "f32")?,
+            Type::Double => float_literal(value, "f64")?,
+            Type::String => match value {
+                Value::Str(s) => format!("{s:?}.to_owned()"),
+                _ => return Err(mismatch()),
+            },
+            Type::Enum(fqn, backing) => {
+                let v = value.as_int()?;
+                let v = match backing {
+                    Backing::Byte => (v as i8).to_string(),
+                    Backing::Int => (v as i32).to_string(),
+                    Backing::Long => (v as i64).to_string(),
+                };
+                format!("{}({v})", self.path(fqn, depth))
+            }
+            Type::Array(e) => {
+                let Value::Array(items) = value else {
+                    return Err(mismatch());
+                };
+                let items = items
+                    .iter()
+                    .map(|item| self.literal(item, e, depth, true))
+                    .collect::<Result<Vec<_>>>()?;
+                format!("vec![{}]", items.join(", "))
+            }
+            Type::List(e) => {
+                let Value::Array(items) = value else {
+                    return Err(mismatch());
+                };
+                let items = items
+                    .iter()
+                    .map(|item| {
+                        let item = self.literal(item, &e.ty, depth, true)?;
+                        Ok(if e.nullable {
+                            format!("Some({item})")
+                        } else {
+                            item
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                format!("vec![{}]", items.join(", "))
+            }
+            Type::FixedArray(e, len) => {
+                let Value::Array(items) = value else {
+                    return Err(mismatch());
+                };
+                if items.len() != *len {
+                    return Err(mismatch());
+                }
+                let items = items
+                    .iter()
+                    .map(|item| self.literal(item, e, depth, true))
+                    .collect::<Result<Vec<_>>>()?;
+                format!("[{}]", items.join(", "))
+            }
+            Type::Void | Type::Map(_) | Type::Parcelable(_) | Type::Union(_) => {
+                return Err(mismatch());
+            }
+        })
+    }
+
+    /// Type and value of a `const`.
+    fn constant(&self, ty: &Type, value: &Value) -> Result<(&'static str, String)> {
+        Ok(match ty {
+            Type::String => match value {
+                Value::Str(s) => ("&str", format!("{s:?}")),
+                _ => return Err(Error::Invalid(format!("can't assign {value:?} to String"))),
+            },
+            Type::Boolean => ("bool", self.literal(value, ty, 0, false)?),
+            Type::Byte => ("i8", self.literal(value, ty, 0, false)?),
+            Type::Char => ("u16", self.literal(value, ty, 0, false)?),
+            Type::Int => ("i32", self.literal(value, ty, 0, false)?),
+            Type::Long => ("i64", self.literal(value, ty, 0, false)?),
+            Type::Float => ("f32", self.literal(value, ty, 0, false)?),
+            Type::Double => ("f64", self.literal(value, ty, 0, false)?),
+            ty => return Err(Error::Unsupported(format!("constant of type {ty:?}"))),
+        })
+    }
+
+    fn field_init(
+        &self,
+        field: &Field,
+        ty: &ResolvedType,
+        scope: Scope<'_>,
+        depth: usize,
+    ) -> Result<String> {
+        match &field.default {
+            None => Ok(Self::default_value(&ty.ty, ty.nullable)),
+            Some(expr) => {
+                let value = self.resolver.eval(expr, scope)?;
+                let literal = self.literal(&value, &ty.ty, depth, false)?;
+                Ok(if ty.nullable {
+                    format!("Some({literal})")
+                } else {
+                    literal
+                })
+            }
+        }
+    }
+
+    fn parcelable(
+        &self,
+        out: &mut String,
+        fqn: &str,
+        parcelable: &Parcelable,
+        scope: Scope<'_>,
+        depth: usize,
+    ) -> Result<()> {
+        let name = &self.resolver.decl(fqn).decl.name;
+        let fields = parcelable
+            .fields
+            .iter()
+            .map(|f| Ok((f, self.resolver.resolve_type(&f.ty, scope)?)))
+            .collect::<Result<Vec<_>>>()?;
+        self.check_fixed_size(fqn, &fields)?;
+
+        let _ = writeln!(out, "#[derive(Debug, Clone, PartialEq)]");
+        let _ = writeln!(out, "pub struct {name} {{");
+        for (field, ty) in &fields {
+            let _ = writeln!(
+                out,
+                "pub {}: {},",
+                ident(&snake_case(&field.name)),
+                self.rust_type(ty, depth)
+            );
+        }
+        let _ = writeln!(out, "}}\n");
+
+        let _ = writeln!(out, "impl Default for {name} {{");
+        let _ = writeln!(out, "fn default() -> Self {{");
+        let _ = writeln!(out, "Self {{");
+        for (field, ty) in &fields {
+            let init = self.field_init(field, ty, scope, depth)?;
+            let _ = writeln!(out, "{}: {init},", ident(&snake_case(&field.name)));
+        }
+        let _ = writeln!(out, "}}\n}}\n}}\n");
+
+        let _ = writeln!(out, "impl Parcelable for {name} {{");
+        let _ = writeln!(
+            out,
+            "fn write_to_parcel(&self, parcel: &mut Parcel) -> {}::error::Result<()> {{",
+            self.krate
+        );
+        let _ = writeln!(out, "parcel.sized_write(|subparcel| {{");
+        for (field, _) in &fields {
+            let _ = writeln!(
+                out,
+                "subparcel.write(&self.{})?;",
+                ident(&snake_case(&field.name))
+            );
+        }
+        let _ = writeln!(out, "Ok(())\n}})\n}}\n");
+        let _ = writeln!(
+            out,
+            "fn read_from_parcel(&mut self, parcel: &mut Parcel) -> {}::error::Result<()> {{",
+            self.krate
+        );
+        let _ = writeln!(out, "parcel.sized_read(|subparcel| {{");
+        for (field, _) in &fields {
+            // fields added by a newer version are not sent by older peers
+            let _ = writeln!(
+                out,
+                "if subparcel.has_more_data() {{\nself.{} = subparcel.read()?;\n}}",
+                ident(&snake_case(&field.name))
+            );
+        }
+        let _ = writeln!(out, "Ok(())\n}})\n}}\n}}\n");
+
+        self.parcelable_traits(out, name);
+        Ok(())
+    }
+
+    fn parcelable_traits(&self, out: &mut String, name: &str) {
+        let _ = writeln!(
+            out,
+            "{}::impl_serialize_for_parcelable!({name});",
+            self.krate
+        );
+        let _ = writeln!(
+            out,
+            "{}::impl_deserialize_for_parcelable!({name});",
+            self.krate
+        );
+    }
+
+    /// `@FixedSize` parcelables and unions only hold primitives, enums,
+    /// fixed-size arrays and other `@FixedSize` types, none nullable.
+    /// They are written like any other parcelable.
+    fn check_fixed_size(&self, fqn: &str, fields: &[(&Field, ResolvedType)]) -> Result<()> {
+        if self
+            .resolver
+            .decl(fqn)
+            .decl
+            .annotation("FixedSize")
+            .is_none()
+        {
+            return Ok(());
+        }
+
+        for (field, ty) in fields {
+            if ty.nullable || !self.is_fixed_size(&ty.ty) {
+                return Err(Error::Invalid(format!(
+                    "{fqn}.{}: @FixedSize field of variable size",
+                    field.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn is_fixed_size(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Void | Type::String | Type::Array(_) | Type::List(_) | Type::Map(_) => false,
+            Type::FixedArray(e, _) => self.is_fixed_size(e),
+            Type::Parcelable(fqn) | Type::Union(fqn) => self
+                .resolver
+                .decl(fqn)
+                .decl
+                .annotation("FixedSize")
+                .is_some(),
+            _ => true,
+        }
+    }
+
+    fn union(
+        &self,
+        out: &mut String,
+        fqn: &str,
+        union: &Parcelable,
+        scope: Scope<'_>,
+        depth: usize,
+    ) -> Result<()> {
+        let name = &self.resolver.decl(fqn).decl.name;
+        let fields = union
+            .fields
+            .iter()
+            .map(|f| Ok((f, self.resolver.resolve_type(&f.ty, scope)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let Some((first, first_ty)) = fields.first() else {
+            return Err(Error::Invalid(format!("union '{fqn}' has no field")));
+        };
+        self.check_fixed_size(fqn, &fields)?;
+
+        let _ = writeln!(out, "#[derive(Debug, Clone, PartialEq)]");
+        let _ = writeln!(out, "pub enum {name} {{");
+        for (field, ty) in &fields {
+            let _ = writeln!(
+                out,
+                "{}({}),",
+                upper_camel_case(&field.name),
+                self.rust_type(ty, depth)
+            );
+        }
+        let _ = writeln!(out, "}}\n");
+
+        let _ = writeln!(out, "impl Default for {name} {{");
+        let _ = writeln!(
+            out,
+            "fn default() -> Self {{\nSelf::{}({})\n}}\n}}\n",
+            upper_camel_case(&first.name),
+            self.field_init(first, first_ty, scope, depth)?
+        );
+
+        // the tag is the index of the field
+        let _ = writeln!(out, "impl ParcelableUnion for {name} {{");
+        let _ = writeln!(out, "const FIELD_COUNT: i32 = {};\n", fields.len());
+        let _ = writeln!(out, "fn tag(&self) -> i32 {{\nmatch self {{");
+        for (tag, (field, _)) in fields.iter().enumerate() {
+            let _ = writeln!(out, "Self::{}(_) => {tag},", upper_camel_case(&field.name));
+        }
+        let _ = writeln!(out, "}}\n}}\n");
+        let _ = writeln!(
+            out,
+            "fn write_field(&self, parcel: &mut Parcel) -> {}::error::Result<()> {{",
+            self.krate
+        );
+        let _ = writeln!(out, "match self {{");
+        for (field, _) in &fields {
+            let _ = writeln!(
+                out,
+                "Self::{}(value) => parcel.write(value),",
+                upper_camel_case(&field.name)
+            );
+        }
+        let _ = writeln!(out, "}}\n}}\n");
+        let _ = writeln!(
+            out,
+            "fn read_field(tag: i32, parcel: &mut Parcel) -> {}::error::Result<Self> {{",
+            self.krate
+        );
+        let _ = writeln!(out, "match tag {{");
+        for (tag, (field, _)) in fields.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "{tag} => parcel.read().map(Self::{}),",
+                upper_camel_case(&field.name)
+            );
+        }
+        let _ = writeln!(out, "_ => Err(BinderError::BadValue),");
+        let _ = writeln!(out, "}}\n}}\n}}\n");
+
+        let _ = writeln!(out, "{}::impl_parcelable_for_union!({name});", self.krate);
+        Ok(())
+    }
+
+    fn enumeration(&self, out: &mut String, fqn: &str, enumeration: &Enum) -> Result<()> {
+        let decl = self.resolver.decl(fqn).decl;
+        let name = &decl.name;
+        let backing = match self.resolver.backing(decl)? {
+            Backing::Byte => "i8",
+            Backing::Int => "i32",
+            Backing::Long => "i64",
+        };
+
+        // a newtype keeps the values unknown to this version
+        let _ = writeln!(
+            out,
+            "#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]"
+        );
+        let _ = writeln!(out, "pub struct {name}(pub {backing});\n");
+
+        let _ = writeln!(out, "impl {name} {{");
+        for (enumerator, _) in &enumeration.enumerators {
+            let value = self.resolver.member_value(fqn, enumerator)?.as_int()?;
+            let value = match backing {
+                "i8" => (value as i8).to_string(),
+                "i32" => (value as i32).to_string(),
+                _ => (value as i64).to_string(),
+            };
+            let _ = writeln!(
+                out,
+                "pub const {}: Self = Self({value});",
+                ident(enumerator)
+            );
+        }
+        let _ = writeln!(out, "}}\n");
+
+        // values unknown to this version are kept
+        let _ = write!(
+            out,
+            r#"impl ParcelableEnum for {name} {{
+type Backing = {backing};
+
+fn to_backing(self) -> {backing} {{
+self.0
+}}
+
+fn from_backing(value: {backing}) -> {krate}::error::Result<Self> {{
+Ok(Self(value))
+}}
+}}
+
+{krate}::impl_parcelable_for_enum!({name});
+"#,
+            krate = self.krate
+        );
+        Ok(())
+    }
+
+    fn interface(
+        &self,
+        out: &mut String,
+        fqn: &str,
+        interface: &Interface,
+        scope: Scope<'_>,
+        depth: usize,
+    ) -> Result<()> {
+        let name = &self.resolver.decl(fqn).decl.name;
+        let short = name
+            .strip_prefix('I')
+            .filter(|s| s.starts_with(|c: char| c.is_ascii_uppercase()))
+            .unwrap_or(name);
+        let methods = self.methods(fqn, interface, scope)?;
+
+        let _ = writeln!(out, "pub const DESCRIPTOR: &str = {fqn:?};\n");
+
+        let _ = writeln!(out, "pub mod transactions {{");
+        for method in &methods {
+            let _ = writeln!(
+                out,
+                "pub const {}: u32 = {}::service::Transaction::FirstCall as u32 + {};",
+                ident(&method.ast.name),
+                self.krate,
+                method.id
+            );
+        }
+        let _ = writeln!(out, "}}\n");
+
+        // trait
+        let _ = writeln!(out, "pub trait {name} {{");
+        for method in &methods {
+            let _ = writeln!(out, "{};", self.signature(method, depth));
+        }
+        let _ = writeln!(out, "}}\n");
+
+        // client
+        let _ = writeln!(out, "/// Client of `{fqn}`.");
+        let _ = writeln!(
+            out,
+            "pub struct Bp{short}<'a> {{\nservice: Service<'a>,\n}}\n"
+        );
+        let _ = writeln!(
+            out,
+            "impl<'a> Bp{short}<'a> {{\npub fn new(service: Service<'a>) -> Self {{\nSelf {{ service }}\n}}\n}}\n"
+        );
+        let _ = writeln!(out, "impl {name} for Bp{short}<'_> {{");
+        for method in &methods {
+            self.proxy_method(out, method, depth);
+        }
+        let _ = writeln!(out, "}}\n");
+
+        // server
+        let _ = writeln!(
+            out,
+            "/// Server of `{fqn}`, to register to the service manager."
+        );
+        let _ = writeln!(out, "pub struct Bn{short}<T: {name}>(pub T);\n");
+        let _ = writeln!(out, "impl<T: {name}> BinderService for Bn{short}<T> {{");
+        let _ = writeln!(
+            out,
+            "fn progress_request(&self, _aidl_code: u32, _aidl_data: &mut Parcel, \
+             _aidl_reply: &mut Parcel, _aidl_ctx: &CallingContext, _aidl_flags: TransactionFlag) \
+             -> std::result::Result<(), Status> {{"
+        );
+        let _ = writeln!(out, "_aidl_data.enforce_interface(DESCRIPTOR)?;");
+        let _ = writeln!(out, "match _aidl_code {{");
+        for method in &methods {
+            self.stub_method(out, method, depth);
+        }
+        let _ = writeln!(out, "_ => Err(BinderError::UnknownTransaction.into()),");
+        let _ = writeln!(out, "}}\n}}\n}}");
+        Ok(())
+    }
+
+    fn methods<'m>(
+        &self,
+        fqn: &str,
+        interface: &'m Interface,
+        scope: Scope<'_>,
+    ) -> Result<Vec<MethodInfo<'m>>> {
+        let explicit_ids = interface.methods.iter().filter(|m| m.id.is_some()).count();
+        if explicit_ids != 0 && explicit_ids != interface.methods.len() {
+            return Err(Error::Invalid(format!(
+                "either all or none of the methods of '{fqn}' must have an id"
+            )));
+        }
+
+        let mut methods = Vec::new();
+        for (idx, method) in interface.methods.iter().enumerate() {
+            let context = |e: Error| Error::Invalid(format!("{fqn}.{}: {e}", method.name));
+
+            let id = match &method.id {
+                Some(expr) => {
+                    let id = self.resolver.eval(expr, scope).map_err(context)?.as_int()?;
+                    u32::try_from(id)
+                        .map_err(|_| context(Error::Invalid(format!("bad id {id}"))))?
+                }
+                None => idx as u32,
+            };
+            let ret = self
+                .resolver
+                .resolve_type(&method.ret, scope)
+                .map_err(context)?;
+            let args = method
+                .args
+                .iter()
+                .map(|arg| Ok((arg, self.resolver.resolve_type(&arg.ty, scope)?)))
+                .collect::<Result<Vec<_>>>()
+                .map_err(context)?;
+
+            let oneway = interface.oneway || method.oneway;
+            if oneway
+                && (ret.ty != Type::Void || args.iter().any(|(a, _)| a.direction != Direction::In))
+            {
+                return Err(context(Error::Invalid(
+                    "oneway methods return void and only take in arguments".to_owned(),
+                )));
+            }
+            for (arg, ty) in &args {
+                let out_ok = matches!(ty.ty, Type::Array(_) | Type::FixedArray(..) | Type::List(_))
+                    || matches!(ty.ty, Type::Parcelable(_) | Type::Union(_));
+                if arg.direction != Direction::In && !out_ok {
+                    return Err(context(Error::Invalid(format!(
+                        "'{}' can only be an in argument",
+                        arg.name
+                    ))));
+                }
+            }
+
+            methods.push(MethodInfo {
+                ast: method,
+                id,
+                oneway,
+                ret,
+                args,
+            });
+        }
+        Ok(methods)
+    }
+
+    fn signature(&self, method: &MethodInfo<'_>, depth: usize) -> String {
+        let mut signature = format!("fn {}(&self", ident(&snake_case(&method.ast.name)));
+        for (arg, ty) in &method.args {
+            let ty = match arg.direction {
+                Direction::In => self.in_arg_type(ty, depth),
+                Direction::Out | Direction::InOut => format!("&mut {}", self.rust_type(ty, depth)),
+            };
+            let _ = write!(signature, ", {}: {ty}", arg_name(&arg.name));
+        }
+        let _ = write!(
+            signature,
+            ") -> std::result::Result<{}, Status>",
+            self.rust_type(&method.ret, depth)
+        );
+        signature
+    }
+
+    fn proxy_method(&self, out: &mut String, method: &MethodInfo<'_>, depth: usize) {
+        let _ = writeln!(out, "{} {{", self.signature(method, depth));
+        let _ = writeln!(
+            out,
+            "let mut _aidl_data = self.service.prepare_transaction()?;"
+        );
+        for (arg, ty) in &method.args {
+            let name = arg_name(&arg.name);
+            match arg.direction {
+                Direction::In => {
+                    let _ = writeln!(out, "_aidl_data.write(&{name})?;");
+                }
+                Direction::InOut => {
+                    let _ = writeln!(out, "_aidl_data.write(&*{name})?;");
+                }
+                // the server allocates an array of the same size, lists and
+                // fixed-size arrays have no size on the wire
+                Direction::Out if matches!(ty.ty, Type::Array(_)) => {
+                    let slice = if ty.nullable {
+                        format!("{name}.as_deref()")
+                    } else {
+                        format!("Some({name}.as_slice())")
+                    };
+                    let _ = writeln!(out, "_aidl_data.write_slice_size({slice})?;");
+                }
+                Direction::Out => {}
+            }
+        }
+
+        let code = ident(&method.ast.name);
+        if method.oneway {
+            let _ = writeln!(
+                out,
+                "self.service.transact(transactions::{code}, &mut _aidl_data, TransactionFlag::OneWay)?;"
+            );
+            let _ = writeln!(out, "Ok(())\n}}\n");
+            return;
+        }
+
+        let _ = writeln!(
+            out,
+            "let mut _aidl_reply = self.service.transact(transactions::{code}, &mut _aidl_data, TransactionFlag::empty())?;"
+        );
+        let _ = writeln!(out, "let _aidl_status: Status = _aidl_reply.read()?;");
+        let _ = writeln!(
+            out,
+            "if !_aidl_status.is_ok() {{\nreturn Err(_aidl_status);\n}}"
+        );
+        if method.ret.ty != Type::Void {
+            let _ = writeln!(
+                out,
+                "let _aidl_return: {} = _aidl_reply.read()?;",
+                self.rust_type(&method.ret, depth)
+            );
+        }
+        for (arg, _) in &method.args {
+            if arg.direction != Direction::In {
+                let _ = writeln!(out, "_aidl_reply.read_onto({})?;", arg_name(&arg.name));
+            }
+        }
+        if method.ret.ty != Type::Void {
+            let _ = writeln!(out, "Ok(_aidl_return)\n}}\n");
+        } else {
+            let _ = writeln!(out, "Ok(())\n}}\n");
+        }
+    }
+
+    fn stub_method(&self, out: &mut String, method: &MethodInfo<'_>, depth: usize) {
+        let _ = writeln!(out, "transactions::{} => {{", ident(&method.ast.name));
+
+        let mut call_args = Vec::new();
+        for (arg, ty) in &method.args {
+            let name = arg_name(&arg.name);
+            let rust_type = self.rust_type(ty, depth);
+            match arg.direction {
+                Direction::In => {
+                    let _ = writeln!(out, "let {name}: {rust_type} = _aidl_data.read()?;");
+                    call_args.push(Self::borrow_in_arg(ty, &name));
+                }
+                Direction::InOut => {
+                    let _ = writeln!(out, "let mut {name}: {rust_type} = _aidl_data.read()?;");
+                    call_args.push(format!("&mut {name}"));
+                }
+                Direction::Out => {
+                    let _ = writeln!(
+                        out,
+                        "let mut {name}: {rust_type} = {};",
+                        Self::default_value(&ty.ty, ty.nullable)
+                    );
+                    // only dynamic arrays are sent with their size, lists
+                    // start empty and fixed-size arrays filled with defaults
+                    match (&ty.ty, ty.nullable) {
+                        (Type::Array(_), false) => {
+                            let _ = writeln!(out, "_aidl_data.resize_out_vec(&mut {name})?;");
+                        }
+                        (Type::Array(_), true) => {
+                            let _ =
+                                writeln!(out, "_aidl_data.resize_nullable_out_vec(&mut {name})?;");
+                        }
+                        _ => {}
+                    }
+                    call_args.push(format!("&mut {name}"));
+                }
+            }
+        }
+
+        let _ = writeln!(
+            out,
+            "let _aidl_return = self.0.{}({})?;",
+            ident(&snake_case(&method.ast.name)),
+            call_args.join(", ")
+        );
+        if !method.oneway {
+            if method.ret.ty != Type::Void {
+                let _ = writeln!(out, "_aidl_reply.write(&_aidl_return)?;");
+            }
+            for (arg, _) in &method.args {
+                if arg.direction != Direction::In {
+                    let _ = writeln!(out, "_aidl_reply.write(&{})?;", arg_name(&arg.name));
+                }
+            }
+        }
+        let _ = writeln!(out, "Ok(())\n}}");
+    }
+}
+
+struct MethodInfo<'m> {
+    ast: &'m Method,
+    id: u32,
+    oneway: bool,
+    ret: ResolvedType,
+    args: Vec<(&'m Arg, ResolvedType)>,
+}
+
+fn float_literal(value: &Value, suffix: &str) -> Result<String> {
+    let v = match value {
+        Value::Float(v) => *v,
+        Value::Int(v) => *v as f64,
+        other => return Err(Error::Invalid(format!("expected a number, got {other:?}"))),
+    };
+    Ok(if v.is_nan() {
+        format!("{suffix}::NAN")
+    } else if v.is_infinite() {
+        let sign = if v < 0.0 { "NEG_" } else { "" };
+        format!("{suffix}::{sign}INFINITY")
+    } else if suffix == "f32" {
+        format!("{:?}f32", v as f32)
+    } else {
+        format!("{v:?}f64")
+    })
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+/// A valid Rust identifier for `name`.
+fn ident(name: &str) -> String {
+    match name {
+        "self" | "Self" | "super" | "crate" => format!("{name}_"),
+        name if KEYWORDS.contains(&name) => format!("r#{name}"),
+        name => name.to_owned(),
+    }
+}
+
+/// Argument names can't clash with the generated locals.
+fn arg_name(name: &str) -> String {
+    ident(&snake_case(name))
+}
+
+/// `getFooBar` -> `get_foo_bar`, `URLPath` -> `url_path`
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_lower)
+            {
+                out.push('_');
+            }
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    out
+}
+
+/// `intValue` / `int_value` -> `IntValue`
+fn upper_camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = true;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Indent the generated code by its brackets, one level per line
+/// opening at least one of them.
+fn reindent(code: &str) -> String {
+    let mut out = String::with_capacity(code.len() * 2);
+    // brackets still open for each indent level
+    let mut levels: Vec<usize> = Vec::new();
+
+    for line in code.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            out.push('\n');
+            continue;
+        }
+
+        let mut indent = None;
+        let mut opened_here = false;
+        let mut in_string = false;
+        let mut escaped = false;
+        for c in line.chars() {
+            if in_string {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '{' | '(' | '[' => {
+                    indent.get_or_insert(levels.len());
+                    match levels.last_mut() {
+                        Some(open) if opened_here => *open += 1,
+                        _ => {
+                            levels.push(1);
+                            opened_here = true;
+                        }
+                    }
+                }
+                '}' | ')' | ']' => {
+                    if let Some(open) = levels.last_mut() {
+                        *open -= 1;
+                        if *open == 0 {
+                            levels.pop();
+                            opened_here = false;
+                        }
+                    }
+                }
+                _ => {
+                    indent.get_or_insert(levels.len());
+                    if c == '"' {
+                        in_string = true;
+                    }
+                }
+            }
+        }
+
+        let indent = indent.unwrap_or(levels.len());
+        out.push_str(&"    ".repeat(indent));
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("IoError: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("{line}:{column}: {message}")]
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    #[error("{}: {source}", path.display())]
+    InFile {
+        path: PathBuf,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[error("Unable to find import '{0}' in the include directories")]
+    ImportNotFound(String),
+
+    #[error("Unknown type '{0}'")]
+    UnknownType(String),
+
+    #[error("Unknown constant '{0}'")]
+    UnknownConstant(String),
+
+    #[error("Invalid definition: {0}")]
+    Invalid(String),
+
+    #[error("Not supported yet: {0}")]
+    Unsupported(String),
+}
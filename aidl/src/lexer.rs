@@ -0,0 +1,228 @@
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TokenKind {
+    Ident(String),
+    Int(String),
+    Float(String),
+    Str(String),
+    Char(char),
+    /// Any punctuation, `>>` and `<<` are split into single characters
+    /// so they can close nested generics.
+    Punct(char),
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Token {
+    pub kind: TokenKind,
+    /// byte offset in the source, used to join `<<` / `>>` back
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub(crate) fn tokenize(source: &str) -> Result<Vec<Token>> {
+    Lexer {
+        chars: source.char_indices().collect(),
+        pos: 0,
+        line: 1,
+        column: 1,
+    }
+    .run()
+}
+
+struct Lexer {
+    chars: Vec<(usize, char)>,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Lexer {
+    fn peek(&self, n: usize) -> Option<char> {
+        self.chars.get(self.pos + n).map(|(_, c)| *c)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek(0)?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::Syntax {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    fn run(mut self) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+
+        loop {
+            self.skip_blank()?;
+
+            let offset = self.chars.get(self.pos).map_or(usize::MAX, |(o, _)| *o);
+            let (line, column) = (self.line, self.column);
+            let Some(c) = self.peek(0) else {
+                tokens.push(Token {
+                    kind: TokenKind::Eof,
+                    offset,
+                    line,
+                    column,
+                });
+                return Ok(tokens);
+            };
+
+            let kind = if c.is_ascii_alphabetic() || c == '_' {
+                TokenKind::Ident(self.take_while(|c| c.is_ascii_alphanumeric() || c == '_'))
+            } else if c.is_ascii_digit()
+                || (c == '.' && self.peek(1).is_some_and(|c| c.is_ascii_digit()))
+            {
+                self.number()
+            } else if c == '"' {
+                self.string()?
+            } else if c == '\'' {
+                self.character()?
+            } else {
+                self.bump();
+                TokenKind::Punct(c)
+            };
+
+            tokens.push(Token {
+                kind,
+                offset,
+                line,
+                column,
+            });
+        }
+    }
+
+    fn skip_blank(&mut self) -> Result<()> {
+        loop {
+            match (self.peek(0), self.peek(1)) {
+                (Some(c), _) if c.is_whitespace() => {
+                    self.bump();
+                }
+                (Some('/'), Some('/')) => {
+                    while self.peek(0).is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    self.bump();
+                    self.bump();
+                    loop {
+                        match (self.peek(0), self.peek(1)) {
+                            (Some('*'), Some('/')) => {
+                                self.bump();
+                                self.bump();
+                                break;
+                            }
+                            (Some(_), _) => {
+                                self.bump();
+                            }
+                            (None, _) => return Err(self.error("unterminated comment")),
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.peek(0).filter(|c| f(*c)) {
+            s.push(c);
+            self.bump();
+        }
+        s
+    }
+
+    fn number(&mut self) -> TokenKind {
+        if self.peek(0) == Some('0') && matches!(self.peek(1), Some('x' | 'X')) {
+            self.bump();
+            self.bump();
+            let digits = self.take_while(|c| c.is_ascii_hexdigit());
+            let suffix = self.take_while(|c| matches!(c, 'l' | 'L' | 'u' | '8'));
+            return TokenKind::Int(format!("0x{digits}{suffix}"));
+        }
+
+        let mut s = self.take_while(|c| c.is_ascii_digit());
+        let mut float = false;
+        if self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            float = true;
+            self.bump();
+            s.push('.');
+            s += &self.take_while(|c| c.is_ascii_digit());
+        }
+        if matches!(self.peek(0), Some('e' | 'E')) {
+            float = true;
+            s.push('e');
+            self.bump();
+            if let Some(sign) = self.peek(0).filter(|c| matches!(c, '+' | '-')) {
+                s.push(sign);
+                self.bump();
+            }
+            s += &self.take_while(|c| c.is_ascii_digit());
+        }
+        match self.peek(0) {
+            Some('f' | 'F' | 'd' | 'D') => {
+                self.bump();
+                TokenKind::Float(s)
+            }
+            Some('l' | 'L') if !float => {
+                self.bump();
+                TokenKind::Int(s + "l")
+            }
+            _ if float => TokenKind::Float(s),
+            _ => TokenKind::Int(s),
+        }
+    }
+
+    fn escape(&mut self) -> Result<char> {
+        match self.bump() {
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('r') => Ok('\r'),
+            Some('0') => Ok('\0'),
+            Some(c @ ('\\' | '\'' | '"')) => Ok(c),
+            _ => Err(self.error("unsupported escape sequence")),
+        }
+    }
+
+    fn string(&mut self) -> Result<TokenKind> {
+        self.bump();
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(TokenKind::Str(s)),
+                Some('\\') => s.push(self.escape()?),
+                Some('\n') | None => return Err(self.error("unterminated string literal")),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn character(&mut self) -> Result<TokenKind> {
+        self.bump();
+        let c = match self.bump() {
+            Some('\\') => self.escape()?,
+            Some(c) => c,
+            None => return Err(self.error("unterminated char literal")),
+        };
+        if self.bump() != Some('\'') {
+            return Err(self.error("unterminated char literal"));
+        }
+        Ok(TokenKind::Char(c))
+    }
+}
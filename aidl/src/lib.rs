@@ -0,0 +1,170 @@
+//! Generate Rust clients and servers for `binder-rs` from `.aidl` files.
+//!
+//! Meant to be called from `build.rs`:
+//!
+//! ```no_run
+//! binder_rs_aidl::Builder::new()
+//!     .source("aidl/com/example/IMyService.aidl")
+//!     .include_dir("aidl")
+//!     .generate()
+//!     .unwrap();
+//! ```
+//!
+//! and included in the crate with
+//! `include!(concat!(env!("OUT_DIR"), "/aidl.rs"));`.
+//!
+//! Every declaration `a.b.Foo` becomes a module `a::b::Foo` holding the item
+//! `Foo`, its constants and nested declarations. An interface `IFoo` gives a
+//! trait `IFoo`, a client `BpFoo` over `binder_rs::service::Service` and a
+//! server `BnFoo` implementing `binder_rs::service::BinderService`.
+use std::path::{Path, PathBuf};
+
+use ast::Document;
+pub use error::{Error, Result};
+use resolve::Resolver;
+
+mod ast;
+mod codegen;
+mod error;
+mod lexer;
+mod parser;
+mod resolve;
+
+pub struct Builder {
+    sources: Vec<PathBuf>,
+    include_dirs: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    crate_path: String,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+            include_dirs: Vec::new(),
+            output: None,
+            crate_path: "binder_rs".to_owned(),
+        }
+    }
+
+    /// `.aidl` file to generate.
+    pub fn source(mut self, path: impl AsRef<Path>) -> Self {
+        self.sources.push(path.as_ref().to_owned());
+        self
+    }
+
+    /// Root directory of the imported files: `import a.b.Foo;` is searched
+    /// as `<dir>/a/b/Foo.aidl`. Imported files are generated too.
+    pub fn include_dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.include_dirs.push(path.as_ref().to_owned());
+        self
+    }
+
+    /// Output file, `$OUT_DIR/aidl.rs` by default.
+    pub fn output(mut self, path: impl AsRef<Path>) -> Self {
+        self.output = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Path of the `binder-rs` crate in the generated code, `binder_rs` by default.
+    pub fn crate_path(mut self, path: impl Into<String>) -> Self {
+        self.crate_path = path.into();
+        self
+    }
+
+    /// Generate the code and write it to the output file.
+    pub fn generate(self) -> Result<()> {
+        let output = match &self.output {
+            Some(output) => output.clone(),
+            None => {
+                let out_dir = std::env::var_os("OUT_DIR").ok_or_else(|| {
+                    Error::Invalid("OUT_DIR is not set, use Builder::output".to_owned())
+                })?;
+                PathBuf::from(out_dir).join("aidl.rs")
+            }
+        };
+
+        let (code, files) = self.generate_code()?;
+        for file in files {
+            println!("cargo:rerun-if-changed={}", file.display());
+        }
+        std::fs::write(output, code)?;
+        Ok(())
+    }
+
+    /// Generate the code, and return it with the files it was made from.
+    pub fn generate_code(&self) -> Result<(String, Vec<PathBuf>)> {
+        let mut files = Vec::new();
+        let mut docs = Vec::new();
+        for source in &self.sources {
+            self.load(source, &mut files, &mut docs)?;
+        }
+
+        // imports are loaded until every one of them is found
+        let mut idx = 0;
+        while idx < docs.len() {
+            for import in docs[idx].imports.clone() {
+                if self.is_loaded(&import, &docs) {
+                    continue;
+                }
+                let path = self
+                    .find_import(&import)
+                    .ok_or_else(|| Error::ImportNotFound(import.clone()))?;
+                if !files.contains(&path) {
+                    self.load(&path, &mut files, &mut docs)?;
+                }
+            }
+            idx += 1;
+        }
+
+        let resolver = Resolver::new(&docs)?;
+        let code = codegen::Generator::new(&resolver, &self.crate_path).generate()?;
+        Ok((code, files))
+    }
+
+    fn load(&self, path: &Path, files: &mut Vec<PathBuf>, docs: &mut Vec<Document>) -> Result<()> {
+        let in_file = |e: Error| Error::InFile {
+            path: path.to_owned(),
+            source: Box::new(e),
+        };
+
+        let source = std::fs::read_to_string(path).map_err(|e| in_file(e.into()))?;
+        docs.push(parser::parse(&source).map_err(in_file)?);
+        files.push(path.to_owned());
+        Ok(())
+    }
+
+    fn is_loaded(&self, fqn: &str, docs: &[Document]) -> bool {
+        docs.iter().any(|doc| {
+            doc.decls.iter().any(|decl| {
+                let name = if doc.package.is_empty() {
+                    decl.name.clone()
+                } else {
+                    format!("{}.{}", doc.package, decl.name)
+                };
+                fqn == name || fqn.starts_with(&format!("{name}."))
+            })
+        })
+    }
+
+    /// `a.b.Foo` is in `a/b/Foo.aidl`, `a.b.Foo.Nested` too.
+    fn find_import(&self, fqn: &str) -> Option<PathBuf> {
+        let parts: Vec<&str> = fqn.split('.').collect();
+        (1..=parts.len()).rev().find_map(|len| {
+            let relative = format!("{}.aidl", parts[..len].join("/"));
+            self.include_dirs
+                .iter()
+                .map(|dir| dir.join(&relative))
+                .find(|path| path.is_file())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests;
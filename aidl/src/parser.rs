@@ -0,0 +1,512 @@
+use crate::{
+    ast::*,
+    error::{Error, Result},
+    lexer::{Token, TokenKind, tokenize},
+};
+
+pub(crate) fn parse(source: &str) -> Result<Document> {
+    Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+    }
+    .document()
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &TokenKind {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> &TokenKind {
+        let idx = (self.pos + n).min(self.tokens.len() - 1);
+        &self.tokens[idx].kind
+    }
+
+    fn next(&mut self) -> TokenKind {
+        let kind = self.peek().clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        kind
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        let token = &self.tokens[self.pos];
+        Error::Syntax {
+            line: token.line,
+            column: token.column,
+            message: message.into(),
+        }
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        *self.peek() == TokenKind::Punct(c)
+    }
+
+    /// Two adjacent punctuation characters, like `<<` or `==`.
+    fn is_punct2(&self, a: char, b: char) -> bool {
+        let (Some(first), Some(second)) =
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1))
+        else {
+            return false;
+        };
+        first.kind == TokenKind::Punct(a)
+            && second.kind == TokenKind::Punct(b)
+            && first.offset + 1 == second.offset
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        if self.is_punct(c) {
+            self.next();
+            return true;
+        }
+        false
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<()> {
+        if !self.eat_punct(c) {
+            return Err(self.error(format!("expected '{c}', found {:?}", self.peek())));
+        }
+        Ok(())
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), TokenKind::Ident(s) if s == keyword)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.next();
+            return true;
+        }
+        false
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.next() {
+            TokenKind::Ident(s) => Ok(s),
+            other => {
+                self.pos -= 1;
+                Err(self.error(format!("expected identifier, found {other:?}")))
+            }
+        }
+    }
+
+    /// `a.b.C`
+    fn qualified_name(&mut self) -> Result<String> {
+        let mut name = self.ident()?;
+        while self.is_punct('.') && matches!(self.peek_nth(1), TokenKind::Ident(_)) {
+            self.next();
+            name.push('.');
+            name += &self.ident()?;
+        }
+        Ok(name)
+    }
+
+    fn document(mut self) -> Result<Document> {
+        let mut package = String::new();
+        let mut imports = Vec::new();
+        let mut decls = Vec::new();
+
+        if self.eat_keyword("package") {
+            package = self.qualified_name()?;
+            self.expect_punct(';')?;
+        }
+        while self.eat_keyword("import") {
+            imports.push(self.qualified_name()?);
+            self.expect_punct(';')?;
+        }
+        while *self.peek() != TokenKind::Eof {
+            let annotations = self.annotations()?;
+            decls.push(self.decl(annotations)?);
+        }
+
+        Ok(Document {
+            package,
+            imports,
+            decls,
+        })
+    }
+
+    fn annotations(&mut self) -> Result<Vec<Annotation>> {
+        let mut annotations = Vec::new();
+        while self.eat_punct('@') {
+            let name = self.qualified_name()?;
+            let mut params = Vec::new();
+            if self.eat_punct('(') {
+                while !self.eat_punct(')') {
+                    if matches!(self.peek(), TokenKind::Ident(_)) && self.is_next_assign() {
+                        let key = self.ident()?;
+                        self.expect_punct('=')?;
+                        params.push((key, self.expr()?));
+                    } else {
+                        params.push(("value".to_owned(), self.expr()?));
+                    }
+                    if !self.is_punct(')') {
+                        self.expect_punct(',')?;
+                    }
+                }
+            }
+            annotations.push(Annotation { name, params });
+        }
+        Ok(annotations)
+    }
+
+    fn is_next_assign(&self) -> bool {
+        *self.peek_nth(1) == TokenKind::Punct('=') && *self.peek_nth(2) != TokenKind::Punct('=')
+    }
+
+    fn is_decl_start(&self) -> bool {
+        match self.peek() {
+            TokenKind::Ident(s) => match s.as_str() {
+                "parcelable" | "union" | "enum" | "interface" => true,
+                "oneway" => matches!(self.peek_nth(1), TokenKind::Ident(s) if s == "interface"),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn decl(&mut self, annotations: Vec<Annotation>) -> Result<Decl> {
+        let keyword = self.ident()?;
+        let (name, kind) = match keyword.as_str() {
+            "parcelable" => {
+                let name = self.qualified_name()?;
+                if self.is_punct('<') {
+                    return Err(Error::Unsupported(format!("generic parcelable '{name}'")));
+                }
+                if self.eat_punct('{') {
+                    (name, DeclKind::Parcelable(self.parcelable_body()?))
+                } else {
+                    // `parcelable Foo cpp_header "foo.h";`
+                    while !self.eat_punct(';') {
+                        if self.next() == TokenKind::Eof {
+                            return Err(self.error("expected ';'"));
+                        }
+                    }
+                    (name, DeclKind::Unstructured)
+                }
+            }
+            "union" => {
+                let name = self.ident()?;
+                self.expect_punct('{')?;
+                (name, DeclKind::Union(self.parcelable_body()?))
+            }
+            "enum" => {
+                let name = self.ident()?;
+                self.expect_punct('{')?;
+                (name, DeclKind::Enum(self.enum_body()?))
+            }
+            "oneway" | "interface" => {
+                let oneway = keyword == "oneway";
+                if oneway && !self.eat_keyword("interface") {
+                    return Err(self.error("expected 'interface'"));
+                }
+                let name = self.ident()?;
+                self.expect_punct('{')?;
+                (name, DeclKind::Interface(self.interface_body(oneway)?))
+            }
+            _ => {
+                self.pos -= 1;
+                return Err(self.error(format!("unexpected '{keyword}'")));
+            }
+        };
+
+        Ok(Decl {
+            name,
+            annotations,
+            kind,
+        })
+    }
+
+    fn parcelable_body(&mut self) -> Result<Parcelable> {
+        let mut parcelable = Parcelable {
+            fields: Vec::new(),
+            constants: Vec::new(),
+            nested: Vec::new(),
+        };
+
+        while !self.eat_punct('}') {
+            let annotations = self.annotations()?;
+            if self.is_decl_start() {
+                parcelable.nested.push(self.decl(annotations)?);
+            } else if self.eat_keyword("const") {
+                parcelable.constants.push(self.constant(annotations)?);
+            } else {
+                let ty = self.type_ref(annotations)?;
+                let name = self.ident()?;
+                let default = if self.eat_punct('=') {
+                    Some(self.expr()?)
+                } else {
+                    None
+                };
+                self.expect_punct(';')?;
+                parcelable.fields.push(Field { ty, name, default });
+            }
+        }
+        Ok(parcelable)
+    }
+
+    fn enum_body(&mut self) -> Result<Enum> {
+        let mut enumerators = Vec::new();
+        while !self.eat_punct('}') {
+            self.annotations()?;
+            let name = self.ident()?;
+            let value = if self.eat_punct('=') {
+                Some(self.expr()?)
+            } else {
+                None
+            };
+            enumerators.push((name, value));
+            if !self.is_punct('}') {
+                self.expect_punct(',')?;
+            }
+        }
+        // optional trailing ';'
+        self.eat_punct(';');
+        Ok(Enum { enumerators })
+    }
+
+    fn interface_body(&mut self, oneway: bool) -> Result<Interface> {
+        let mut interface = Interface {
+            oneway,
+            methods: Vec::new(),
+            constants: Vec::new(),
+            nested: Vec::new(),
+        };
+
+        while !self.eat_punct('}') {
+            let annotations = self.annotations()?;
+            if self.is_decl_start() {
+                interface.nested.push(self.decl(annotations)?);
+            } else if self.eat_keyword("const") {
+                interface.constants.push(self.constant(annotations)?);
+            } else {
+                interface.methods.push(self.method(annotations)?);
+            }
+        }
+        Ok(interface)
+    }
+
+    fn constant(&mut self, annotations: Vec<Annotation>) -> Result<Constant> {
+        let ty = self.type_ref(annotations)?;
+        let name = self.ident()?;
+        self.expect_punct('=')?;
+        let value = self.expr()?;
+        self.expect_punct(';')?;
+        Ok(Constant { ty, name, value })
+    }
+
+    fn method(&mut self, mut annotations: Vec<Annotation>) -> Result<Method> {
+        let oneway = self.eat_keyword("oneway");
+        annotations.extend(self.annotations()?);
+        let ret = self.type_ref(annotations)?;
+        let name = self.ident()?;
+
+        self.expect_punct('(')?;
+        let mut args = Vec::new();
+        while !self.eat_punct(')') {
+            let mut annotations = self.annotations()?;
+            let direction = if self.eat_keyword("in") {
+                Direction::In
+            } else if self.eat_keyword("out") {
+                Direction::Out
+            } else if self.eat_keyword("inout") {
+                Direction::InOut
+            } else {
+                Direction::In
+            };
+            annotations.extend(self.annotations()?);
+            let ty = self.type_ref(annotations)?;
+            let name = self.ident()?;
+            args.push(Arg {
+                direction,
+                ty,
+                name,
+            });
+            if !self.is_punct(')') {
+                self.expect_punct(',')?;
+            }
+        }
+
+        let id = if self.eat_punct('=') {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+        self.expect_punct(';')?;
+
+        Ok(Method {
+            oneway,
+            ret,
+            name,
+            args,
+            id,
+        })
+    }
+
+    fn type_ref(&mut self, mut annotations: Vec<Annotation>) -> Result<TypeRef> {
+        annotations.extend(self.annotations()?);
+        let name = self.qualified_name()?;
+
+        let mut generics = Vec::new();
+        if self.eat_punct('<') {
+            loop {
+                let annotations = self.annotations()?;
+                generics.push(self.type_ref(annotations)?);
+                if self.eat_punct('>') {
+                    break;
+                }
+                self.expect_punct(',')?;
+            }
+        }
+
+        let mut array = None;
+        if self.is_punct('[') {
+            if *self.peek_nth(1) == TokenKind::Punct(']') {
+                self.next();
+                self.next();
+                array = Some(ArrayKind::Dynamic);
+            } else {
+                let mut dimensions = Vec::new();
+                while self.eat_punct('[') {
+                    dimensions.push(self.expr()?);
+                    self.expect_punct(']')?;
+                }
+                array = Some(ArrayKind::Fixed(dimensions));
+            }
+        }
+
+        Ok(TypeRef {
+            name,
+            generics,
+            array,
+            annotations,
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let cond = self.binary(0)?;
+        if self.eat_punct('?') {
+            let a = self.expr()?;
+            self.expect_punct(':')?;
+            let b = self.expr()?;
+            return Ok(Expr::Ternary(Box::new(cond), Box::new(a), Box::new(b)));
+        }
+        Ok(cond)
+    }
+
+    /// Binary operator at the current position, with its precedence
+    /// and token length.
+    fn binary_op(&self) -> Option<(BinaryOp, u8, usize)> {
+        let two = [
+            ('|', '|', BinaryOp::Or, 1),
+            ('&', '&', BinaryOp::And, 2),
+            ('=', '=', BinaryOp::Eq, 6),
+            ('!', '=', BinaryOp::Ne, 6),
+            ('<', '=', BinaryOp::Le, 7),
+            ('>', '=', BinaryOp::Ge, 7),
+            ('<', '<', BinaryOp::Shl, 8),
+            ('>', '>', BinaryOp::Shr, 8),
+        ];
+        for (a, b, op, prec) in two {
+            if self.is_punct2(a, b) {
+                return Some((op, prec, 2));
+            }
+        }
+
+        let TokenKind::Punct(c) = self.peek() else {
+            return None;
+        };
+        let (op, prec) = match c {
+            '|' => (BinaryOp::BitOr, 3),
+            '^' => (BinaryOp::BitXor, 4),
+            '&' => (BinaryOp::BitAnd, 5),
+            '<' => (BinaryOp::Lt, 7),
+            '>' => (BinaryOp::Gt, 7),
+            '+' => (BinaryOp::Add, 9),
+            '-' => (BinaryOp::Sub, 9),
+            '*' => (BinaryOp::Mul, 10),
+            '/' => (BinaryOp::Div, 10),
+            '%' => (BinaryOp::Rem, 10),
+            _ => return None,
+        };
+        Some((op, prec, 1))
+    }
+
+    fn binary(&mut self, min_prec: u8) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        while let Some((op, prec, len)) = self.binary_op() {
+            if prec <= min_prec {
+                break;
+            }
+            for _ in 0..len {
+                self.next();
+            }
+            let rhs = self.binary(prec)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let op = match self.peek() {
+            TokenKind::Punct('-') => UnaryOp::Neg,
+            TokenKind::Punct('+') => UnaryOp::Plus,
+            TokenKind::Punct('!') => UnaryOp::Not,
+            TokenKind::Punct('~') => UnaryOp::BitNot,
+            _ => return self.primary(),
+        };
+        self.next();
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.peek().clone() {
+            TokenKind::Int(s) => {
+                self.next();
+                Ok(Expr::Int(s))
+            }
+            TokenKind::Float(s) => {
+                self.next();
+                Ok(Expr::Float(s))
+            }
+            TokenKind::Str(s) => {
+                self.next();
+                Ok(Expr::Str(s))
+            }
+            TokenKind::Char(c) => {
+                self.next();
+                Ok(Expr::Char(c))
+            }
+            TokenKind::Ident(s) if s == "true" || s == "false" => {
+                self.next();
+                Ok(Expr::Bool(s == "true"))
+            }
+            TokenKind::Ident(_) => Ok(Expr::Ident(self.qualified_name()?)),
+            TokenKind::Punct('(') => {
+                self.next();
+                let expr = self.expr()?;
+                self.expect_punct(')')?;
+                Ok(expr)
+            }
+            TokenKind::Punct('{') => {
+                self.next();
+                let mut items = Vec::new();
+                while !self.eat_punct('}') {
+                    items.push(self.expr()?);
+                    if !self.is_punct('}') {
+                        self.expect_punct(',')?;
+                    }
+                }
+                Ok(Expr::Array(items))
+            }
+            other => Err(self.error(format!("expected expression, found {other:?}"))),
+        }
+    }
+}
@@ -0,0 +1,576 @@
+//! Name resolution and constant evaluation over all the parsed documents.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+};
+
+use crate::{
+    ast::*,
+    error::{Error, Result},
+};
+
+/// Backing type of an AIDL enum, `@Backing(type="...")`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Backing {
+    Byte,
+    Int,
+    Long,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Type {
+    Void,
+    Boolean,
+    Byte,
+    Char,
+    Int,
+    Long,
+    Float,
+    Double,
+    String,
+    /// `T[]`
+    Array(Box<Type>),
+    /// `T[N]`, the outer dimension first
+    FixedArray(Box<Type>, usize),
+    /// `List<T>`, the elements may be `@nullable`
+    List(Box<ResolvedType>),
+    /// `Map<String, V>`, the values may be `@nullable`
+    Map(Box<ResolvedType>),
+    Parcelable(String),
+    Union(String),
+    Enum(String, Backing),
+}
+
+impl Type {
+    pub fn is_array(&self) -> bool {
+        matches!(self, Type::Array(_) | Type::FixedArray(..) | Type::List(_))
+    }
+
+    pub fn is_primitive(&self) -> bool {
+        matches!(
+            self,
+            Type::Boolean
+                | Type::Byte
+                | Type::Char
+                | Type::Int
+                | Type::Long
+                | Type::Float
+                | Type::Double
+                | Type::Enum(..)
+        )
+    }
+}
+
+/// Resolved type with its `@nullable` annotation.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ResolvedType {
+    pub ty: Type,
+    pub nullable: bool,
+}
+
+/// Value of a constant expression.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    Char(char),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_int(&self) -> Result<i128> {
+        match self {
+            Value::Int(v) => Ok(*v),
+            Value::Char(c) => Ok(*c as i128),
+            Value::Bool(b) => Ok(*b as i128),
+            other => Err(Error::Invalid(format!(
+                "expected an integer, got {other:?}"
+            ))),
+        }
+    }
+
+    fn as_float(&self) -> Result<f64> {
+        match self {
+            Value::Float(v) => Ok(*v),
+            other => Ok(other.as_int()? as f64),
+        }
+    }
+
+    fn as_bool(&self) -> Result<bool> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            other => Ok(other.as_int()? != 0),
+        }
+    }
+}
+
+/// A declaration and where it lives.
+pub(crate) struct DeclInfo<'a> {
+    pub decl: &'a Decl,
+    pub doc: &'a Document,
+    /// fully qualified names of the enclosing declarations, then this one
+    pub scopes: Vec<String>,
+    /// Rust module path of the declaration, which contains the item
+    /// of the same name
+    pub module: Vec<String>,
+}
+
+/// Lookup context of a name: the document and the declarations around it.
+#[derive(Clone, Copy)]
+pub(crate) struct Scope<'s> {
+    pub doc: &'s Document,
+    pub scopes: &'s [String],
+}
+
+pub(crate) struct Resolver<'a> {
+    pub decls: BTreeMap<String, DeclInfo<'a>>,
+    values: RefCell<HashMap<(String, String), Value>>,
+    in_progress: RefCell<HashSet<(String, String)>>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(docs: &'a [Document]) -> Result<Self> {
+        let mut decls = BTreeMap::new();
+        for doc in docs {
+            for decl in &doc.decls {
+                let fqn = qualify(&doc.package, &decl.name);
+                let module = doc
+                    .package
+                    .split('.')
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_owned)
+                    .collect();
+                register(&mut decls, doc, decl, fqn, Vec::new(), module)?;
+            }
+        }
+
+        Ok(Self {
+            decls,
+            values: RefCell::default(),
+            in_progress: RefCell::default(),
+        })
+    }
+
+    pub fn decl(&self, fqn: &str) -> &DeclInfo<'a> {
+        &self.decls[fqn]
+    }
+
+    /// Find the fully qualified name of a declaration, the way Java resolves
+    /// it: nested types first, then imports, the current package and at last
+    /// a fully qualified name.
+    pub fn lookup_decl(&self, name: &str, scope: Scope<'_>) -> Option<String> {
+        for enclosing in scope.scopes.iter().rev() {
+            let candidate = format!("{enclosing}.{name}");
+            if self.decls.contains_key(&candidate) {
+                return Some(candidate);
+            }
+        }
+
+        let first = name.split('.').next().unwrap_or(name);
+        for import in &scope.doc.imports {
+            if import == first || import.ends_with(&format!(".{first}")) {
+                let candidate = format!("{import}{}", &name[first.len()..]);
+                if self.decls.contains_key(&candidate) {
+                    return Some(candidate);
+                }
+            }
+        }
+
+        let candidate = qualify(&scope.doc.package, name);
+        if self.decls.contains_key(&candidate) {
+            return Some(candidate);
+        }
+
+        self.decls.contains_key(name).then(|| name.to_owned())
+    }
+
+    pub fn resolve_type(&self, ty: &TypeRef, scope: Scope<'_>) -> Result<ResolvedType> {
+        let element = TypeRef {
+            array: None,
+            ..ty.clone()
+        };
+        let mut resolved = self.resolve_element(&element, scope)?;
+
+        match &ty.array {
+            None => {}
+            Some(ArrayKind::Dynamic) => resolved = Type::Array(Box::new(resolved)),
+            Some(ArrayKind::Fixed(dimensions)) => {
+                for dimension in dimensions.iter().rev() {
+                    let len = self.eval(dimension, scope)?.as_int()?;
+                    let len = usize::try_from(len).map_err(|_| {
+                        Error::Invalid(format!("bad array size {len} for '{}'", ty.name))
+                    })?;
+                    resolved = Type::FixedArray(Box::new(resolved), len);
+                }
+            }
+        }
+
+        if matches!(resolved, Type::Array(ref e) if **e == Type::Void)
+            || matches!(resolved, Type::List(ref e) if e.ty == Type::Void)
+        {
+            return Err(Error::Invalid("array of void".to_owned()));
+        }
+
+        let nullable = ty.is_nullable();
+        if nullable && resolved.is_primitive() {
+            return Err(Error::Invalid(format!(
+                "primitive type '{}' can't be @nullable",
+                ty.name
+            )));
+        }
+
+        Ok(ResolvedType {
+            ty: resolved,
+            nullable,
+        })
+    }
+
+    fn resolve_element(&self, ty: &TypeRef, scope: Scope<'_>) -> Result<Type> {
+        let no_generics = |t: Type| {
+            if ty.generics.is_empty() {
+                Ok(t)
+            } else {
+                Err(Error::Invalid(format!(
+                    "'{}' doesn't take type arguments",
+                    ty.name
+                )))
+            }
+        };
+
+        match ty.name.as_str() {
+            "void" => no_generics(Type::Void),
+            "boolean" => no_generics(Type::Boolean),
+            "byte" => no_generics(Type::Byte),
+            "char" => no_generics(Type::Char),
+            "int" => no_generics(Type::Int),
+            "long" => no_generics(Type::Long),
+            "float" => no_generics(Type::Float),
+            "double" => no_generics(Type::Double),
+            "String" | "java.lang.String" => no_generics(Type::String),
+            "List" | "java.util.List" => {
+                let [element] = ty.generics.as_slice() else {
+                    return Err(Error::Unsupported(
+                        "List without a type argument".to_owned(),
+                    ));
+                };
+                let element = self.resolve_type(element, scope)?;
+                if element.ty.is_primitive() || element.ty.is_array() {
+                    return Err(Error::Invalid(format!(
+                        "List<{:?}>, use an array",
+                        element.ty
+                    )));
+                }
+                Ok(Type::List(Box::new(element)))
+            }
+            "Map" | "java.util.Map" => {
+                let [key, value] = ty.generics.as_slice() else {
+                    return Err(Error::Unsupported("Map without type arguments".to_owned()));
+                };
+                let key = self.resolve_type(key, scope)?;
+                if key.ty != Type::String || key.nullable {
+                    return Err(Error::Unsupported(format!("Map key {:?}", key.ty)));
+                }
+                let value = self.resolve_type(value, scope)?;
+                Ok(Type::Map(Box::new(value)))
+            }
+            name @ ("CharSequence"
+            | "IBinder"
+            | "FileDescriptor"
+            | "ParcelFileDescriptor"
+            | "ParcelableHolder") => Err(Error::Unsupported(format!("type '{name}'"))),
+            name => {
+                let fqn = self
+                    .lookup_decl(name, scope)
+                    .ok_or_else(|| Error::UnknownType(name.to_owned()))?;
+                let info = self.decl(&fqn);
+                match &info.decl.kind {
+                    DeclKind::Parcelable(_) => no_generics(Type::Parcelable(fqn)),
+                    DeclKind::Union(_) => no_generics(Type::Union(fqn)),
+                    DeclKind::Enum(_) => {
+                        let backing = self.backing(info.decl)?;
+                        no_generics(Type::Enum(fqn, backing))
+                    }
+                    DeclKind::Interface(_) => {
+                        Err(Error::Unsupported(format!("interface type '{fqn}'")))
+                    }
+                    DeclKind::Unstructured => Err(Error::Unsupported(format!(
+                        "unstructured parcelable '{fqn}'"
+                    ))),
+                }
+            }
+        }
+    }
+
+    pub fn backing(&self, decl: &Decl) -> Result<Backing> {
+        let Some(annotation) = decl.annotation("Backing") else {
+            return Ok(Backing::Byte);
+        };
+        let ty = annotation
+            .params
+            .iter()
+            .find(|(key, _)| key == "type")
+            .map(|(_, value)| value);
+        match ty {
+            Some(Expr::Str(s)) if s == "byte" => Ok(Backing::Byte),
+            Some(Expr::Str(s)) if s == "int" => Ok(Backing::Int),
+            Some(Expr::Str(s)) if s == "long" => Ok(Backing::Long),
+            _ => Err(Error::Invalid(format!(
+                "bad @Backing for enum '{}'",
+                decl.name
+            ))),
+        }
+    }
+
+    /// Evaluate a constant expression.
+    pub fn eval(&self, expr: &Expr, scope: Scope<'_>) -> Result<Value> {
+        Ok(match expr {
+            Expr::Int(literal) => Value::Int(parse_int(literal)?),
+            Expr::Float(literal) => Value::Float(
+                literal
+                    .parse()
+                    .map_err(|_| Error::Invalid(format!("bad float literal '{literal}'")))?,
+            ),
+            Expr::Bool(b) => Value::Bool(*b),
+            Expr::Str(s) => Value::Str(s.clone()),
+            Expr::Char(c) => Value::Char(*c),
+            Expr::Array(items) => Value::Array(
+                items
+                    .iter()
+                    .map(|item| self.eval(item, scope))
+                    .collect::<Result<_>>()?,
+            ),
+            Expr::Ident(name) => self.eval_ident(name, scope)?,
+            Expr::Unary(op, e) => {
+                let v = self.eval(e, scope)?;
+                match (op, v) {
+                    (UnaryOp::Plus, v) => v,
+                    (UnaryOp::Neg, Value::Float(f)) => Value::Float(-f),
+                    (UnaryOp::Neg, v) => Value::Int(-v.as_int()?),
+                    (UnaryOp::Not, v) => Value::Bool(!v.as_bool()?),
+                    (UnaryOp::BitNot, v) => Value::Int(!v.as_int()?),
+                }
+            }
+            Expr::Binary(op, a, b) => {
+                let a = self.eval(a, scope)?;
+                let b = self.eval(b, scope)?;
+                binary(*op, a, b)?
+            }
+            Expr::Ternary(cond, a, b) => {
+                if self.eval(cond, scope)?.as_bool()? {
+                    self.eval(a, scope)?
+                } else {
+                    self.eval(b, scope)?
+                }
+            }
+        })
+    }
+
+    /// `NAME`, `Type.NAME` or `a.b.Type.NAME`
+    fn eval_ident(&self, name: &str, scope: Scope<'_>) -> Result<Value> {
+        let (owner, member) = match name.rsplit_once('.') {
+            Some((owner, member)) => {
+                let fqn = self
+                    .lookup_decl(owner, scope)
+                    .ok_or_else(|| Error::UnknownConstant(name.to_owned()))?;
+                (fqn, member)
+            }
+            None => {
+                let owner = scope
+                    .scopes
+                    .iter()
+                    .rev()
+                    .find(|fqn| self.has_member(fqn, name))
+                    .ok_or_else(|| Error::UnknownConstant(name.to_owned()))?;
+                (owner.clone(), name)
+            }
+        };
+
+        self.member_value(&owner, member)
+    }
+
+    fn has_member(&self, fqn: &str, name: &str) -> bool {
+        let decl = self.decl(fqn).decl;
+        match &decl.kind {
+            DeclKind::Enum(e) => e.enumerators.iter().any(|(n, _)| n == name),
+            _ => decl.constants().iter().any(|c| c.name == name),
+        }
+    }
+
+    /// Value of a constant or an enumerator, evaluated on first use.
+    pub fn member_value(&self, fqn: &str, name: &str) -> Result<Value> {
+        let key = (fqn.to_owned(), name.to_owned());
+        if let Some(value) = self.values.borrow().get(&key) {
+            return Ok(value.clone());
+        }
+        if !self.in_progress.borrow_mut().insert(key.clone()) {
+            return Err(Error::Invalid(format!(
+                "cyclic definition of '{fqn}.{name}'"
+            )));
+        }
+
+        let value = self.compute_member(fqn, name);
+
+        self.in_progress.borrow_mut().remove(&key);
+        let value = value?;
+        self.values.borrow_mut().insert(key, value.clone());
+        Ok(value)
+    }
+
+    fn compute_member(&self, fqn: &str, name: &str) -> Result<Value> {
+        let info = self.decl(fqn);
+        let scope = Scope {
+            doc: info.doc,
+            scopes: &info.scopes,
+        };
+
+        if let DeclKind::Enum(e) = &info.decl.kind {
+            let idx = e
+                .enumerators
+                .iter()
+                .position(|(n, _)| n == name)
+                .ok_or_else(|| Error::UnknownConstant(format!("{fqn}.{name}")))?;
+            return match &e.enumerators[idx].1 {
+                Some(expr) => Ok(Value::Int(self.eval(expr, scope)?.as_int()?)),
+                None if idx == 0 => Ok(Value::Int(0)),
+                None => {
+                    let previous = self.member_value(fqn, &e.enumerators[idx - 1].0)?;
+                    Ok(Value::Int(previous.as_int()? + 1))
+                }
+            };
+        }
+
+        let constant = info
+            .decl
+            .constants()
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| Error::UnknownConstant(format!("{fqn}.{name}")))?;
+        self.eval(&constant.value, scope)
+    }
+}
+
+fn qualify(package: &str, name: &str) -> String {
+    if package.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{package}.{name}")
+    }
+}
+
+fn register<'a>(
+    decls: &mut BTreeMap<String, DeclInfo<'a>>,
+    doc: &'a Document,
+    decl: &'a Decl,
+    fqn: String,
+    mut scopes: Vec<String>,
+    mut module: Vec<String>,
+) -> Result<()> {
+    scopes.push(fqn.clone());
+    module.push(decl.name.clone());
+
+    for nested in decl.nested() {
+        register(
+            decls,
+            doc,
+            nested,
+            format!("{fqn}.{}", nested.name),
+            scopes.clone(),
+            module.clone(),
+        )?;
+    }
+
+    let info = DeclInfo {
+        decl,
+        doc,
+        scopes,
+        module,
+    };
+    if decls.insert(fqn.clone(), info).is_some() {
+        return Err(Error::Invalid(format!("'{fqn}' is defined twice")));
+    }
+    Ok(())
+}
+
+fn parse_int(literal: &str) -> Result<i128> {
+    let bad = || Error::Invalid(format!("bad integer literal '{literal}'"));
+
+    if let Some(hex) = literal.strip_prefix("0x") {
+        // hexadecimal literals are two's complement of their type:
+        // 0xffffffff is -1 as an int
+        let (digits, long, byte) = if let Some(d) = hex.strip_suffix(['l', 'L']) {
+            (d, true, false)
+        } else if let Some(d) = hex.strip_suffix("u8") {
+            (d, false, true)
+        } else {
+            (hex, false, false)
+        };
+        let value = u64::from_str_radix(digits, 16).map_err(|_| bad())?;
+        return Ok(if byte {
+            u8::try_from(value).map_err(|_| bad())? as i8 as i128
+        } else if !long && value <= u32::MAX as u64 {
+            value as u32 as i32 as i128
+        } else {
+            value as i64 as i128
+        });
+    }
+
+    literal
+        .trim_end_matches(['l', 'L'])
+        .parse()
+        .map_err(|_| bad())
+}
+
+fn binary(op: BinaryOp, a: Value, b: Value) -> Result<Value> {
+    use BinaryOp::*;
+
+    if let (Add, Value::Str(a), Value::Str(b)) = (op, &a, &b) {
+        return Ok(Value::Str(format!("{a}{b}")));
+    }
+
+    if matches!(op, And | Or) {
+        let (a, b) = (a.as_bool()?, b.as_bool()?);
+        return Ok(Value::Bool(if op == And { a && b } else { a || b }));
+    }
+
+    if matches!(a, Value::Float(_)) || matches!(b, Value::Float(_)) {
+        let (a, b) = (a.as_float()?, b.as_float()?);
+        return Ok(match op {
+            Mul => Value::Float(a * b),
+            Div => Value::Float(a / b),
+            Add => Value::Float(a + b),
+            Sub => Value::Float(a - b),
+            Lt => Value::Bool(a < b),
+            Gt => Value::Bool(a > b),
+            Le => Value::Bool(a <= b),
+            Ge => Value::Bool(a >= b),
+            Eq => Value::Bool(a == b),
+            Ne => Value::Bool(a != b),
+            _ => return Err(Error::Invalid(format!("{op:?} on floating point values"))),
+        });
+    }
+
+    let (a, b) = (a.as_int()?, b.as_int()?);
+    let div_zero = || Error::Invalid("division by zero".to_owned());
+    Ok(match op {
+        Mul => Value::Int(a.wrapping_mul(b)),
+        Div => Value::Int(a.checked_div(b).ok_or_else(div_zero)?),
+        Rem => Value::Int(a.checked_rem(b).ok_or_else(div_zero)?),
+        Add => Value::Int(a.wrapping_add(b)),
+        Sub => Value::Int(a.wrapping_sub(b)),
+        Shl => Value::Int(a.checked_shl(b as u32).unwrap_or(0)),
+        Shr => Value::Int(a.checked_shr(b as u32).unwrap_or(0)),
+        Lt => Value::Bool(a < b),
+        Gt => Value::Bool(a > b),
+        Le => Value::Bool(a <= b),
+        Ge => Value::Bool(a >= b),
+        Eq => Value::Bool(a == b),
+        Ne => Value::Bool(a != b),
+        BitAnd => Value::Int(a & b),
+        BitXor => Value::Int(a ^ b),
+        BitOr => Value::Int(a | b),
+        And | Or => unreachable!(),
+    })
+}
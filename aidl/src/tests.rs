@@ -0,0 +1,72 @@
+//! Golden tests of the parser: each `tests/golden/<name>.aidl` is parsed and
+//! its syntax tree compared to `<name>.ast`. Run them with `BLESS=1` to
+//! write the expected trees after a change of the parser.
+
+use std::{fs, path::Path};
+
+use crate::{error::Error, parser::parse};
+
+#[test]
+fn golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let bless = std::env::var_os("BLESS").is_some();
+
+    let mut count = 0;
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "aidl") {
+            continue;
+        }
+        count += 1;
+
+        let source = fs::read_to_string(&path).unwrap();
+        let doc = parse(&source).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+        let actual = format!("{doc:#?}\n");
+
+        let golden = path.with_extension("ast");
+        if bless {
+            fs::write(&golden, actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&golden)
+            .unwrap_or_else(|e| panic!("{}: {e}, run with BLESS=1", golden.display()));
+        assert!(
+            actual == expected,
+            "{} doesn't match {}, run with BLESS=1 to update it:\n{actual}",
+            path.display(),
+            golden.display()
+        );
+    }
+    assert!(count > 0, "no golden file in {}", dir.display());
+}
+
+#[test]
+fn syntax_errors() {
+    let cases = [
+        ("parcelable Foo { int x }", 1, 24, "expected ';'"),
+        (
+            "interface IFoo {\n    void f(int a b);\n}",
+            2,
+            18,
+            "expected ','",
+        ),
+        ("package a.b\nparcelable Foo {}", 2, 1, "expected ';'"),
+        ("struct Foo {}", 1, 1, "unexpected 'struct'"),
+        ("oneway parcelable Foo {}", 1, 8, "expected 'interface'"),
+        ("parcelable Foo { int x = ; }", 1, 26, "expected expression"),
+    ];
+
+    for (source, line, column, message) in cases {
+        match parse(source) {
+            Err(Error::Syntax {
+                line: l,
+                column: c,
+                message: m,
+            }) => {
+                assert_eq!((l, c), (line, column), "{source:?}: {m}");
+                assert!(m.starts_with(message), "{source:?}: {m}");
+            }
+            other => panic!("{source:?}: expected a syntax error, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Cost of the parcels of a `Service::call` round: a command buffer, the
+//! request data, a read buffer and the reply data, allocated each time or
+//! taken from the pool of the thread as the library does. The reply is
+//! copied into a parcel returned to the caller, it is allocated either way.
+//!
+//! `cargo +nightly bench -p binder-rs-bench --bench parcel_pool`
+#![feature(test)]
+
+extern crate test;
+
+use binder_rs::parcel::{Parcel, pool::PooledParcel};
+use test::{Bencher, black_box};
+
+const PAYLOAD: [u8; 4096] = [0x5a; 4096];
+
+/// A command and its transaction data, as `Binder::transaction` writes.
+fn command(parcel: &mut Parcel) {
+    parcel.write(&0x40406300u32).unwrap();
+    for word in 0..16u32 {
+        parcel.write(&word).unwrap();
+    }
+}
+
+fn request(parcel: &mut Parcel) {
+    parcel.write_interface_token("android.os.IFoo").unwrap();
+    parcel.write(&PAYLOAD[..1024]).unwrap();
+}
+
+fn reply(parcel: &mut Parcel) {
+    parcel.write(&0i32).unwrap();
+    parcel.write(&PAYLOAD[..]).unwrap();
+}
+
+fn round(mut parcel: impl FnMut(usize) -> PooledOrNot) {
+    let mut cmd = parcel(256);
+    command(cmd.get());
+    let mut data = parcel(256);
+    request(data.get());
+    let mut read = parcel(32 * 8);
+    read.get().set_data_size(32 * 8);
+    let mut out = PooledOrNot::Allocated(Parcel::with_capacity(256));
+    reply(out.get());
+    black_box((cmd, data, read, out));
+}
+
+enum PooledOrNot {
+    Allocated(Parcel<'static>),
+    Pooled(PooledParcel),
+}
+
+impl PooledOrNot {
+    fn get(&mut self) -> &mut Parcel<'static> {
+        match self {
+            PooledOrNot::Allocated(parcel) => parcel,
+            PooledOrNot::Pooled(parcel) => parcel,
+        }
+    }
+}
+
+#[bench]
+fn allocated(b: &mut Bencher) {
+    b.iter(|| round(|capacity| PooledOrNot::Allocated(Parcel::with_capacity(capacity))));
+}
+
+#[bench]
+fn pooled(b: &mut Bencher) {
+    b.iter(|| round(|capacity| PooledOrNot::Pooled(PooledParcel::with_capacity(capacity))));
+}
@@ -0,0 +1 @@
+//! Benchmarks of binder-rs live in `benches/`: `cargo +nightly bench -p binder-rs-bench`.
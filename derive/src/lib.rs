@@ -0,0 +1,449 @@
+//! Derive macros for the traits of `binder_rs::parcel::parcelable`.
+//!
+//! Structs are written as AIDL structured parcelables: a null flag, the
+//! size of the parcelable, then the fields in declaration order. Fields
+//! missing at the end, sent by an older peer, take their
+//! `#[parcel(default = ...)]` value or `Default::default()`. Data after
+//! the known fields, sent by a newer peer, is skipped.
+//!
+//! Enums with unit variants are written as their backing integer, taken
+//! from `#[repr(...)]` (`i32` if absent). Unknown values are rejected
+//! with `BinderError::BadValue`.
+//!
+//! ```ignore
+//! use binder_rs::parcel::parcelable::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Point {
+//!     x: i32,
+//!     y: i32,
+//!     #[parcel(default = String::from("origin"))]
+//!     label: String,
+//! }
+//! ```
+//!
+//! The generated code refers to the `binder_rs` crate, another path can
+//! be given with `#[parcel(crate = "path::to::binder_rs")]`.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    Data, DeriveInput, Error, Expr, Fields, Ident, LitStr, Member, Path, Type, parse_macro_input,
+    parse_quote, spanned::Spanned,
+};
+
+#[proc_macro_derive(Serialize, attributes(parcel))]
+pub fn derive_serialize(input: TokenStream) -> TokenStream {
+    expand(input, serialize)
+}
+
+#[proc_macro_derive(Deserialize, attributes(parcel))]
+pub fn derive_deserialize(input: TokenStream) -> TokenStream {
+    expand(input, deserialize)
+}
+
+#[proc_macro_derive(SerializeOption, attributes(parcel))]
+pub fn derive_serialize_option(input: TokenStream) -> TokenStream {
+    expand(input, serialize_option)
+}
+
+#[proc_macro_derive(DeserializeOption, attributes(parcel))]
+pub fn derive_deserialize_option(input: TokenStream) -> TokenStream {
+    expand(input, deserialize_option)
+}
+
+#[proc_macro_derive(SerializeArray, attributes(parcel))]
+pub fn derive_serialize_array(input: TokenStream) -> TokenStream {
+    expand(input, serialize_array)
+}
+
+#[proc_macro_derive(DeserializeArray, attributes(parcel))]
+pub fn derive_deserialize_array(input: TokenStream) -> TokenStream {
+    expand(input, deserialize_array)
+}
+
+fn expand(input: TokenStream, f: fn(&Input<'_>) -> TokenStream2) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    match Input::parse(&ast) {
+        Ok(input) => f(&input).into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+struct Input<'a> {
+    ast: &'a DeriveInput,
+    krate: Path,
+    kind: Kind<'a>,
+}
+
+enum Kind<'a> {
+    Struct {
+        fields: Vec<Field<'a>>,
+    },
+    Enum {
+        repr: Ident,
+        variants: Vec<&'a Ident>,
+    },
+}
+
+struct Field<'a> {
+    member: Member,
+    ty: &'a Type,
+    default: Option<Expr>,
+}
+
+impl<'a> Input<'a> {
+    fn parse(ast: &'a DeriveInput) -> syn::Result<Self> {
+        let mut krate: Path = parse_quote!(::binder_rs);
+        for attr in ast.attrs.iter().filter(|a| a.path().is_ident("parcel")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("crate") {
+                    krate = meta.value()?.parse::<LitStr>()?.parse()?;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown parcel attribute, expected `crate`"))
+                }
+            })?;
+        }
+
+        let kind = match &ast.data {
+            Data::Struct(data) => {
+                let fields = data
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, field)| {
+                        let member = match &field.ident {
+                            Some(ident) => Member::Named(ident.clone()),
+                            None => Member::Unnamed(idx.into()),
+                        };
+                        Ok(Field {
+                            member,
+                            ty: &field.ty,
+                            default: field_default(field)?,
+                        })
+                    })
+                    .collect::<syn::Result<_>>()?;
+                Kind::Struct { fields }
+            }
+            Data::Enum(data) => {
+                if !ast.generics.params.is_empty() {
+                    return Err(Error::new(
+                        ast.generics.span(),
+                        "generic enums are not supported",
+                    ));
+                }
+                if data.variants.is_empty() {
+                    return Err(Error::new(
+                        ast.ident.span(),
+                        "empty enums are not supported",
+                    ));
+                }
+                let variants = data
+                    .variants
+                    .iter()
+                    .map(|variant| match variant.fields {
+                        Fields::Unit => Ok(&variant.ident),
+                        _ => Err(Error::new(
+                            variant.span(),
+                            "only unit variants can be written as their backing integer",
+                        )),
+                    })
+                    .collect::<syn::Result<_>>()?;
+                Kind::Enum {
+                    repr: enum_repr(ast)?,
+                    variants,
+                }
+            }
+            Data::Union(data) => {
+                return Err(Error::new(
+                    data.union_token.span(),
+                    "unions are not supported, use an enum",
+                ));
+            }
+        };
+
+        Ok(Self { ast, krate, kind })
+    }
+
+    /// `impl<...> #krate::parcel::parcelable::#name for Type<...> where ...`,
+    /// the field types bounded by the trait for generic structs.
+    fn impl_header(&self, name: &str) -> TokenStream2 {
+        let krate = &self.krate;
+        let ident = &self.ast.ident;
+        let trait_ident = format_ident!("{name}");
+        let trait_path = quote!(#krate::parcel::parcelable::#trait_ident);
+
+        let mut generics = self.ast.generics.clone();
+        if let Kind::Struct { fields, .. } = &self.kind
+            && generics.type_params().next().is_some()
+        {
+            let where_clause = generics.make_where_clause();
+            for field in fields {
+                let ty = field.ty;
+                where_clause.predicates.push(parse_quote!(#ty: #trait_path));
+                // missing fields fall back to `Default::default()`
+                if name == "Deserialize" && field.default.is_none() {
+                    where_clause
+                        .predicates
+                        .push(parse_quote!(#ty: ::core::default::Default));
+                }
+            }
+        }
+
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        quote!(impl #impl_generics #trait_path for #ident #ty_generics #where_clause)
+    }
+}
+
+fn field_default(field: &syn::Field) -> syn::Result<Option<Expr>> {
+    let mut default = None;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("parcel")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("default") {
+                default = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown parcel attribute, expected `default`"))
+            }
+        })?;
+    }
+    Ok(default)
+}
+
+fn enum_repr(ast: &DeriveInput) -> syn::Result<Ident> {
+    const INTEGERS: &[&str] = &["i8", "u8", "i16", "u16", "i32", "u32", "i64", "u64"];
+
+    let mut repr = None;
+    for attr in ast.attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if let Some(ident) = meta.path.get_ident()
+                && INTEGERS.contains(&ident.to_string().as_str())
+            {
+                repr = Some(ident.clone());
+            }
+            Ok(())
+        })?;
+    }
+    Ok(repr.unwrap_or_else(|| format_ident!("i32")))
+}
+
+/// `match #value { Self::A => Self::A as #repr, ... }`
+fn enum_to_repr(value: TokenStream2, repr: &Ident, variants: &[&Ident]) -> TokenStream2 {
+    quote! {
+        match #value {
+            #(Self::#variants => Self::#variants as #repr,)*
+        }
+    }
+}
+
+/// `match #value { v if v == Self::A as #repr => Ok(Self::A), ..., _ => Err(BadValue) }`
+fn enum_from_repr(
+    krate: &Path,
+    value: TokenStream2,
+    repr: &Ident,
+    variants: &[&Ident],
+) -> TokenStream2 {
+    quote! {
+        match #value {
+            #(v if v == Self::#variants as #repr => Ok(Self::#variants),)*
+            _ => Err(#krate::error::BinderError::BadValue),
+        }
+    }
+}
+
+fn serialize(input: &Input<'_>) -> TokenStream2 {
+    let krate = &input.krate;
+    let header = input.impl_header("Serialize");
+
+    let body = match &input.kind {
+        Kind::Struct { fields, .. } => {
+            let members = fields.iter().map(|f| &f.member);
+            quote! {
+                parcel.write(&#krate::parcel::parcelable::NON_NULL_PARCELABLE_FLAG)?;
+                parcel.sized_write(|subparcel| {
+                    #(subparcel.write(&self.#members)?;)*
+                    Ok(())
+                })
+            }
+        }
+        Kind::Enum { repr, variants } => {
+            let value = enum_to_repr(quote!(self), repr, variants);
+            quote! {
+                let value: #repr = #value;
+                parcel.write(&value)
+            }
+        }
+    };
+
+    quote! {
+        #header {
+            fn serialize(&self, parcel: &mut #krate::parcel::Parcel) -> #krate::error::Result<()> {
+                #body
+            }
+        }
+    }
+}
+
+fn deserialize(input: &Input<'_>) -> TokenStream2 {
+    let krate = &input.krate;
+    let header = input.impl_header("Deserialize");
+
+    let body = match &input.kind {
+        Kind::Struct { fields } => {
+            let vars: Vec<Ident> = (0..fields.len())
+                .map(|i| format_ident!("__field{i}"))
+                .collect();
+            let types = fields.iter().map(|f| f.ty);
+            let values = fields
+                .iter()
+                .zip(&vars)
+                .map(|(field, var)| match &field.default {
+                    Some(default) => quote!(#var.unwrap_or_else(|| #default)),
+                    None => quote!(#var.unwrap_or_default()),
+                });
+            let members = fields.iter().map(|f| &f.member);
+            // `Self { 0: .. }` also builds tuple structs
+            let construct = quote!(Self { #(#members: #values,)* });
+
+            quote! {
+                let flag: i32 = parcel.read()?;
+                if flag == #krate::parcel::parcelable::NULL_PARCELABLE_FLAG {
+                    return Err(#krate::error::BinderError::UnexpectedNull);
+                }
+
+                #(let mut #vars: Option<#types> = None;)*
+                parcel.sized_read(|subparcel| {
+                    // fields unknown to an older peer are not sent
+                    #(
+                        if subparcel.has_more_data() {
+                            #vars = Some(subparcel.read()?);
+                        }
+                    )*
+                    Ok(())
+                })?;
+
+                Ok(#construct)
+            }
+        }
+        Kind::Enum { repr, variants } => {
+            let value = enum_from_repr(krate, quote!(value), repr, variants);
+            quote! {
+                let value: #repr = parcel.read()?;
+                #value
+            }
+        }
+    };
+
+    quote! {
+        #header {
+            fn deserialize(parcel: &mut #krate::parcel::Parcel) -> #krate::error::Result<Self> {
+                #body
+            }
+        }
+    }
+}
+
+fn serialize_option(input: &Input<'_>) -> TokenStream2 {
+    let krate = &input.krate;
+    let header = input.impl_header("SerializeOption");
+
+    match &input.kind {
+        // the null flag is already written by `Serialize`
+        Kind::Struct { .. } => quote! {
+            #header {
+                fn serialize_option(
+                    this: Option<&Self>,
+                    parcel: &mut #krate::parcel::Parcel,
+                ) -> #krate::error::Result<()> {
+                    match this {
+                        Some(this) => #krate::parcel::parcelable::Serialize::serialize(this, parcel),
+                        None => parcel.write(&#krate::parcel::parcelable::NULL_PARCELABLE_FLAG),
+                    }
+                }
+            }
+        },
+        Kind::Enum { .. } => quote!(#header {}),
+    }
+}
+
+fn deserialize_option(input: &Input<'_>) -> TokenStream2 {
+    let krate = &input.krate;
+    let header = input.impl_header("DeserializeOption");
+
+    match &input.kind {
+        // peek at the null flag, `Deserialize` reads it again
+        Kind::Struct { .. } => quote! {
+            #header {
+                fn deserialize_option(
+                    parcel: &mut #krate::parcel::Parcel,
+                ) -> #krate::error::Result<Option<Self>> {
+                    let start = parcel.data_position();
+                    let flag: i32 = parcel.read()?;
+                    if flag == #krate::parcel::parcelable::NULL_PARCELABLE_FLAG {
+                        return Ok(None);
+                    }
+                    parcel.set_data_position(start);
+                    #krate::parcel::parcelable::Deserialize::deserialize(parcel).map(Some)
+                }
+            }
+        },
+        Kind::Enum { .. } => quote!(#header {}),
+    }
+}
+
+fn serialize_array(input: &Input<'_>) -> TokenStream2 {
+    let krate = &input.krate;
+    let header = input.impl_header("SerializeArray");
+
+    match &input.kind {
+        Kind::Struct { .. } => quote!(#header {}),
+        // same layout as an array of the backing type
+        Kind::Enum { repr, variants } => {
+            let value = enum_to_repr(quote!(e), repr, variants);
+            quote! {
+                #header {
+                    fn serialize_array(
+                        slice: &[Self],
+                        parcel: &mut #krate::parcel::Parcel,
+                    ) -> #krate::error::Result<()> {
+                        let values: Vec<#repr> = slice.iter().map(|e| #value).collect();
+                        <#repr as #krate::parcel::parcelable::SerializeArray>::serialize_array(
+                            &values, parcel,
+                        )
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn deserialize_array(input: &Input<'_>) -> TokenStream2 {
+    let krate = &input.krate;
+    let header = input.impl_header("DeserializeArray");
+
+    match &input.kind {
+        Kind::Struct { .. } => quote!(#header {}),
+        Kind::Enum { repr, variants } => {
+            let value = enum_from_repr(krate, quote!(value), repr, variants);
+            quote! {
+                #header {
+                    fn deserialize_array(
+                        parcel: &mut #krate::parcel::Parcel,
+                    ) -> #krate::error::Result<Option<Vec<Self>>> {
+                        let Some(values) =
+                            <#repr as #krate::parcel::parcelable::DeserializeArray>::deserialize_array(parcel)?
+                        else {
+                            return Ok(None);
+                        };
+                        values
+                            .into_iter()
+                            .map(|value| #value)
+                            .collect::<#krate::error::Result<Vec<Self>>>()
+                            .map(Some)
+                    }
+                }
+            }
+        }
+    }
+}
@@ -17,7 +17,7 @@ const TF_FD: u32 = pack_chars!(b'f', b'd', b'*', BINDER_TYPE_LARGE);
 const TF_FDA: u32 = pack_chars!(b'f', b'd', b'a', BINDER_TYPE_LARGE);
 const TF_PTR: u32 = pack_chars!(b'p', b't', b'*', BINDER_TYPE_LARGE);
 
-#[derive(Debug, Hash, Clone, Copy, PartialEq, FromPrimitive, ToPrimitive)]
+#[derive(Debug, Hash, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
 #[repr(u32)]
 pub enum BinderType {
     Binder = TF_BINDER,
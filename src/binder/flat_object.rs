@@ -3,6 +3,8 @@ use std::{
     os::fd::{BorrowedFd, FromRawFd, OwnedFd},
 };
 
+use num_traits::FromPrimitive;
+
 use crate::error::{BinderError, Result};
 
 use super::binder_type::BinderType;
@@ -39,6 +41,15 @@ pub struct BinderFlatObject {
     cookie: usize,
 }
 
+impl PartialEq for BinderFlatObject {
+    fn eq(&self, other: &Self) -> bool {
+        self.binder_type == other.binder_type
+            && self.flags == other.flags
+            && self.pointer() == other.pointer()
+            && self.cookie == other.cookie
+    }
+}
+
 impl Default for BinderFlatObject {
     fn default() -> Self {
         Self {
@@ -52,22 +63,39 @@ impl Default for BinderFlatObject {
 
 impl BinderFlatObject {
     pub fn new_with_fd(raw_fd: i32, take_ownership: bool) -> Self {
-        Self {
+        let mut obj = Self {
             binder_type: BinderType::Fd,
             flags: 0x7F & FLAT_BINDER_FLAG_ACCEPTS_FDS,
-            data: UnionFlatObject {
-                handle: raw_fd as _,
-            },
             cookie: if take_ownership { 1 } else { 0 },
+            ..Default::default()
+        };
+        obj.set_handle(raw_fd as _);
+        obj
+    }
+
+    /// Decode an object from the start of `bytes`, which doesn't need to be aligned.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let Some(bytes) = bytes.get(..size_of::<Self>()) else {
+            error!("Not enough data for a flat object: {}", bytes.len());
+            return Err(BinderError::NotEnoughData);
+        };
+
+        let raw_type = u32::from_ne_bytes(bytes[..4].try_into().unwrap());
+        if BinderType::from_u32(raw_type).is_none() {
+            error!("Invalid object type {raw_type:#X}");
+            return Err(BinderError::BadType);
         }
-    }
 
-    pub unsafe fn ref_from_raw(ptr: *const u8, offset: usize) -> &'static Self {
-        unsafe { std::mem::transmute(&*ptr.add(offset)) }
+        // SAFETY: `bytes` holds a whole object, its type was checked above
+        // and any bit pattern is valid for the other fields.
+        Ok(unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const Self) })
     }
 
-    pub unsafe fn mut_from_raw(ptr: *mut u8, offset: usize) -> &'static mut Self {
-        unsafe { std::mem::transmute(&mut *ptr.add(offset)) }
+    /// The object as written in a parcel.
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        // SAFETY: the object has no padding, and `data` is always
+        // initialized as a whole (see `Default` and `set_handle`).
+        unsafe { std::slice::from_raw_parts(self as *const Self as *const u8, size_of::<Self>()) }
     }
 
     pub(crate) fn handle(&self) -> u32 {
@@ -86,6 +114,10 @@ impl BinderFlatObject {
         self.data.binder = pointer
     }
 
+    pub(crate) fn flags(&self) -> u32 {
+        self.flags
+    }
+
     pub(crate) fn cookie(&self) -> usize {
         self.cookie
     }
@@ -3,9 +3,11 @@
 // https://android.googlesource.com/platform/frameworks/native/+/master/libs/binder/rust/src/binder.rs
 use std::{
     ffi::c_void,
+    io::Write,
     num::NonZero,
-    os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
     ptr::NonNull,
+    sync::Mutex,
 };
 
 use command_protocol::{BinderCommand, BinderReturn};
@@ -23,7 +25,14 @@ use num_traits::FromPrimitive;
 use transaction::{Transaction, TransactionFlag};
 use transaction_data::{BinderTransactionData, TargetUnion};
 
-use crate::{error::Result, parcel::Parcel};
+use crate::{
+    error::Result,
+    parcel::{
+        Parcel,
+        pool::PooledParcel,
+        record::{ParcelRecord, RecordKind},
+    },
+};
 
 pub mod binder_type;
 pub mod command_protocol;
@@ -55,6 +64,7 @@ ioctl_readwrite!(binder_read_version, b'b', 9, BinderVersion);
 pub struct Binder {
     fd: OwnedFd,
     mem: NonNull<c_void>,
+    recorder: Mutex<Option<Box<dyn Write + Send>>>,
 }
 
 impl Drop for Binder {
@@ -67,6 +77,12 @@ impl Drop for Binder {
     }
 }
 
+impl AsFd for Binder {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
 impl Binder {
     pub fn new(device: BinderDevice) -> Result<Self> {
         let flags = OFlag::O_RDWR | OFlag::O_CLOEXEC;
@@ -96,7 +112,11 @@ impl Binder {
 
         unsafe { binder_set_max_threads(fd.as_raw_fd(), &DEFAULT_MAX_BINDER_THREADS)? };
 
-        Ok(Self { fd, mem })
+        Ok(Self {
+            fd,
+            mem,
+            recorder: Mutex::new(None),
+        })
     }
 
     pub fn become_context_manager(&self) -> Result<()> {
@@ -104,6 +124,46 @@ impl Binder {
         Ok(())
     }
 
+    /// Write a [`ParcelRecord`] of each transaction sent by
+    /// [`transaction`](Self::transaction) and received by
+    /// [`binder_parse`](Self::binder_parse) to `writer`, `None` to stop.
+    pub fn set_recorder(&self, writer: Option<Box<dyn Write + Send>>) {
+        *self.recorder.lock().unwrap() = writer;
+    }
+
+    fn record(&self, kind: RecordKind, tx: &BinderTransactionData, data: &Parcel) {
+        if data.is_sensitive() {
+            return;
+        }
+        self.write_record(ParcelRecord::new(kind, tx, data));
+    }
+
+    fn write_record(&self, record: Result<ParcelRecord>) {
+        let mut recorder = self.recorder.lock().unwrap();
+        let Some(writer) = recorder.as_mut() else {
+            return;
+        };
+        if let Err(e) = record.and_then(|record| record.write_to(writer)) {
+            warn!("[Record] {e}");
+        }
+    }
+
+    /// Record the transaction at the position of `parcel`, without moving it.
+    fn record_received(&self, kind: RecordKind, parcel: &mut Parcel) {
+        if self.recorder.lock().unwrap().is_none() {
+            return;
+        }
+
+        match parcel.peek::<BinderTransactionData>() {
+            // the driver clears the buffer, keep its data out of the records
+            Ok(tx) if tx.flags.contains(TransactionFlag::ClearBuf) => {}
+            // SAFETY: the transaction was just read from this binder.
+            tx => self.write_record(
+                tx.and_then(|tx| unsafe { ParcelRecord::from_transaction(kind, &tx) }),
+            ),
+        }
+    }
+
     pub fn binder_write(&self, buffer: &mut Parcel) -> Result<()> {
         if buffer.data_size() == 0 {
             // warn!("[BinderWrite] trying write buffer size 0.");
@@ -146,11 +206,13 @@ impl Binder {
             warn!("[BinderRead] Trying read driver with buffer capacity 0");
             return Ok(());
         }
+        // the driver writes into initialized memory, the whole capacity
+        buffer.set_data_size(buffer.capacity());
         let mut data = BinderWriteRead {
             write_size: 0,
             write_consumed: 0,
             write_buffer: std::ptr::null(),
-            read_size: buffer.capacity(),
+            read_size: buffer.data_size(),
             read_consumed: 0,
             read_buffer: buffer.as_mut_ptr(),
         };
@@ -171,7 +233,7 @@ impl Binder {
 
     pub fn enter_loop(&self) -> Result<()> {
         info!("[EnterLoopCmd]");
-        let mut parcel = Parcel::default();
+        let mut parcel = PooledParcel::default();
         parcel.write(&BinderCommand::EnterLooper)?;
 
         self.binder_write(&mut parcel)
@@ -179,7 +241,7 @@ impl Binder {
 
     pub fn exit_loop(&self) -> Result<()> {
         info!("[ExitLoopCmd]");
-        let mut parcel = Parcel::default();
+        let mut parcel = PooledParcel::default();
         parcel.write(&BinderCommand::ExitLooper)?;
         self.binder_write(&mut parcel)
     }
@@ -208,6 +270,12 @@ impl Binder {
             let cmd = cmd.unwrap();
             info!("[BinderParse] Got cmd: {cmd:#?}");
 
+            match cmd {
+                BinderReturn::Transaction => self.record_received(RecordKind::Incoming, parcel),
+                BinderReturn::Reply => self.record_received(RecordKind::Reply, parcel),
+                _ => {}
+            }
+
             // if handler success handle this
             // we move to another
             if !handler_progressed {
@@ -235,7 +303,9 @@ impl Binder {
                 BinderReturn::Transaction | BinderReturn::Reply => {
                     let tx = parcel.read::<BinderTransactionData>()?;
                     info!("[BinderParse] Transaction data: \n{tx:#?}");
-                    info!("[BinderParse] Parcel: \n{:#?}", tx.to_parcel(None));
+                    // SAFETY: the transaction was just read from this binder.
+                    let data = unsafe { tx.to_parcel(self, None)? };
+                    info!("[BinderParse] Parcel: \n{data:#?}");
                 }
                 BinderReturn::AcquireResult => {
                     info!("[BinderParse] AcquireResult: {}", parcel.read::<i32>()?);
@@ -282,7 +352,8 @@ impl Binder {
         flags: TransactionFlag,
         data: &mut Parcel,
     ) -> Result<()> {
-        let mut parcel = Parcel::default();
+        let mut parcel = PooledParcel::default();
+        let flags = clear_buf_if_sensitive(flags, data);
 
         let transaction_data_out = BinderTransactionData {
             target: TargetUnion::new_handle(handle),
@@ -298,6 +369,7 @@ impl Binder {
         };
 
         info!("[Transaction]\n{transaction_data_out:#?}");
+        self.record(RecordKind::Outgoing, &transaction_data_out, data);
 
         parcel.write(&BinderCommand::Transaction)?;
         parcel.write_aligned(&transaction_data_out);
@@ -317,7 +389,7 @@ impl Binder {
     {
         self.transaction(handle, code, flags, data)?;
 
-        let mut parcel = Parcel::with_capacity(32 * 8);
+        let mut parcel = PooledParcel::with_capacity(32 * 8);
 
         loop {
             info!("[TransactionWithParse] Looping");
@@ -338,7 +410,8 @@ impl Binder {
     }
 
     pub fn reply(&self, data: &mut Parcel, flags: TransactionFlag) -> Result<()> {
-        let mut parcel = Parcel::default();
+        let mut parcel = PooledParcel::default();
+        let flags = clear_buf_if_sensitive(flags, data);
 
         let transaction_data_out = BinderTransactionData {
             target: TargetUnion {
@@ -359,4 +432,20 @@ impl Binder {
         parcel.write_aligned(&transaction_data_out);
         self.binder_write(&mut parcel)
     }
+
+    /// Reply with a bare `status_t` instead of a reply parcel.
+    pub fn reply_status(&self, status: i32) -> Result<()> {
+        let mut data = PooledParcel::with_capacity(size_of::<i32>());
+        data.write(&status)?;
+        self.reply(&mut data, TransactionFlag::StatusCode)
+    }
+}
+
+/// Have the driver clear the buffer of the receiver of sensitive data.
+pub(crate) fn clear_buf_if_sensitive(flags: TransactionFlag, data: &Parcel) -> TransactionFlag {
+    if data.is_sensitive() {
+        flags | TransactionFlag::ClearBuf
+    } else {
+        flags
+    }
 }
@@ -2,9 +2,12 @@ use std::fmt::Debug;
 
 use nix::libc;
 
-use crate::parcel::{FnFreeBuffer, Parcel};
+use crate::{
+    error::{BinderError, Result},
+    parcel::{FnFreeBuffer, Parcel, Pod},
+};
 
-use super::transaction::TransactionFlag;
+use super::{Binder, transaction::TransactionFlag};
 
 #[derive(Clone, Copy)]
 #[repr(C)]
@@ -34,7 +37,7 @@ impl Debug for TargetUnion {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 #[repr(C, packed(4))]
 pub struct BinderTransactionData {
     pub target: TargetUnion,
@@ -53,14 +56,65 @@ pub struct BinderTransactionData {
     pub offsets: *mut usize,
 }
 
+// SAFETY: only integers and pointers, packed without padding.
+unsafe impl Pod for BinderTransactionData {}
+
 impl BinderTransactionData {
-    pub fn to_parcel(&self, free_buffer: Option<FnFreeBuffer>) -> Parcel {
-        Parcel::from_ipc_parts(
-            self.data,
-            self.data_size,
-            self.offsets,
-            self.offsets_size / size_of::<usize>(),
-            free_buffer,
-        )
+    /// The transaction data, borrowed from the mapping of `binder`.
+    /// Fails if the object offsets are invalid.
+    ///
+    /// # Safety
+    /// The transaction must have been delivered by `binder`, and its buffer
+    /// not freed yet.
+    pub unsafe fn to_parcel<'a>(
+        &self,
+        _binder: &'a Binder,
+        free_buffer: Option<FnFreeBuffer>,
+    ) -> Result<Parcel<'a>> {
+        // SAFETY: guaranteed by the caller.
+        unsafe { self.parcel(free_buffer) }
+    }
+
+    /// The transaction data, borrowed from the driver buffer without
+    /// releasing its objects on drop: they belong to the parcel handed to
+    /// the handler of the transaction.
+    ///
+    /// # Safety
+    /// The buffer of the transaction must be valid for `'a`.
+    pub(crate) unsafe fn borrowed_parcel<'a>(&self) -> Result<Parcel<'a>> {
+        // SAFETY: guaranteed by the caller.
+        unsafe { self.parcel(Some(keep_objects)) }
+    }
+
+    unsafe fn parcel<'a>(&self, free_buffer: Option<FnFreeBuffer>) -> Result<Parcel<'a>> {
+        let (offsets, offsets_size) = (self.offsets, self.offsets_size);
+        if offsets_size % size_of::<usize>() != 0 {
+            error!("Bad offsets size: {offsets_size}");
+            return Err(BinderError::BadValue);
+        }
+        if !offsets.is_aligned() {
+            error!("Unaligned offsets: {offsets:p}");
+            return Err(BinderError::BadValue);
+        }
+
+        // SAFETY: the driver gave a valid buffer, living in the binder mapping.
+        let mut parcel = unsafe {
+            Parcel::from_ipc_parts(
+                self.data,
+                self.data_size,
+                offsets,
+                offsets_size / size_of::<usize>(),
+                free_buffer,
+            )?
+        };
+        // the driver clears the buffer, keep the data from being copied or printed
+        if self.flags.contains(TransactionFlag::ClearBuf) {
+            parcel.mark_sensitive();
+        }
+        Ok(parcel)
     }
 }
+
+fn keep_objects(_: Option<&Parcel<'_>>, _: usize, _: usize, _: usize, _: usize) -> Result<()> {
+    Ok(())
+}
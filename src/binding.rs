@@ -1,8 +1,17 @@
+use std::{
+    sync::{Mutex, PoisonError, mpsc},
+    thread::{self, JoinHandle},
+};
+
 use jni::{JNIEnv, JNIVersion, JavaVM, objects::JObject};
 
 use crate::{
     parcel::Parcel,
-    service::{BinderService, service_manager::ServiceManager},
+    service::{
+        BinderService, CallingContext, TransactionFlag, service_manager::ServiceManager,
+        shutdown::ShutdownHandle,
+    },
+    status::Status,
 };
 
 struct MyService;
@@ -10,22 +19,46 @@ impl BinderService for MyService {
     fn progress_request(
         &self,
         code: u32,
-        data: &mut crate::parcel::Parcel,
-    ) -> crate::parcel::Parcel {
-        info!("We got code: {code}");
-        Parcel::default()
+        _data: &mut Parcel,
+        _reply: &mut Parcel,
+        ctx: &CallingContext,
+        _flags: TransactionFlag,
+    ) -> Result<(), Status> {
+        info!("We got code: {code} from {ctx:?}");
+        Ok(())
+    }
+}
+
+static SERVICE_THREAD: Mutex<Option<(ShutdownHandle, JoinHandle<()>)>> = Mutex::new(None);
+
+/// Empties [`SERVICE_THREAD`] when the service thread exits, however the
+/// loop ended, so the service can be loaded again.
+struct ServiceThreadGuard;
+
+impl Drop for ServiceThreadGuard {
+    fn drop(&mut self) {
+        let mut service = SERVICE_THREAD
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // the slot may already hold a newer thread after an unload
+        if service
+            .as_ref()
+            .is_some_and(|(_, thread)| thread.thread().id() == thread::current().id())
+        {
+            *service = None;
+        }
     }
 }
 
 #[tokio::main]
-async fn service_root() {
+async fn service_root(ready: mpsc::Sender<ShutdownHandle>) {
     let handler = MyService;
     let service = ServiceManager::new().unwrap();
-    service
+    let listener = service
         .register_service(&handler, "myservice", "com.example.IMyService", true, 0)
-        .unwrap()
-        .binder_loop()
         .unwrap();
+    ready.send(listener.shutdown_handle()).unwrap();
+    listener.binder_loop().unwrap();
 
     info!("Graceful exit!");
 }
@@ -37,7 +70,47 @@ unsafe extern "C" fn Java_com_example_binderserver_BinderServer_00024Companion_l
     mut _env: JNIEnv<'local>,
     _obj: JObject<'local>,
 ) {
-    std::thread::spawn(service_root);
+    let mut service = SERVICE_THREAD.lock().unwrap();
+    if service.is_some() {
+        warn!("Service already loaded");
+        return;
+    }
+
+    let (ready, shutdown) = mpsc::channel();
+    let thread = thread::spawn(move || {
+        let _guard = ServiceThreadGuard;
+        service_root(ready)
+    });
+    match shutdown.recv() {
+        Ok(shutdown) => *service = Some((shutdown, thread)),
+        Err(_) => {
+            error!("Service failed to start");
+            // the guard of the exiting thread takes the lock
+            drop(service);
+            let _ = thread.join();
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn Java_com_example_binderserver_BinderServer_00024Companion_unloadService<
+    'local,
+>(
+    mut _env: JNIEnv<'local>,
+    _obj: JObject<'local>,
+) {
+    let Some((shutdown, thread)) = SERVICE_THREAD.lock().unwrap().take() else {
+        warn!("Service not loaded");
+        return;
+    };
+
+    if let Err(e) = shutdown.shutdown() {
+        error!("Unable to stop service: {e}");
+        return;
+    }
+    if thread.join().is_err() {
+        error!("Service thread panicked");
+    }
 }
 
 #[tokio::main]
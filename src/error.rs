@@ -22,6 +22,64 @@ pub enum BinderError {
     BadType,
     #[error("InvalidOperation")]
     InvalidOperation,
+    #[error("UnknownTransaction")]
+    UnknownTransaction,
+    #[error("FailedTransaction")]
+    FailedTransaction,
+    #[error("DeadObject")]
+    DeadObject,
+    #[error("Status: {0}")]
+    Status(i32),
 }
 
 pub type Result<T> = std::result::Result<T, BinderError>;
+
+// http://aospxref.com/android-14.0.0_r2/xref/system/core/libutils/include/utils/Errors.h
+pub const UNKNOWN_ERROR: i32 = i32::MIN;
+pub const BAD_TYPE: i32 = UNKNOWN_ERROR + 1;
+pub const FAILED_TRANSACTION: i32 = UNKNOWN_ERROR + 2;
+pub const FDS_NOT_ALLOWED: i32 = UNKNOWN_ERROR + 7;
+pub const UNEXPECTED_NULL: i32 = UNKNOWN_ERROR + 8;
+
+/// `status_t` value of the error, as sent over binder.
+impl From<&BinderError> for i32 {
+    fn from(error: &BinderError) -> i32 {
+        use nix::libc;
+
+        match error {
+            BinderError::IoError(e) => e.raw_os_error().map_or(UNKNOWN_ERROR, |e| -e),
+            BinderError::NixError(e) => -(*e as i32),
+            BinderError::SliceError(_) | BinderError::NotEnoughData => -libc::ENODATA,
+            BinderError::Utf16Error(_)
+            | BinderError::Utf8Error(_)
+            | BinderError::FailedParseParcel(_)
+            | BinderError::BadValue => -libc::EINVAL,
+            BinderError::UnexpectedNull => UNEXPECTED_NULL,
+            BinderError::BadType => BAD_TYPE,
+            BinderError::InvalidOperation => -libc::ENOSYS,
+            BinderError::UnknownTransaction => -libc::EBADMSG,
+            BinderError::FailedTransaction => FAILED_TRANSACTION,
+            BinderError::DeadObject => -libc::EPIPE,
+            BinderError::Status(status) => *status,
+        }
+    }
+}
+
+/// Error of a `status_t` value received over binder.
+impl From<i32> for BinderError {
+    fn from(status: i32) -> BinderError {
+        use nix::libc;
+
+        match status {
+            status if status == -libc::EINVAL => BinderError::BadValue,
+            status if status == -libc::ENODATA => BinderError::NotEnoughData,
+            status if status == -libc::ENOSYS => BinderError::InvalidOperation,
+            status if status == -libc::EBADMSG => BinderError::UnknownTransaction,
+            status if status == -libc::EPIPE => BinderError::DeadObject,
+            UNEXPECTED_NULL => BinderError::UnexpectedNull,
+            BAD_TYPE => BinderError::BadType,
+            FAILED_TRANSACTION => BinderError::FailedTransaction,
+            status => BinderError::Status(status),
+        }
+    }
+}
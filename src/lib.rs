@@ -1,6 +1,6 @@
 #![allow(unsafe_op_in_unsafe_fn)]
-#![feature(never_type)]
 #![feature(let_chains)]
+#![cfg_attr(feature = "serde", feature(min_specialization))]
 
 // https://www.synacktiv.com/en/publications/binder-transactions-in-the-bowels-of-the-linux-kernel.html
 #[macro_use]
@@ -13,9 +13,51 @@ mod macros;
 pub mod parcel;
 pub mod service;
 pub mod stability;
+pub mod status;
+
+#[cfg(target_os = "android")]
+static ANDROID_VERSION: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
 
 #[cfg(target_os = "android")]
 pub fn get_android_version() -> u32 {
-    // TODO
-    15
+    use std::sync::atomic::Ordering;
+
+    match ANDROID_VERSION.load(Ordering::Relaxed) {
+        0 => {
+            let version = read_android_version();
+            ANDROID_VERSION.store(version, Ordering::Relaxed);
+            version
+        }
+        version => version,
+    }
+}
+
+#[cfg(target_os = "android")]
+fn read_android_version() -> u32 {
+    use nix::libc;
+
+    let mut value = [0u8; libc::PROP_VALUE_MAX as usize];
+    let len = unsafe {
+        libc::__system_property_get(
+            c"ro.build.version.release".as_ptr(),
+            value.as_mut_ptr() as *mut libc::c_char,
+        )
+    };
+
+    // "15", "12", "8.1.0", ...
+    std::str::from_utf8(&value[..len.max(0) as usize])
+        .ok()
+        .and_then(|release| release.split('.').next()?.parse().ok())
+        .unwrap_or_else(|| {
+            warn!("Unable to read ro.build.version.release, assume Android 15");
+            15
+        })
+}
+
+/// Drop cached system properties so they are read again on next use.
+///
+/// Called when a `SYSPROPS_TRANSACTION` is received.
+pub fn report_sysprop_change() {
+    #[cfg(target_os = "android")]
+    ANDROID_VERSION.store(0, std::sync::atomic::Ordering::Relaxed);
 }
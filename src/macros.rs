@@ -26,3 +26,166 @@ macro_rules! _io {
         ((($c1 as u32) << 8) | ($c2 as u32))
     };
 }
+
+/// Declare a binder interface without an `.aidl` file.
+///
+/// ```ignore
+/// binder_rs::declare_binder_interface! {
+///     /// Adds numbers.
+///     pub trait ICalculator["com.example.ICalculator"] {
+///         fn add(a: i32, b: i32) -> i32 = 0;
+///         oneway fn reset(value: i32) = 1;
+///     }
+///     proxy BpCalculator;
+///     native BnCalculator;
+/// }
+/// ```
+///
+/// Method `= n` is sent as transaction code `Transaction::FirstCall + n`.
+/// This generates:
+/// - the trait `ICalculator`, each method returning `Result<T, Status>`,
+/// - `BpCalculator`, its client over [`Service::call`](crate::service::Service::call),
+/// - `BnCalculator<T: ICalculator>`, a `BinderService` dispatching the
+///   transactions to `T` after checking the interface token.
+#[macro_export]
+macro_rules! declare_binder_interface {
+    (
+        $(#[$attr:meta])*
+        $vis:vis trait $interface:ident[$descriptor:expr] {
+            $($methods:tt)*
+        }
+        proxy $proxy:ident;
+        native $native:ident;
+    ) => {
+        $crate::declare_binder_interface! {
+            @parse
+            [$(#[$attr])* $vis $interface $descriptor $proxy $native]
+            []
+            $($methods)*
+        }
+    };
+
+    // normalize the methods to `[attrs] oneway name (args) [ret] code`
+    (
+        @parse $header:tt [$($parsed:tt)*]
+        $(#[$meta:meta])*
+        oneway fn $name:ident($($arg:ident: $ty:ty),* $(,)?) = $code:expr;
+        $($rest:tt)*
+    ) => {
+        $crate::declare_binder_interface! {
+            @parse $header
+            [$($parsed)* {[$(#[$meta])*] true $name ($($arg: $ty),*) [] $code}]
+            $($rest)*
+        }
+    };
+    (
+        @parse $header:tt [$($parsed:tt)*]
+        $(#[$meta:meta])*
+        fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)? = $code:expr;
+        $($rest:tt)*
+    ) => {
+        $crate::declare_binder_interface! {
+            @parse $header
+            [$($parsed)* {[$(#[$meta])*] false $name ($($arg: $ty),*) [$($ret)?] $code}]
+            $($rest)*
+        }
+    };
+
+    (
+        @parse
+        [$(#[$attr:meta])* $vis:vis $interface:ident $descriptor:tt $proxy:ident $native:ident]
+        [$({
+            [$(#[$meta:meta])*] $oneway:tt $name:ident ($($arg:ident: $ty:ty),*) [$($ret:ty)?] $code:expr
+        })*]
+    ) => {
+        $(#[$attr])*
+        $vis trait $interface {
+            $(
+                $(#[$meta])*
+                fn $name(&self, $($arg: $ty),*)
+                    -> ::std::result::Result<$crate::declare_binder_interface!(@ret [$($ret)?]), $crate::status::Status>;
+            )*
+        }
+
+        #[doc = concat!("Client of `", $descriptor, "`.")]
+        $vis struct $proxy<'a> {
+            service: $crate::service::Service<'a>,
+        }
+
+        impl<'a> $proxy<'a> {
+            pub const DESCRIPTOR: &'static str = $descriptor;
+
+            pub fn new(service: $crate::service::Service<'a>) -> Self {
+                Self { service }
+            }
+        }
+
+        impl $interface for $proxy<'_> {
+            $(
+                fn $name(&self, $($arg: $ty),*)
+                    -> ::std::result::Result<$crate::declare_binder_interface!(@ret [$($ret)?]), $crate::status::Status>
+                {
+                    #[allow(unused_mut)]
+                    let mut data = $crate::parcel::Parcel::new();
+                    $(data.write(&$arg)?;)*
+
+                    let code = $crate::service::Transaction::FirstCall as u32 + $code;
+                    $crate::declare_binder_interface!(@call self.service, code, data, $oneway, [$($ret)?])
+                }
+            )*
+        }
+
+        #[doc = concat!("Server of `", $descriptor, "`, to register to the service manager.")]
+        $vis struct $native<T: $interface>(pub T);
+
+        impl<T: $interface> $native<T> {
+            pub const DESCRIPTOR: &'static str = $descriptor;
+        }
+
+        impl<T: $interface> $crate::service::BinderService for $native<T> {
+            fn progress_request(
+                &self,
+                code: u32,
+                data: &mut $crate::parcel::Parcel,
+                #[allow(unused_variables)] reply: &mut $crate::parcel::Parcel,
+                _ctx: &$crate::service::CallingContext,
+                _flags: $crate::service::TransactionFlag,
+            ) -> ::std::result::Result<(), $crate::status::Status> {
+                data.enforce_interface(Self::DESCRIPTOR)?;
+
+                $(
+                    if code == $crate::service::Transaction::FirstCall as u32 + $code {
+                        $(let $arg: $ty = data.read()?;)*
+                        #[allow(unused_variables)]
+                        let ret = self.0.$name($($arg),*)?;
+                        $crate::declare_binder_interface!(@reply reply, ret, $oneway, [$($ret)?]);
+                        return Ok(());
+                    }
+                )*
+
+                Err($crate::error::BinderError::UnknownTransaction.into())
+            }
+        }
+    };
+
+    (@ret []) => { () };
+    (@ret [$ret:ty]) => { $ret };
+
+    (@call $service:expr, $code:ident, $data:ident, true, []) => {{
+        $service.call($code, &mut $data, $crate::service::TransactionFlag::OneWay)?;
+        Ok(())
+    }};
+    (@call $service:expr, $code:ident, $data:ident, false, []) => {{
+        $service.call($code, &mut $data, $crate::service::TransactionFlag::empty())?;
+        Ok(())
+    }};
+    (@call $service:expr, $code:ident, $data:ident, false, [$ret:ty]) => {{
+        let mut reply = $service.call($code, &mut $data, $crate::service::TransactionFlag::empty())?;
+        Ok(reply.read::<$ret>()?)
+    }};
+
+    (@reply $reply:ident, $value:ident, false, [$ret:ty]) => {
+        $reply.write(&$value)?
+    };
+    (@reply $reply:ident, $value:ident, $oneway:tt, []) => {};
+}
@@ -0,0 +1,228 @@
+//! Blobs of Java `Parcel.writeBlob`: small data is written in place, larger
+//! data is put in shared memory passed as a file descriptor, so that it
+//! doesn't count against the binder buffer (`BINDER_VM_SIZE`).
+//!
+//! ```text
+//! length   i32 (-1 for null)
+//! type     i32 (BLOB_INPLACE, BLOB_ASHMEM_IMMUTABLE or BLOB_ASHMEM_MUTABLE)
+//! data     the bytes padded to 4 bytes, or a file descriptor object
+//! ```
+//!
+//! Mutable regions are refused, the sender could change or shrink them while
+//! they are read. Of the others:
+//! - a memfd sealed with `F_SEAL_SHRINK` and `F_SEAL_WRITE` has no writable
+//!   mapping left, it is mapped and borrowed,
+//! - a memfd sealed with `F_SEAL_SHRINK` and only `F_SEAL_FUTURE_WRITE`, as
+//!   set by libcutils when `ashmem_set_prot_region` drops `PROT_WRITE`, or
+//!   an ashmem region whose protection mask lacks `PROT_WRITE`, can still be
+//!   written through the mappings of the sender: its content is copied.
+//!
+//! Blobs written here are memfds sealed against any change.
+//!
+//! http://aospxref.com/android-14.0.0_r2/xref/frameworks/native/libs/binder/Parcel.cpp
+
+use std::{
+    ffi::c_void,
+    fs::File,
+    io::Write,
+    num::NonZero,
+    ops::Deref,
+    os::{
+        fd::{AsRawFd, BorrowedFd, OwnedFd},
+        unix::fs::FileExt,
+    },
+    ptr::NonNull,
+};
+
+use nix::{
+    errno::Errno,
+    fcntl::{FcntlArg, SealFlag, fcntl},
+    ioctl_none, libc,
+    sys::{
+        memfd::{MemFdCreateFlag, memfd_create},
+        mman::{MapFlags, ProtFlags, mmap, munmap},
+        stat::fstat,
+    },
+};
+
+use crate::error::{BinderError, Result};
+
+/// Larger blobs are written in shared memory.
+pub const BLOB_INPLACE_LIMIT: usize = 16 * 1024;
+
+pub const BLOB_INPLACE: i32 = 0;
+pub const BLOB_ASHMEM_IMMUTABLE: i32 = 1;
+pub const BLOB_ASHMEM_MUTABLE: i32 = 2;
+
+/// Seals making the content of a memfd immutable.
+const IMMUTABLE_SEALS: SealFlag = SealFlag::F_SEAL_SHRINK
+    .union(SealFlag::F_SEAL_GROW)
+    .union(SealFlag::F_SEAL_WRITE);
+
+/// Either seal keeps the content of a memfd from being written through
+/// new mappings.
+const WRITE_SEALS: SealFlag = SealFlag::F_SEAL_WRITE.union(SealFlag::F_SEAL_FUTURE_WRITE);
+
+/// What keeps the sender of a shared region from changing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protection {
+    /// `F_SEAL_WRITE`: no writable mapping of the region exists.
+    Sealed,
+    /// New mappings are read only, the existing ones may still be writable.
+    ReadOnly,
+}
+
+const ASHMEM_IOC: u8 = 0x77;
+ioctl_none!(ashmem_get_size, ASHMEM_IOC, 4);
+ioctl_none!(ashmem_get_prot_mask, ASHMEM_IOC, 6);
+
+/// The data of a blob read from a [`Parcel`](super::Parcel).
+pub struct Blob {
+    data: BlobData,
+}
+
+enum BlobData {
+    Inplace(Vec<u8>),
+    Mapped { ptr: NonNull<c_void>, len: usize },
+}
+
+// SAFETY: the mapping is owned by the blob, and of a memfd sealed against
+// writes: nobody can change it.
+unsafe impl Send for Blob {}
+unsafe impl Sync for Blob {}
+
+impl Blob {
+    pub(crate) fn inplace(data: Vec<u8>) -> Self {
+        Self {
+            data: BlobData::Inplace(data),
+        }
+    }
+
+    /// Read `len` bytes of the read only memfd or ashmem region `fd`: map
+    /// them when sealed against writes, copy them otherwise.
+    pub(crate) fn from_shared(fd: BorrowedFd, len: usize) -> Result<Self> {
+        let (size, protection) = read_only_size(fd)?;
+        if size < 0 || (size as u64) < len as u64 {
+            error!("Blob: {len} bytes expected, the region has {size}");
+            return Err(BinderError::BadValue);
+        }
+
+        let Some(length) = NonZero::new(len) else {
+            return Ok(Self::inplace(Vec::new()));
+        };
+        if protection == Protection::ReadOnly {
+            // a borrow of a mapping the sender can write through would be
+            // unsound, read a snapshot instead
+            let mut data = vec![0; len];
+            File::from(fd.try_clone_to_owned()?).read_exact_at(&mut data, 0)?;
+            return Ok(Self::inplace(data));
+        }
+        // SAFETY: a new shared read only mapping, of a region which can't
+        // shrink nor be written.
+        let ptr = unsafe {
+            mmap(
+                None,
+                length,
+                ProtFlags::PROT_READ,
+                MapFlags::MAP_SHARED,
+                fd,
+                0,
+            )?
+        };
+        Ok(Self {
+            data: BlobData::Mapped { ptr, len },
+        })
+    }
+
+    /// Whether the data is mapped from shared memory rather than copied,
+    /// only memfds sealed with `F_SEAL_WRITE` are.
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.data, BlobData::Mapped { .. })
+    }
+}
+
+impl Deref for Blob {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.data {
+            BlobData::Inplace(data) => data,
+            // SAFETY: the mapping lives as long as `self`, and its memfd is
+            // sealed against writes.
+            BlobData::Mapped { ptr, len } => unsafe {
+                std::slice::from_raw_parts(ptr.as_ptr().cast(), *len)
+            },
+        }
+    }
+}
+
+impl Drop for Blob {
+    fn drop(&mut self) {
+        if let BlobData::Mapped { ptr, len } = self.data {
+            // SAFETY: mapped by `Blob::map`, no slice of it outlives `self`.
+            if let Err(e) = unsafe { munmap(ptr, len) } {
+                error!("Blob: unable to unmap: {e}");
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Blob {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Blob")
+            .field("len", &self.len())
+            .field("mapped", &self.is_mapped())
+            .finish()
+    }
+}
+
+/// The size of the region `fd` and what protects it, if it is read only.
+fn read_only_size(fd: BorrowedFd) -> Result<(i64, Protection)> {
+    let fd = fd.as_raw_fd();
+    match fcntl(fd, FcntlArg::F_GET_SEALS) {
+        Ok(seals) => {
+            let seals = SealFlag::from_bits_truncate(seals);
+            if !seals.contains(SealFlag::F_SEAL_SHRINK) || !seals.intersects(WRITE_SEALS) {
+                error!("Blob: refusing to map an unsealed memfd, seals: {seals:?}");
+                return Err(BinderError::InvalidOperation);
+            }
+            let protection = if seals.contains(SealFlag::F_SEAL_WRITE) {
+                Protection::Sealed
+            } else {
+                Protection::ReadOnly
+            };
+            Ok((fstat(fd)?.st_size, protection))
+        }
+        // not a memfd, maybe ashmem
+        Err(Errno::EINVAL) => {
+            // SAFETY: ashmem ioctls without argument, `fd` is open.
+            let prot = unsafe { ashmem_get_prot_mask(fd) }.map_err(|e| {
+                error!("Blob: neither a memfd nor ashmem: {e}");
+                BinderError::InvalidOperation
+            })?;
+            if prot & libc::PROT_WRITE != 0 {
+                error!("Blob: refusing to map a writable ashmem region");
+                return Err(BinderError::InvalidOperation);
+            }
+            // SAFETY: as above.
+            let size = unsafe { ashmem_get_size(fd) }?;
+            Ok((size.into(), Protection::ReadOnly))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A memfd holding `data`, sealed against any change.
+pub(crate) fn sealed_memfd(data: &[u8]) -> Result<OwnedFd> {
+    let fd = memfd_create(
+        c"Parcel Blob",
+        MemFdCreateFlag::MFD_CLOEXEC | MemFdCreateFlag::MFD_ALLOW_SEALING,
+    )?;
+    let mut file = File::from(fd);
+    file.write_all(data)?;
+    fcntl(
+        file.as_raw_fd(),
+        FcntlArg::F_ADD_SEALS(IMMUTABLE_SEALS | SealFlag::F_SEAL_SEAL),
+    )?;
+    Ok(file.into())
+}
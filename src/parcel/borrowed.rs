@@ -0,0 +1,112 @@
+//! Reads borrowing the data of a parcel instead of copying it.
+//!
+//! The values borrow the buffer of the parcel for its lifetime `'p`: the
+//! binder mapping for a parcel of a received transaction, or the slice
+//! given to [`Parcel::from_slice`]. Owned parcels, including borrowed ones
+//! which were written to, can't be read this way.
+
+use super::{Parcel, Pod};
+use crate::error::{BinderError, Result};
+
+/// A value which can be read from a [`Parcel`] without copying its data.
+pub trait DeserializeBorrowed<'p>: Sized {
+    fn deserialize_borrowed(parcel: &mut Parcel<'p>) -> Result<Self>;
+}
+
+/// Elements of a slice which can be borrowed from a [`Parcel`]: written as
+/// an `i32` length (`-1` for null) followed by the packed elements.
+///
+/// Implement it for `#[repr(C)]` structs also implementing [`Pod`] to borrow
+/// arrays of them. Values in a parcel are only aligned to 4 bytes, so the
+/// elements can't need more: `i64`, `u64` and `f64` arrays are copied with
+/// [`Parcel::read`] instead.
+pub trait DeserializeBorrowedSlice: Pod {
+    /// Read a (possibly null) slice.
+    fn deserialize_slice<'p>(parcel: &mut Parcel<'p>) -> Result<Option<&'p [Self]>> {
+        const {
+            assert!(
+                align_of::<Self>() <= 4,
+                "parcel data is only aligned to 4 bytes"
+            )
+        };
+        let Some(len) = read_len(parcel)? else {
+            return Ok(None);
+        };
+        let size = len.checked_mul(size_of::<Self>()).ok_or_else(|| {
+            error!("Parcel: array of {len} too large");
+            BinderError::BadValue
+        })?;
+        cast(parcel.read_borrowed_data(size)?).map(Some)
+    }
+}
+
+/// `byte[]`.
+impl DeserializeBorrowedSlice for u8 {}
+impl DeserializeBorrowedSlice for i8 {}
+impl DeserializeBorrowedSlice for i32 {}
+impl DeserializeBorrowedSlice for u32 {}
+impl DeserializeBorrowedSlice for f32 {}
+
+/// A UTF-16 string (`String16`), without its nul terminator.
+impl DeserializeBorrowedSlice for u16 {
+    fn deserialize_slice<'p>(parcel: &mut Parcel<'p>) -> Result<Option<&'p [Self]>> {
+        let Some(len) = read_len(parcel)? else {
+            return Ok(None);
+        };
+        let size = len
+            .checked_add(1)
+            .and_then(|len| len.checked_mul(size_of::<u16>()))
+            .ok_or_else(|| {
+                error!("Parcel: string of {len} too large");
+                BinderError::BadValue
+            })?;
+        let units: &[u16] = cast(parcel.read_borrowed_data(size)?)?;
+        if units[len] != 0 {
+            error!("Parcel: string not terminated");
+            return Err(BinderError::BadValue);
+        }
+        Ok(Some(&units[..len]))
+    }
+}
+
+impl<'p, T: DeserializeBorrowedSlice> DeserializeBorrowed<'p> for &'p [T] {
+    fn deserialize_borrowed(parcel: &mut Parcel<'p>) -> Result<Self> {
+        T::deserialize_slice(parcel)?.ok_or_else(|| {
+            error!("DeserializeBorrowed for slice: UnexpectedNull");
+            BinderError::UnexpectedNull
+        })
+    }
+}
+
+impl<'p, T: DeserializeBorrowedSlice> DeserializeBorrowed<'p> for Option<&'p [T]> {
+    fn deserialize_borrowed(parcel: &mut Parcel<'p>) -> Result<Self> {
+        T::deserialize_slice(parcel)
+    }
+}
+
+/// A length, `None` for null.
+fn read_len(parcel: &mut Parcel) -> Result<Option<usize>> {
+    match parcel.read::<i32>()? {
+        -1 => Ok(None),
+        len if len < 0 => {
+            error!("Parcel: bad array length: {len}");
+            Err(BinderError::BadValue)
+        }
+        len => Ok(Some(len as usize)),
+    }
+}
+
+fn cast<T: Pod>(bytes: &[u8]) -> Result<&[T]> {
+    // SAFETY: any bit pattern is a valid `T`.
+    let (prefix, values, suffix) = unsafe { bytes.align_to::<T>() };
+    if !prefix.is_empty() || !suffix.is_empty() {
+        error!(
+            "Parcel: {} bytes at {:p} are not an aligned array of {}",
+            bytes.len(),
+            bytes.as_ptr(),
+            std::any::type_name::<T>()
+        );
+        return Err(BinderError::BadValue);
+    }
+    Ok(values)
+}
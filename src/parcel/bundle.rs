@@ -0,0 +1,211 @@
+//! Java `Bundle` and `PersistableBundle`.
+//!
+//! http://aospxref.com/android-14.0.0_r2/xref/frameworks/base/core/java/android/os/BaseBundle.java
+
+use std::collections::BTreeMap;
+
+use super::{Parcel, parcelable::Parcelable, value::ParcelValue};
+use crate::error::{BinderError, Result};
+
+/// 'BNDL'
+const BUNDLE_MAGIC: i32 = 0x4C444E42;
+/// 'BNDN', a bundle written by native code.
+const BUNDLE_MAGIC_NATIVE: i32 = 0x4C444E44;
+
+/// `String.hashCode`, by which `ArrayMap` orders its keys.
+fn java_hash_code(s: &str) -> i32 {
+    s.encode_utf16()
+        .fold(0i32, |h, c| h.wrapping_mul(31).wrapping_add(c as i32))
+}
+
+/// String keyed values of a Java `Bundle` or `PersistableBundle`.
+///
+/// In a parcel: the length of the content (`0` for an empty bundle), the
+/// magic, the number of entries, then each key followed by its value as
+/// written by `Parcel.writeValue`. The entries are in the order of the
+/// backing `ArrayMap`, by the `String.hashCode` of their keys. Parcelables of unknown classes are kept
+/// undecoded, which requires the length-prefixed values of Android 13 and
+/// later.
+///
+/// A persistable bundle only holds the values of
+/// [`ParcelValue::is_persistable`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bundle {
+    persistable: bool,
+    values: BTreeMap<String, ParcelValue>,
+}
+
+impl Bundle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty `PersistableBundle`.
+    pub fn new_persistable() -> Self {
+        Self {
+            persistable: true,
+            values: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_persistable(&self) -> bool {
+        self.persistable
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&ParcelValue> {
+        self.values.get(key)
+    }
+
+    pub fn get_string(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            ParcelValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn get_int(&self, key: &str) -> Option<i32> {
+        match self.get(key)? {
+            ParcelValue::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_long(&self, key: &str) -> Option<i64> {
+        match self.get(key)? {
+            ParcelValue::Long(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)? {
+            ParcelValue::Boolean(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_bundle(&self, key: &str) -> Option<&Bundle> {
+        match self.get(key)? {
+            ParcelValue::Bundle(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Insert a value, returning the previous one.
+    /// Fails with [`BinderError::BadValue`] if a persistable bundle can't hold it.
+    pub fn insert(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<ParcelValue>,
+    ) -> Result<Option<ParcelValue>> {
+        let value = value.into();
+        if self.persistable && !value.is_persistable() {
+            error!(
+                "PersistableBundle can't hold a value of type {}",
+                value.tag()
+            );
+            return Err(BinderError::BadValue);
+        }
+        Ok(self.values.insert(key.into(), value))
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<ParcelValue> {
+        self.values.remove(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ParcelValue)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v))
+    }
+}
+
+// BaseBundle.writeToParcelInner / readFromParcelInner
+impl Parcelable for Bundle {
+    fn write_to_parcel(&self, parcel: &mut Parcel) -> Result<()> {
+        if self.values.is_empty() {
+            return parcel.write(&0i32);
+        }
+
+        let len_pos = parcel.data_position();
+        parcel.write(&-1i32)?;
+        parcel.write(&BUNDLE_MAGIC)?;
+
+        // the length doesn't cover the magic
+        let start = parcel.data_position();
+        let count = i32::try_from(self.values.len()).map_err(|_| BinderError::BadValue)?;
+        parcel.write(&count)?;
+        let mut entries: Vec<_> = self.values.iter().collect();
+        entries.sort_by_key(|(key, _)| java_hash_code(key));
+        for (key, value) in entries {
+            parcel.write(key)?;
+            parcel.write(value)?;
+        }
+
+        let end = parcel.data_position();
+        let len = i32::try_from(end - start).map_err(|_| BinderError::BadValue)?;
+        parcel.set_data_position(len_pos);
+        parcel.write(&len)?;
+        parcel.set_data_position(end);
+        Ok(())
+    }
+
+    fn read_from_parcel(&mut self, parcel: &mut Parcel) -> Result<()> {
+        self.values.clear();
+
+        let len: i32 = parcel.read()?;
+        if len < 0 {
+            error!("Bad bundle length: {len}");
+            return Err(BinderError::BadValue);
+        }
+        if len == 0 {
+            return Ok(());
+        }
+
+        let magic: i32 = parcel.read()?;
+        if magic != BUNDLE_MAGIC && magic != BUNDLE_MAGIC_NATIVE {
+            error!("Bad bundle magic: {magic:#X}");
+            return Err(BinderError::BadType);
+        }
+
+        // the length doesn't cover the magic
+        let len = len as usize;
+        if len > parcel.data_avail() {
+            error!("Bundle of {len} bytes, {} available", parcel.data_avail());
+            return Err(BinderError::NotEnoughData);
+        }
+        let end = parcel.data_position() + len;
+
+        let count: i32 = parcel.read()?;
+        if count < 0 {
+            error!("Bad bundle size: {count}");
+            return Err(BinderError::BadValue);
+        }
+        parcel.check_alloc(count as usize, size_of::<(String, ParcelValue)>())?;
+        for _ in 0..count {
+            let key: String = parcel.read()?;
+            let value: ParcelValue = parcel.read()?;
+            if parcel.data_position() > end {
+                error!("Bundle value {key:?} overflows the bundle");
+                return Err(BinderError::BadValue);
+            }
+            if self.persistable && !value.is_persistable() {
+                error!("PersistableBundle holds a value of type {}", value.tag());
+                return Err(BinderError::BadValue);
+            }
+            self.values.insert(key, value);
+        }
+
+        parcel.set_data_position(end);
+        Ok(())
+    }
+}
+
+crate::impl_serialize_for_parcelable!(Bundle);
+crate::impl_deserialize_for_parcelable!(Bundle);
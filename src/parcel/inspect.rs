@@ -0,0 +1,456 @@
+//! Best effort decoding of the structure of a parcel, for debugging.
+//!
+//! A parcel doesn't describe its content, so the decoding is a guess:
+//! objects are found from the object offsets, everything else from what the
+//! data looks like. Anything not recognized is shown as `int` words.
+
+use std::{fmt, ops::Range};
+
+use num_traits::FromPrimitive;
+
+use crate::binder::{binder_type::BinderType, constant::INTERFACE_HEADER};
+
+/// Parcelables nested deeper are shown as words.
+const MAX_DEPTH: usize = 32;
+/// Bytes of a `byte[]` shown.
+const MAX_BYTES_SHOWN: usize = 16;
+
+/// What a range of the data was decoded as.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+    /// An interface token, with its descriptor.
+    InterfaceToken(String),
+    StrictModePolicy(i32),
+    WorkSource(i32),
+    InterfaceHeader,
+    String16(String),
+    /// An array of this many elements.
+    Array(usize),
+    /// A `byte[]` of this many bytes.
+    ByteArray(usize),
+    /// A binder, handle or file descriptor object.
+    Object {
+        binder_type: BinderType,
+        flags: u32,
+        value: usize,
+        cookie: usize,
+    },
+    /// A `binder_fd_array_object`.
+    FdArray {
+        num_fds: usize,
+        parent: usize,
+        parent_offset: usize,
+    },
+    /// A `binder_buffer_object`.
+    Buffer {
+        flags: u32,
+        buffer: usize,
+        length: usize,
+        parent: usize,
+        parent_offset: usize,
+    },
+    /// A structured parcelable: its flag, size and fields.
+    Parcelable,
+    NonNullFlag,
+    Null,
+    /// The size of a structured parcelable, itself included.
+    Size(usize),
+    Int(i32),
+    /// Trailing bytes, less than a word.
+    Bytes,
+}
+
+/// A decoded range of the data, and the ranges it is made of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub range: Range<usize>,
+    pub kind: NodeKind,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn new(range: Range<usize>, kind: NodeKind) -> Self {
+        Self {
+            range,
+            kind,
+            children: Vec::new(),
+        }
+    }
+
+    fn with_children(range: Range<usize>, kind: NodeKind, children: Vec<Node>) -> Self {
+        Self {
+            range,
+            kind,
+            children,
+        }
+    }
+}
+
+/// The decoded structure of a parcel, given by [`Parcel::inspect`](super::Parcel::inspect).
+///
+/// Displayed as a tree, one range per line:
+///
+/// ```text
+/// 0x0000..0x0040 interface token "android.os.IServiceManager"
+///   0x0000..0x0004 strict mode policy 0x42000004
+/// ...
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inspection {
+    pub nodes: Vec<Node>,
+    data: Vec<u8>,
+}
+
+impl Inspection {
+    pub(crate) fn new(data: &[u8], objects: &[usize]) -> Self {
+        let mut offsets = objects.to_vec();
+        offsets.sort_unstable();
+        let decoder = Decoder {
+            data,
+            objects: &offsets,
+        };
+
+        let mut pos = 0;
+        let mut nodes = Vec::new();
+        if let Some(token) = decoder.interface_token(0, data.len()) {
+            pos = token.range.end;
+            nodes.push(token);
+        }
+        nodes.extend(decoder.sequence(pos, data.len(), 0));
+
+        Self {
+            nodes,
+            data: data.to_vec(),
+        }
+    }
+
+    fn fmt_node(&self, f: &mut fmt::Formatter<'_>, node: &Node, depth: usize) -> fmt::Result {
+        let Range { start, end } = node.range;
+        write!(
+            f,
+            "{:indent$}{start:#06x}..{end:#06x} ",
+            "",
+            indent = depth * 2
+        )?;
+        match &node.kind {
+            NodeKind::InterfaceToken(descriptor) => write!(f, "interface token {descriptor:?}")?,
+            NodeKind::StrictModePolicy(policy) => write!(f, "strict mode policy {policy:#x}")?,
+            NodeKind::WorkSource(uid) => write!(f, "work source {uid}")?,
+            NodeKind::InterfaceHeader => write!(f, "interface header {INTERFACE_HEADER:#x}")?,
+            NodeKind::String16(s) => write!(f, "string16 {s:?}")?,
+            NodeKind::Array(len) => write!(f, "array of {len}")?,
+            NodeKind::ByteArray(len) => {
+                let bytes = &self.data[start + 4..start + 4 + len.min(&MAX_BYTES_SHOWN)];
+                write!(f, "byte[{len}] {bytes:02x?}")?;
+                if *len > MAX_BYTES_SHOWN {
+                    write!(f, "...")?;
+                }
+            }
+            NodeKind::Object {
+                binder_type,
+                flags,
+                value,
+                cookie,
+            } => write!(
+                f,
+                "{binder_type:?} object {value:#x}, cookie {cookie:#x}, flags {flags:#x}"
+            )?,
+            NodeKind::FdArray {
+                num_fds,
+                parent,
+                parent_offset,
+            } => write!(
+                f,
+                "fd array of {num_fds}, parent {parent} at {parent_offset:#x}"
+            )?,
+            NodeKind::Buffer {
+                flags,
+                buffer,
+                length,
+                parent,
+                parent_offset,
+            } => write!(
+                f,
+                "buffer {buffer:#x} of {length} bytes, parent {parent} at {parent_offset:#x}, flags {flags:#x}"
+            )?,
+            NodeKind::Parcelable => write!(f, "parcelable")?,
+            NodeKind::NonNullFlag => write!(f, "non-null flag")?,
+            NodeKind::Null => write!(f, "null")?,
+            NodeKind::Size(size) => write!(f, "size {size}")?,
+            NodeKind::Int(value) => write!(f, "int {value} ({:#x})", *value as u32)?,
+            NodeKind::Bytes => write!(f, "bytes {:02x?}", &self.data[start..end])?,
+        }
+        writeln!(f)?;
+
+        for child in &node.children {
+            self.fmt_node(f, child, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for node in &self.nodes {
+            self.fmt_node(f, node, 0)?;
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    /// Sorted object offsets.
+    objects: &'a [usize],
+}
+
+impl Decoder<'_> {
+    fn word(&self, pos: usize) -> Option<i32> {
+        let bytes = self.data.get(pos..pos.checked_add(4)?)?;
+        Some(i32::from_ne_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn usize_at(&self, pos: usize) -> Option<usize> {
+        let bytes = self.data.get(pos..pos.checked_add(size_of::<usize>())?)?;
+        Some(usize::from_ne_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn is_object(&self, pos: usize) -> bool {
+        self.objects.binary_search(&pos).is_ok()
+    }
+
+    /// Whether an object starts within `range`.
+    fn has_object(&self, range: Range<usize>) -> bool {
+        let first = self.objects.partition_point(|&offset| offset < range.start);
+        self.objects
+            .get(first)
+            .is_some_and(|&offset| offset < range.end)
+    }
+
+    /// Decode `start..end` as a sequence of values.
+    fn sequence(&self, mut pos: usize, end: usize, depth: usize) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        while pos < end {
+            let node = self
+                .object(pos, end)
+                .or_else(|| self.parcelable(pos, end, depth))
+                .or_else(|| self.string16(pos, end, 1))
+                .or_else(|| self.array(pos, end, depth))
+                .unwrap_or_else(|| match self.word(pos) {
+                    Some(value) if pos + 4 <= end => Node::new(pos..pos + 4, NodeKind::Int(value)),
+                    _ => Node::new(pos..end, NodeKind::Bytes),
+                });
+            pos = node.range.end;
+            nodes.push(node);
+        }
+        nodes
+    }
+
+    fn interface_token(&self, pos: usize, end: usize) -> Option<Node> {
+        let policy = self.word(pos)?;
+        let work_source = self.word(pos + 4)?;
+        if self.word(pos + 8)? as u32 != INTERFACE_HEADER || self.has_object(pos..pos + 12) {
+            return None;
+        }
+        let descriptor = self.string16(pos + 12, end, 0)?;
+        let NodeKind::String16(name) = &descriptor.kind else {
+            return None;
+        };
+
+        Some(Node::with_children(
+            pos..descriptor.range.end,
+            NodeKind::InterfaceToken(name.clone()),
+            vec![
+                Node::new(pos..pos + 4, NodeKind::StrictModePolicy(policy)),
+                Node::new(pos + 4..pos + 8, NodeKind::WorkSource(work_source)),
+                Node::new(pos + 8..pos + 12, NodeKind::InterfaceHeader),
+                descriptor,
+            ],
+        ))
+    }
+
+    fn object(&self, pos: usize, end: usize) -> Option<Node> {
+        if !self.is_object(pos) {
+            return None;
+        }
+        let binder_type = BinderType::from_u32(self.word(pos)? as u32)?;
+        let flags = self.word(pos + 4)? as u32;
+        let word = size_of::<usize>();
+
+        let (size, kind) = match binder_type {
+            BinderType::Fda => (
+                8 + 3 * word,
+                NodeKind::FdArray {
+                    num_fds: self.usize_at(pos + 8)?,
+                    parent: self.usize_at(pos + 8 + word)?,
+                    parent_offset: self.usize_at(pos + 8 + 2 * word)?,
+                },
+            ),
+            BinderType::Ptr => (
+                8 + 4 * word,
+                NodeKind::Buffer {
+                    flags,
+                    buffer: self.usize_at(pos + 8)?,
+                    length: self.usize_at(pos + 8 + word)?,
+                    parent: self.usize_at(pos + 8 + 2 * word)?,
+                    parent_offset: self.usize_at(pos + 8 + 3 * word)?,
+                },
+            ),
+            _ => (
+                8 + 2 * word,
+                NodeKind::Object {
+                    binder_type,
+                    flags,
+                    value: self.usize_at(pos + 8)?,
+                    cookie: self.usize_at(pos + 8 + word)?,
+                },
+            ),
+        };
+
+        (pos + size <= end).then(|| Node::new(pos..pos + size, kind))
+    }
+
+    /// A non-null flag followed by a size covering at least one field.
+    fn parcelable(&self, pos: usize, end: usize, depth: usize) -> Option<Node> {
+        if depth >= MAX_DEPTH || self.word(pos)? != 1 {
+            return None;
+        }
+        let size = usize::try_from(self.word(pos + 4)?).ok()?;
+        let body_end = (pos + 4).checked_add(size)?;
+        if size < 8 || size % 4 != 0 || body_end > end {
+            return None;
+        }
+        // an object may not start in the header, nor straddle the end
+        if self.has_object(pos..pos + 8) || self.straddles(body_end) {
+            return None;
+        }
+
+        let mut children = vec![
+            Node::new(pos..pos + 4, NodeKind::NonNullFlag),
+            Node::new(pos + 4..pos + 8, NodeKind::Size(size)),
+        ];
+        children.extend(self.sequence(pos + 8, body_end, depth + 1));
+        Some(Node::with_children(
+            pos..body_end,
+            NodeKind::Parcelable,
+            children,
+        ))
+    }
+
+    /// Whether an object starting before `pos` ends after it.
+    fn straddles(&self, pos: usize) -> bool {
+        let before = self.objects.partition_point(|&offset| offset < pos);
+        before > 0 && self.objects[before - 1] + 8 + 2 * size_of::<usize>() > pos
+    }
+
+    /// A string of at least `min_len` printable characters.
+    fn string16(&self, pos: usize, end: usize, min_len: usize) -> Option<Node> {
+        let len = usize::try_from(self.word(pos)?).ok()?;
+        if len < min_len || len > (end - pos) / 2 {
+            return None;
+        }
+        let str_end = pos + 4 + ((len + 1) * 2).next_multiple_of(4);
+        if str_end > end || self.has_object(pos..str_end) {
+            return None;
+        }
+
+        let units: Vec<u16> = self.data[pos + 4..pos + 4 + (len + 1) * 2]
+            .chunks_exact(2)
+            .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+            .collect();
+        if units[len] != 0 {
+            return None;
+        }
+        let s = String::from_utf16(&units[..len]).ok()?;
+        if s.chars()
+            .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+        {
+            return None;
+        }
+
+        Some(Node::new(pos..str_end, NodeKind::String16(s)))
+    }
+
+    /// A length followed by recognized elements, or by ints or bytes
+    /// filling the rest of `pos..end` exactly.
+    fn array(&self, pos: usize, end: usize, depth: usize) -> Option<Node> {
+        let len = usize::try_from(self.word(pos)?).ok()?;
+        let available = end.checked_sub(pos + 4)?;
+        if len == 0 || len > available {
+            return None;
+        }
+
+        let elements = self
+            .elements(pos + 4, end, len, |pos| {
+                self.string16(pos, end, 0).or_else(|| self.null(pos, -1))
+            })
+            .filter(|nodes| {
+                nodes
+                    .iter()
+                    .any(|n| matches!(&n.kind, NodeKind::String16(s) if !s.is_empty()))
+            })
+            .or_else(|| {
+                self.elements(pos + 4, end, len, |pos| {
+                    self.parcelable(pos, end, depth)
+                        .or_else(|| self.null(pos, 0))
+                })
+                .filter(|nodes| nodes.iter().any(|n| n.kind == NodeKind::Parcelable))
+            })
+            .or_else(|| self.elements(pos + 4, end, len, |pos| self.object(pos, end)));
+        if let Some(elements) = elements {
+            let array_end = elements.last().map_or(pos + 4, |n| n.range.end);
+            return Some(Node::with_children(
+                pos..array_end,
+                NodeKind::Array(len),
+                elements,
+            ));
+        }
+
+        // rather show strings than bytes or ints they would be part of
+        if self.has_object(pos..end)
+            || (pos + 4..end)
+                .step_by(4)
+                .any(|at| self.string16(at, end, 1).is_some())
+        {
+            return None;
+        }
+        if len >= 2 && len * 4 == available {
+            let ints = (0..len)
+                .map(|i| {
+                    let at = pos + 4 + i * 4;
+                    Node::new(at..at + 4, NodeKind::Int(self.word(at).unwrap()))
+                })
+                .collect();
+            return Some(Node::with_children(pos..end, NodeKind::Array(len), ints));
+        }
+        if len % 4 != 0 && len.next_multiple_of(4) == available {
+            return Some(Node::new(pos..end, NodeKind::ByteArray(len)));
+        }
+        None
+    }
+
+    fn elements(
+        &self,
+        mut pos: usize,
+        end: usize,
+        len: usize,
+        element: impl Fn(usize) -> Option<Node>,
+    ) -> Option<Vec<Node>> {
+        let mut nodes = Vec::new();
+        for _ in 0..len {
+            if pos >= end {
+                return None;
+            }
+            let node = element(pos)?;
+            pos = node.range.end;
+            nodes.push(node);
+        }
+        Some(nodes)
+    }
+
+    /// A null marker: `-1` for strings and arrays, `0` for parcelables.
+    fn null(&self, pos: usize, marker: i32) -> Option<Node> {
+        (self.word(pos)? == marker && !self.is_object(pos))
+            .then(|| Node::new(pos..pos + 4, NodeKind::Null))
+    }
+}
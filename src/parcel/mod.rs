@@ -1,11 +1,28 @@
+use std::os::fd::{AsFd, IntoRawFd, OwnedFd};
+
+use blob::{BLOB_ASHMEM_IMMUTABLE, BLOB_ASHMEM_MUTABLE, BLOB_INPLACE, BLOB_INPLACE_LIMIT, Blob};
+use borrowed::DeserializeBorrowed;
+use inspect::Inspection;
 use parcelable::{Deserialize, Serialize};
 use pretty_hex::pretty_hex;
+use value::ParcelValue;
 
 use crate::{
     binder::{binder_type::BinderType, constant::INTERFACE_HEADER, flat_object::BinderFlatObject},
     error::{BinderError, Result},
+    stability::Stability,
 };
+pub mod blob;
+pub mod borrowed;
+pub mod bundle;
+pub mod inspect;
 pub mod parcelable;
+pub mod pool;
+pub mod record;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod string8;
+pub mod value;
 const STRICT_MODE_PENALTY_GATHER: i32 = 1 << 31;
 #[inline]
 pub(crate) fn pad_size(len: usize) -> usize {
@@ -38,12 +55,49 @@ impl CharType for u16 {
     }
 }
 
-pub(crate) enum ParcelData<T: Clone + Default + 'static> {
+/// Types which can be copied to and from a parcel as raw bytes.
+///
+/// # Safety
+/// Any bit pattern must be a valid value of the type, and the type must
+/// not have padding bytes.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(unsafe impl Pod for $ty {})*
+    };
+}
+
+impl_pod!(
+    i8, u8, i16, u16, i32, u32, i64, u64, i128, u128, isize, usize, f32, f64
+);
+
+/// Zero the whole allocation of `data`, in a way the compiler can't elide.
+fn zeroize(data: &mut Vec<u8>) {
+    let ptr = data.as_mut_ptr();
+    for i in 0..data.capacity() {
+        // SAFETY: within the allocation of `data`.
+        unsafe { ptr.add(i).write_volatile(0) };
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+/// Bytes of a slice of [`Pod`] values.
+fn pod_bytes<T: Pod>(values: &[T]) -> &[u8] {
+    // SAFETY: `T` has no padding, so all the bytes are initialized.
+    unsafe { std::slice::from_raw_parts(values.as_ptr() as *const u8, size_of_val(values)) }
+}
+
+/// Storage of a parcel: owned, or borrowed from the buffer the driver
+/// delivered a transaction in.
+///
+/// A borrowed buffer is read only, it is copied on the first write.
+pub(crate) enum ParcelData<'a, T: Clone> {
     Vec(Vec<T>),
-    Slice(&'static mut [T]),
+    Slice(&'a [T]),
 }
 
-impl<T: Clone + Default> ParcelData<T> {
+impl<T: Clone> ParcelData<'_, T> {
     fn new() -> Self {
         ParcelData::Vec(Vec::new())
     }
@@ -56,10 +110,6 @@ impl<T: Clone + Default> ParcelData<T> {
         ParcelData::Vec(data)
     }
 
-    fn from_raw_parts_mut(data: *mut T, len: usize) -> Self {
-        ParcelData::Slice(unsafe { std::slice::from_raw_parts_mut(data, len) })
-    }
-
     fn as_slice(&self) -> &[T] {
         match self {
             ParcelData::Vec(v) => v.as_slice(),
@@ -67,88 +117,94 @@ impl<T: Clone + Default> ParcelData<T> {
         }
     }
 
-    fn as_mut_slice(&mut self) -> &mut [T] {
-        match self {
-            ParcelData::Vec(v) => v.as_mut_slice(),
-            ParcelData::Slice(_) => panic!("ParcelData::Slice can't support as_mut_slice()."),
+    /// The owned buffer, copying a borrowed one first.
+    fn to_mut(&mut self) -> &mut Vec<T> {
+        if let ParcelData::Slice(s) = self {
+            *self = ParcelData::Vec(s.to_vec());
         }
-    }
-
-    pub(crate) fn as_ptr(&self) -> *const T {
         match self {
-            ParcelData::Vec(v) => v.as_ptr(),
-            ParcelData::Slice(s) => s.as_ptr(),
+            ParcelData::Vec(v) => v,
+            ParcelData::Slice(_) => unreachable!(),
         }
     }
 
-    fn as_mut_ptr(&mut self) -> *mut T {
-        match self {
-            ParcelData::Vec(v) => v.as_mut_ptr(),
-            ParcelData::Slice(s) => s.as_mut_ptr(),
-        }
+    pub(crate) fn as_ptr(&self) -> *const T {
+        self.as_slice().as_ptr()
     }
 
     pub(crate) fn len(&self) -> usize {
         self.as_slice().len()
     }
 
-    fn set_len(&mut self, len: usize) {
-        match self {
-            ParcelData::Vec(v) => unsafe { v.set_len(len) },
-            _ => panic!("&[u8] can't support set_len()."),
-        }
-    }
-
-    fn resize(&mut self, len: usize) {
-        match self {
-            ParcelData::Vec(v) => v.resize_with(len, Default::default),
-            _ => panic!("&[u8] can't support resize()."),
-        }
-    }
-
     fn capacity(&self) -> usize {
         match self {
             ParcelData::Vec(v) => v.capacity(),
             ParcelData::Slice(s) => s.len(),
         }
     }
+}
 
-    fn reserve(&mut self, additional: usize) {
-        match self {
-            ParcelData::Vec(v) => v.reserve(additional),
-            _ => panic!("&[u8] can't support reserve()."),
-        }
-    }
+/// Limits on what reading a parcel may allocate, so that lengths sent by
+/// a hostile peer can't exhaust our memory or stack.
+///
+/// Reads going over a limit fail with [`BinderError::BadValue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParcelLimits {
+    /// Maximum number of elements of an array, list, map or string.
+    pub max_elements: usize,
+    /// Maximum nesting of parcelables and values.
+    pub max_depth: usize,
+    /// Maximum number of bytes allocated for the arrays, lists, maps and
+    /// strings read from the parcel, over its whole life.
+    pub max_allocation: usize,
+}
 
-    fn push(&mut self, other: T) {
-        match self {
-            ParcelData::Vec(v) => v.push(other),
-            _ => panic!("extend_from_slice() is only available for ParcelData::Vec."),
+impl Default for ParcelLimits {
+    fn default() -> Self {
+        Self {
+            max_elements: 1 << 24,
+            max_depth: 64,
+            max_allocation: 64 << 20,
         }
     }
 }
 
-pub type FnFreeBuffer = fn(Option<&Parcel>, usize, usize, usize, usize) -> Result<()>;
+/// A read position saved by [`Parcel::mark`], restored by [`Parcel::rewind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadMark {
+    pos: usize,
+    next_object_hint: usize,
+    allocated: usize,
+}
+
+pub type FnFreeBuffer = fn(Option<&Parcel<'_>>, usize, usize, usize, usize) -> Result<()>;
 
 /// Parcel converts data into a byte stream (serialization), making it transferable.
 /// The receiving side then transforms this byte stream back into its original data form (deserialization).
-pub struct Parcel {
-    data: ParcelData<u8>,
-    pub(crate) objects: ParcelData<usize>,
+///
+/// A parcel received from the driver borrows the driver buffer for `'a`,
+/// other parcels own their data.
+pub struct Parcel<'a> {
+    data: ParcelData<'a, u8>,
+    pub(crate) objects: ParcelData<'a, usize>,
     pos: usize,
     next_object_hint: usize,
     request_header_present: bool,
     work_source_request_header_pos: usize,
     free_buffer: Option<FnFreeBuffer>,
+    limits: ParcelLimits,
+    depth: usize,
+    allocated: usize,
+    sensitive: bool,
 }
 
-impl Default for Parcel {
+impl Default for Parcel<'_> {
     fn default() -> Self {
         Parcel::with_capacity(256)
     }
 }
 
-impl Parcel {
+impl<'a> Parcel<'a> {
     pub fn new() -> Self {
         Parcel::with_capacity(256)
     }
@@ -162,27 +218,89 @@ impl Parcel {
             request_header_present: false,
             work_source_request_header_pos: 0,
             free_buffer: None,
+            limits: ParcelLimits::default(),
+            depth: 0,
+            allocated: 0,
+            sensitive: false,
         }
     }
 
+    /// A parcel reading a buffer delivered by the driver.
+    ///
+    /// The object offsets are checked: each one must be aligned, sorted,
+    /// not overlapping the previous object, and hold a whole object of a
+    /// known type within the data.
+    ///
+    /// # Safety
+    /// `data` must point to `length` bytes, and `objects` to `object_count`
+    /// aligned offsets, both valid and unmodified for `'a`. Null pointers
+    /// are accepted for empty buffers.
     // We leaking free buffer for now
     // TODO: fix free buffer
-    pub fn from_ipc_parts(
-        data: *mut u8,
+    pub unsafe fn from_ipc_parts(
+        data: *const u8,
         length: usize,
-        objects: *mut usize,
+        objects: *const usize,
         object_count: usize,
         free_buffer: Option<FnFreeBuffer>,
-    ) -> Self {
-        Parcel {
-            data: ParcelData::from_raw_parts_mut(data, length),
-            objects: ParcelData::from_raw_parts_mut(objects, object_count),
+    ) -> Result<Self> {
+        // SAFETY: guaranteed by the caller.
+        let (data, objects) =
+            unsafe { (raw_slice(data, length), raw_slice(objects, object_count)) };
+        let parcel = Parcel {
+            data: ParcelData::Slice(data),
+            objects: ParcelData::Slice(objects),
             pos: 0,
             next_object_hint: 0,
             request_header_present: false,
             work_source_request_header_pos: 0,
             free_buffer,
+            limits: ParcelLimits::default(),
+            depth: 0,
+            allocated: 0,
+            sensitive: false,
+        };
+        parcel.validate_objects()?;
+        Ok(parcel)
+    }
+
+    fn validate_objects(&self) -> Result<()> {
+        let object_size = std::mem::size_of::<BinderFlatObject>();
+        let mut min_offset = 0;
+
+        for &offset in self.objects.as_slice() {
+            if offset % 4 != 0 {
+                error!("Parcel: unaligned object offset {offset}");
+                return Err(BinderError::BadValue);
+            }
+            if offset < min_offset {
+                error!(
+                    "Parcel: object offset {offset} unsorted or overlapping, expected >= {min_offset}"
+                );
+                return Err(BinderError::BadValue);
+            }
+            match offset.checked_add(object_size) {
+                Some(end) if end <= self.data.len() => min_offset = end,
+                _ => {
+                    error!(
+                        "Parcel: object offset {offset} out of the data ({} bytes)",
+                        self.data.len()
+                    );
+                    return Err(BinderError::BadValue);
+                }
+            }
+            self.object_at(offset)?;
         }
+
+        Ok(())
+    }
+
+    /// A parcel reading `data`, without objects. Unlike [`from_vec`](Self::from_vec)
+    /// it supports [`read_borrowed`](Self::read_borrowed).
+    pub fn from_slice(data: &'a [u8]) -> Self {
+        let mut parcel = Parcel::with_capacity(0);
+        parcel.data = ParcelData::Slice(data);
+        parcel
     }
 
     pub fn from_vec(data: Vec<u8>) -> Self {
@@ -196,11 +314,54 @@ impl Parcel {
             request_header_present: false,
             work_source_request_header_pos: 0,
             free_buffer: None,
+            limits: ParcelLimits::default(),
+            depth: 0,
+            allocated: 0,
+            sensitive: false,
         }
     }
 
+    /// Replace the object offsets, checked like those of
+    /// [`from_ipc_parts`](Self::from_ipc_parts).
+    pub(crate) fn set_objects(&mut self, objects: Vec<usize>) -> Result<()> {
+        let previous = std::mem::replace(&mut self.objects, ParcelData::from_vec(objects));
+        if let Err(e) = self.validate_objects() {
+            self.objects = previous;
+            return Err(e);
+        }
+        self.next_object_hint = 0;
+        Ok(())
+    }
+
+    /// Empty the parcel to write it again, keeping an owned buffer. The
+    /// objects are released, or the received buffer freed, as on drop.
+    pub fn reset(&mut self) {
+        if self.free_buffer.is_some() || self.sensitive {
+            *self = Parcel::with_capacity(0);
+            return;
+        }
+
+        self.release_objects();
+        match &mut self.objects {
+            ParcelData::Vec(objects) => objects.clear(),
+            ParcelData::Slice(_) => self.objects = ParcelData::new(),
+        }
+        match &mut self.data {
+            ParcelData::Vec(data) => data.clear(),
+            ParcelData::Slice(_) => self.data = ParcelData::new(),
+        }
+        self.pos = 0;
+        self.next_object_hint = 0;
+        self.request_header_present = false;
+        self.work_source_request_header_pos = 0;
+        self.limits = ParcelLimits::default();
+        self.depth = 0;
+        self.allocated = 0;
+    }
+
+    /// Pointer to the data, a borrowed buffer is copied first.
     pub fn as_mut_ptr(&mut self) -> *mut u8 {
-        self.data.as_mut_ptr()
+        self.data.to_mut().as_mut_ptr()
     }
 
     pub fn as_ptr(&self) -> *const u8 {
@@ -215,17 +376,49 @@ impl Parcel {
         self.pos >= self.data.len()
     }
 
+    /// Truncate the data, or grow it with zeroes.
     pub fn set_data_size(&mut self, new_len: usize) {
-        self.data.set_len(new_len);
+        self.data_mut(new_len).truncate(new_len);
         if new_len < self.pos {
             self.pos = new_len;
         }
     }
 
+    /// Mark the data as sensitive: its memory is zeroed when the parcel is
+    /// dropped or its buffer reallocated, transactions sending it set
+    /// [`ClearBuf`](crate::service::TransactionFlag::ClearBuf) so that the
+    /// driver clears the buffer of the receiver, and it isn't recorded
+    /// nor printed. A buffer borrowed from the driver can't be zeroed.
+    pub fn mark_sensitive(&mut self) {
+        self.sensitive = true;
+    }
+
+    pub fn is_sensitive(&self) -> bool {
+        self.sensitive
+    }
+
+    /// The owned buffer, grown with zeroes to at least `len` bytes. The
+    /// previous allocation of a sensitive parcel is zeroed when it moves.
+    fn data_mut(&mut self, len: usize) -> &mut Vec<u8> {
+        let sensitive = self.sensitive;
+        let buf = self.data.to_mut();
+        if sensitive && len > buf.capacity() {
+            let mut grown = Vec::with_capacity(len.max(buf.capacity() * 2));
+            grown.extend_from_slice(buf);
+            zeroize(buf);
+            *buf = grown;
+        }
+        if buf.len() < len {
+            buf.resize(len, 0);
+        }
+        buf
+    }
+
     pub fn close_file_descriptors(&self) {
-        for offset in self.objects.as_slice() {
-            let obj: &BinderFlatObject =
-                unsafe { BinderFlatObject::ref_from_raw(self.data.as_ptr(), *offset) };
+        for &offset in self.objects.as_slice() {
+            let Ok(obj) = self.object_at(offset) else {
+                continue;
+            };
             if obj.header_type() == BinderType::Fd {
                 // Close the file descriptor
                 obj.owned_fd();
@@ -233,6 +426,15 @@ impl Parcel {
         }
     }
 
+    /// The object at `offset` of the data.
+    pub(crate) fn object_at(&self, offset: usize) -> Result<BinderFlatObject> {
+        let bytes = self.data.as_slice().get(offset..).ok_or_else(|| {
+            error!("Parcel: object offset {offset} out of bounds");
+            BinderError::BadValue
+        })?;
+        BinderFlatObject::from_bytes(bytes)
+    }
+
     pub fn set_data_position(&mut self, pos: usize) {
         self.pos = pos;
     }
@@ -241,6 +443,11 @@ impl Parcel {
         self.pos
     }
 
+    /// Decode the structure of the data, to display it as an annotated tree.
+    pub fn inspect(&self) -> Inspection {
+        Inspection::new(self.data.as_slice(), self.objects.as_slice())
+    }
+
     pub fn data_size(&self) -> usize {
         if self.data.len() > self.pos {
             self.data.len()
@@ -264,11 +471,68 @@ impl Parcel {
         self.unread_data_size() > std::mem::size_of::<T>()
     }
 
+    /// Save the read position, to come back to it with [`rewind`](Self::rewind).
+    pub fn mark(&self) -> ReadMark {
+        ReadMark {
+            pos: self.pos,
+            next_object_hint: self.next_object_hint,
+            allocated: self.allocated,
+        }
+    }
+
+    /// Go back to a position saved by [`mark`](Self::mark). What was read
+    /// since no longer counts in the [`ParcelLimits`].
+    pub fn rewind(&mut self, mark: ReadMark) {
+        self.pos = mark.pos;
+        self.next_object_hint = mark.next_object_hint;
+        self.allocated = mark.allocated;
+    }
+
+    /// Read the next value without moving the position.
+    pub fn peek<D: Deserialize>(&mut self) -> Result<D> {
+        let mark = self.mark();
+        let result = self.read();
+        self.rewind(mark);
+        result
+    }
+
+    /// Read and drop the next value.
+    pub fn skip<D: Deserialize>(&mut self) -> Result<()> {
+        self.read::<D>().map(drop)
+    }
+
+    /// Move the position past `len` bytes, padded to 4 bytes.
+    pub fn skip_bytes(&mut self, len: usize) -> Result<()> {
+        self.read_aligned_data(len).map(drop)
+    }
+
+    /// A hash of the data and object offsets, the same across builds and
+    /// platforms of the same endianness (FNV-1a).
+    pub fn stable_hash(&self) -> u64 {
+        const PRIME: u64 = 0x100000001b3;
+
+        let lengths = [self.data.len() as u64, self.objects.len() as u64];
+        let offsets = self.objects.as_slice().iter().map(|&offset| offset as u64);
+        lengths
+            .into_iter()
+            .chain(offsets)
+            .flat_map(u64::to_le_bytes)
+            .chain(self.data.as_slice().iter().copied())
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(PRIME)
+            })
+    }
+
     /// Read a type that implements [`Deserialize`] from the sub-parcel.
     pub fn read<D: Deserialize>(&mut self) -> Result<D> {
         D::deserialize(self)
     }
 
+    /// Read a value borrowing the buffer of the parcel, see [`borrowed`].
+    pub fn read_borrowed<D: DeserializeBorrowed<'a>>(&mut self) -> Result<D> {
+        D::deserialize_borrowed(self)
+    }
+
     /// Attempt to read a type that implements [`Deserialize`] from this parcel
     /// onto an existing value. This operation will overwrite the old value
     /// partially or completely, depending on how much data is available.
@@ -276,18 +540,125 @@ impl Parcel {
         x.deserialize_from(self)
     }
 
+    /// Read a (possibly null) UTF-8 string written by `writeString8`.
+    pub fn read_string8(&mut self) -> Result<Option<String>> {
+        let len = self.read::<i32>()?;
+        if len == -1 {
+            return Ok(None);
+        }
+        let len = usize::try_from(len).map_err(|_| {
+            error!("Bad String8 length: {len}");
+            BinderError::BadValue
+        })?;
+
+        self.check_alloc(len + 1, 1)?;
+        let data = self.read_aligned_data(len + 1)?;
+        if data[len] != 0 {
+            error!("String8 is not nul terminated");
+            return Err(BinderError::BadValue);
+        }
+        String::from_utf8(data[..len].to_vec())
+            .map(Some)
+            .map_err(|e| {
+                error!("Read String8: {e}");
+                BinderError::BadValue
+            })
+    }
+
+    /// Read a (possibly null) blob written by Java `Parcel.writeBlob`,
+    /// mapping it when it was passed in shared memory.
+    pub fn read_blob(&mut self) -> Result<Option<Blob>> {
+        let len = self.read::<i32>()?;
+        if len == -1 {
+            return Ok(None);
+        }
+        let len = usize::try_from(len).map_err(|_| {
+            error!("Bad blob length: {len}");
+            BinderError::BadValue
+        })?;
+
+        match self.read::<i32>()? {
+            BLOB_INPLACE => {
+                self.check_alloc(len, 1)?;
+                Ok(Some(Blob::inplace(self.read_aligned_data(len)?.to_vec())))
+            }
+            BLOB_ASHMEM_IMMUTABLE => {
+                let fd = self.read_file_descriptor()?;
+                Blob::from_shared(fd.as_fd(), len).map(Some)
+            }
+            BLOB_ASHMEM_MUTABLE => {
+                error!("Parcel: refusing to map a mutable blob");
+                Err(BinderError::InvalidOperation)
+            }
+            blob_type => {
+                error!("Parcel: bad blob type {blob_type}");
+                Err(BinderError::BadValue)
+            }
+        }
+    }
+
+    /// Read a value written by Java `Parcel.writeValue`.
+    pub fn read_value(&mut self) -> Result<ParcelValue> {
+        self.read()
+    }
+
     pub fn data_avail(&self) -> usize {
-        let result = self.data.len() - self.pos;
-        assert!(result < i32::MAX as _, "data too big: {}", result);
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    pub fn limits(&self) -> &ParcelLimits {
+        &self.limits
+    }
 
+    pub fn set_limits(&mut self, limits: ParcelLimits) {
+        self.limits = limits;
+    }
+
+    /// Check a length read from the parcel before allocating `len`
+    /// elements of `elem_size` bytes.
+    pub(crate) fn check_alloc(&mut self, len: usize, elem_size: usize) -> Result<()> {
+        if len > self.limits.max_elements {
+            error!(
+                "Parcel: {len} elements, the limit is {}",
+                self.limits.max_elements
+            );
+            return Err(BinderError::BadValue);
+        }
+
+        let allocated = len
+            .checked_mul(elem_size)
+            .and_then(|size| self.allocated.checked_add(size))
+            .filter(|allocated| *allocated <= self.limits.max_allocation)
+            .ok_or_else(|| {
+                error!(
+                    "Parcel: allocating {len} * {elem_size} bytes goes over the limit of {}",
+                    self.limits.max_allocation
+                );
+                BinderError::BadValue
+            })?;
+        self.allocated = allocated;
+        Ok(())
+    }
+
+    /// Run `f` one nesting level deeper.
+    pub(crate) fn nested<R>(&mut self, f: impl FnOnce(&mut Self) -> Result<R>) -> Result<R> {
+        if self.depth >= self.limits.max_depth {
+            error!("Parcel: nesting deeper than {}", self.limits.max_depth);
+            return Err(BinderError::BadValue);
+        }
+
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
         result
     }
 
     pub(crate) fn read_aligned_data(&mut self, len: usize) -> Result<&[u8]> {
-        let aligned = pad_size(len);
+        let avail = self.data_avail();
+        let aligned = if len <= avail { pad_size(len) } else { len };
         let pos = self.pos;
 
-        if aligned <= self.data_avail() {
+        if aligned <= avail {
             self.pos = pos + aligned;
             Ok(&self.data.as_slice()[pos..pos + len])
         } else {
@@ -299,12 +670,23 @@ impl Parcel {
         }
     }
 
-    pub(crate) fn read_object(&mut self, null_meta: bool) -> Result<&BinderFlatObject> {
+    /// Like [`read_aligned_data`](Self::read_aligned_data), borrowing the
+    /// buffer for `'a`.
+    pub(crate) fn read_borrowed_data(&mut self, len: usize) -> Result<&'a [u8]> {
+        let ParcelData::Slice(data) = self.data else {
+            error!("Parcel: borrowed reads need a borrowed buffer");
+            return Err(BinderError::InvalidOperation);
+        };
+        let pos = self.pos;
+        self.read_aligned_data(len)?;
+        Ok(&data[pos..pos + len])
+    }
+
+    pub(crate) fn read_object(&mut self, null_meta: bool) -> Result<BinderFlatObject> {
         let data_pos = self.pos;
         let size = std::mem::size_of::<BinderFlatObject>();
 
-        let obj: &BinderFlatObject =
-            unsafe { BinderFlatObject::ref_from_raw(self.read_aligned_data(size)?.as_ptr(), 0) };
+        let obj = BinderFlatObject::from_bytes(self.read_aligned_data(size)?)?;
 
         if !null_meta && obj.cookie() == 0 && obj.pointer() == 0 {
             return Ok(obj);
@@ -341,6 +723,38 @@ impl Parcel {
         Err(BinderError::BadType)
     }
 
+    /// Read a file descriptor object and return a duplicate of it,
+    /// owned by the caller.
+    pub fn read_file_descriptor(&mut self) -> Result<OwnedFd> {
+        let obj = self.read_object(true)?;
+        match obj.borrowed_fd() {
+            Some(fd) => Ok(fd.try_clone_to_owned()?),
+            None => {
+                error!(
+                    "Parcel: expected a file descriptor, got {:?}",
+                    obj.header_type()
+                );
+                Err(BinderError::BadType)
+            }
+        }
+    }
+
+    /// Read a (possibly null) binder object written by `writeStrongBinder`.
+    pub(crate) fn read_strong_binder(&mut self) -> Result<Option<BinderFlatObject>> {
+        let obj = self.read_object(false)?;
+        let stability = self.read::<i32>()?;
+        trace!("Parcel: binder stability {stability}");
+
+        match obj.header_type() {
+            BinderType::Binder if obj.pointer() == 0 => Ok(None),
+            BinderType::Binder | BinderType::Handle => Ok(Some(obj)),
+            binder_type => {
+                error!("Parcel: expected a binder, got {binder_type:?}");
+                Err(BinderError::BadType)
+            }
+        }
+    }
+
     /// Safely read a sized parcelable.
     ///
     /// Read the size of a parcelable, compute the end position
@@ -353,7 +767,7 @@ impl Parcel {
     ///
     pub fn sized_read<F>(&mut self, f: F) -> Result<()>
     where
-        for<'b> F: FnOnce(&mut Parcel) -> Result<()>,
+        for<'b> F: FnOnce(&mut ReadableSubParcel<'b, 'a>) -> Result<()>,
     {
         let start = self.data_position();
         let parcelable_size: i32 = self.read()?;
@@ -371,7 +785,12 @@ impl Parcel {
             return Err(BinderError::NotEnoughData);
         }
 
-        f(self)?;
+        self.nested(|parcel| {
+            f(&mut ReadableSubParcel {
+                parcel,
+                end_position: end,
+            })
+        })?;
 
         // Advance the data position to the actual end,
         // in case the closure read less data than was available
@@ -380,7 +799,7 @@ impl Parcel {
         Ok(())
     }
 
-    pub(crate) fn read_array<D: Deserialize + ?Sized>(&mut self) -> Result<Option<Vec<D>>> {
+    pub(crate) fn read_array<D: Pod>(&mut self) -> Result<Option<Vec<D>>> {
         let len: i32 = self.read()?;
         if len < -1 {
             error!("Parcel: bad array length: {}", len);
@@ -390,29 +809,18 @@ impl Parcel {
             return Ok(None);
         }
 
+        self.check_alloc(len as usize, std::mem::size_of::<D>())?;
         let size = len as usize * std::mem::size_of::<D>();
-        let padded = pad_size(size);
-
-        if padded > self.data_avail() {
-            error!(
-                "Parcel: not enough data to read array: {} > {}",
-                padded,
-                self.data_avail()
-            );
-            return Err(BinderError::NotEnoughData);
-        }
+        let bytes = self
+            .read_aligned_data(size)
+            .inspect_err(|_| error!("Parcel: not enough data to read array of {len}"))?;
 
-        let mut result = Vec::with_capacity(len as usize);
-        let pos = self.pos;
+        let mut result = Vec::<D>::with_capacity(len as usize);
+        // SAFETY: `bytes` holds `len` values, and any bit pattern is a valid `D`.
         unsafe {
-            std::ptr::copy_nonoverlapping::<u8>(
-                self.data.as_slice()[pos..pos + size].as_ptr(),
-                result.as_mut_ptr() as _,
-                size,
-            );
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), result.as_mut_ptr() as *mut u8, size);
             result.set_len(len as usize);
         }
-        self.set_data_position(pos + padded);
 
         Ok(Some(result))
     }
@@ -429,28 +837,17 @@ impl Parcel {
             return Ok(None);
         }
 
-        let size = len as usize * 4;
-        let padded = pad_size(size);
-
-        if padded > self.data_avail() {
-            error!(
-                "Parcel: not enough data to read array char: {} > {}",
-                padded,
-                self.data_avail()
-            );
-            return Err(BinderError::NotEnoughData);
-        }
-
-        let mut result = Vec::with_capacity(len as usize);
-        let pos = self.pos;
-        let (_, ints, _) = unsafe { self.data.as_slice()[pos..pos + size].align_to::<i32>() };
-        for i in ints {
-            result.push(D::from(i));
-        }
-
-        self.set_data_position(pos + padded);
-
-        Ok(Some(result))
+        self.check_alloc(len as usize, std::mem::size_of::<D::Output>())?;
+        let bytes = self
+            .read_aligned_data(len as usize * 4)
+            .inspect_err(|_| error!("Parcel: not enough data to read array char of {len}"))?;
+
+        Ok(Some(
+            bytes
+                .chunks_exact(4)
+                .map(|c| D::from(&i32::from_ne_bytes(c.try_into().unwrap())))
+                .collect(),
+        ))
     }
 
     /// Read a vector size from the parcel and resize the given output vector to
@@ -467,6 +864,7 @@ impl Parcel {
 
         // usize in Rust may be 16-bit, so i32 may not fit
         let len = len.try_into().or(Err(BinderError::BadValue))?;
+        self.check_alloc(len, std::mem::size_of::<D>())?;
         out_vec.resize_with(len, Default::default);
 
         Ok(())
@@ -489,6 +887,7 @@ impl Parcel {
         } else {
             // usize in Rust may be 16-bit, so i32 may not fit
             let len = len.try_into().or(Err(BinderError::BadValue))?;
+            self.check_alloc(len, std::mem::size_of::<D>())?;
             let mut vec = Vec::with_capacity(len);
             vec.resize_with(len, Default::default);
             *out_vec = Some(vec);
@@ -508,31 +907,51 @@ impl Parcel {
         parcelable.serialize(self)
     }
 
-    pub(crate) fn write_array<S: Serialize + Sized>(&mut self, parcelable: &[S]) -> Result<()> {
-        let len = parcelable.len();
-        self.write::<i32>(&(len as _))?;
+    /// Write a (possibly null) UTF-8 string as `writeString8` does.
+    pub fn write_string8(&mut self, s: Option<&str>) -> Result<()> {
+        let Some(s) = s else {
+            return self.write(&-1i32);
+        };
+        let len = i32::try_from(s.len()).map_err(|_| BinderError::BadValue)?;
+        self.write(&len)?;
+
+        // the padding is the terminator, a copy would leak sensitive strings
+        self.write_aligned_data(s.as_bytes());
+        if s.len() % 4 == 0 {
+            self.write(&0i32)?;
+        }
+        Ok(())
+    }
 
-        if len == 0 {
+    /// Write a (possibly null) blob as Java `Parcel.writeBlob` does: in place
+    /// up to [`BLOB_INPLACE_LIMIT`] bytes, otherwise in a sealed memfd.
+    pub fn write_blob(&mut self, data: Option<&[u8]>) -> Result<()> {
+        let Some(data) = data else {
+            return self.write(&-1i32);
+        };
+        let len = i32::try_from(data.len()).map_err(|_| BinderError::BadValue)?;
+        self.write(&len)?;
+
+        if data.len() <= BLOB_INPLACE_LIMIT {
+            self.write(&BLOB_INPLACE)?;
+            self.write_aligned_data(data);
             return Ok(());
         }
 
-        let size = std::mem::size_of_val(parcelable);
-        let padded = pad_size(size);
-        let pos = self.pos;
+        let fd = blob::sealed_memfd(data)?;
+        self.write(&BLOB_ASHMEM_IMMUTABLE)?;
+        self.write_object(&BinderFlatObject::new_with_fd(fd.into_raw_fd(), true), true)
+    }
 
-        self.data.reserve(pos + padded);
-        unsafe {
-            std::ptr::copy_nonoverlapping::<u8>(
-                parcelable.as_ptr() as _,
-                self.data.as_mut_ptr().add(pos) as _,
-                size,
-            );
-            if self.data.len() < pos + padded {
-                self.data.set_len(pos + padded);
-            }
-        }
+    /// Write a value as Java `Parcel.writeValue` does, for `Parcel.readValue`.
+    pub fn write_value(&mut self, value: &ParcelValue) -> Result<()> {
+        self.write(value)
+    }
 
-        self.set_data_position(pos + padded);
+    pub(crate) fn write_array<S: Pod>(&mut self, parcelable: &[S]) -> Result<()> {
+        let len = i32::try_from(parcelable.len()).map_err(|_| BinderError::BadValue)?;
+        self.write(&len)?;
+        self.write_aligned_data(pod_bytes(parcelable));
 
         Ok(())
     }
@@ -541,10 +960,6 @@ impl Parcel {
         let len = parcelable.len();
         self.write::<i32>(&(len as _))?;
 
-        let size = 4 * len;
-        let padded = pad_size(size);
-
-        self.data.reserve(self.pos + padded);
         for c in parcelable {
             self.write(&c.as_i32())?;
         }
@@ -565,49 +980,51 @@ impl Parcel {
         }
     }
 
-    pub(crate) fn write_aligned<T>(&mut self, val: &T) {
-        let unaligned = std::mem::size_of::<T>();
-        let val_bytes: &[u8] =
-            unsafe { std::slice::from_raw_parts(val as *const T as *const u8, unaligned) };
-
-        self.write_aligned_data(val_bytes);
+    pub(crate) fn write_aligned<T: Pod>(&mut self, val: &T) {
+        self.write_aligned_data(pod_bytes(std::slice::from_ref(val)));
     }
 
+    /// Write `data` at the current position, padded with zeroes to 4 bytes.
+    /// The parcel grows with zeroes if the position is past its end.
     pub(crate) fn write_aligned_data(&mut self, data: &[u8]) {
-        let unaligned = data.len();
-        let aligned = pad_size(unaligned);
         let pos = self.pos;
-        // info!("pos={pos} unaligned={unaligned} - aligned={aligned}");
+        let end = pos + pad_size(data.len());
 
-        self.data.reserve(pos + aligned);
+        let buf = self.data_mut(end);
+        buf[pos..pos + data.len()].copy_from_slice(data);
+        buf[pos + data.len()..end].fill(0);
 
-        unsafe {
-            std::ptr::copy_nonoverlapping::<u8>(
-                data.as_ptr() as _,
-                self.data.as_mut_ptr().add(pos) as _,
-                unaligned,
-            );
-            if pos + aligned > self.data.len() {
-                self.data.set_len(pos + aligned);
-            }
-        }
-
-        self.set_data_position(pos + aligned);
+        self.set_data_position(end);
     }
 
     pub(crate) fn write_object(&mut self, obj: &BinderFlatObject, null_meta: bool) -> Result<()> {
         let data_pos = self.pos;
-        self.write_aligned(obj);
+        self.write_aligned_data(obj.as_bytes());
 
         if null_meta || obj.pointer() != 0 {
             obj.acquire()?;
-            self.objects.push(data_pos as _);
+            self.objects.to_mut().push(data_pos as _);
         }
 
         Ok(())
     }
 
-    pub(crate) fn write_interface_token(&mut self, interface: &str) -> Result<()> {
+    /// Write a (possibly null) binder object followed by its stability,
+    /// the same layout as `Parcel::writeStrongBinder` in C++.
+    pub(crate) fn write_strong_binder(&mut self, obj: Option<&BinderFlatObject>) -> Result<()> {
+        match obj {
+            Some(obj) => {
+                self.write(obj)?;
+                self.write::<i32>(&Stability::System.into())
+            }
+            None => {
+                self.write(&BinderFlatObject::default())?;
+                self.write::<i32>(&Stability::Local.into())
+            }
+        }
+    }
+
+    pub fn write_interface_token(&mut self, interface: &str) -> Result<()> {
         // strict mode policy: 0x42000004
         // this hardcode for fast
         // TODO : implement get strict mode policy
@@ -626,6 +1043,43 @@ impl Parcel {
         Ok(())
     }
 
+    /// The interface descriptor of the interface token at the start of
+    /// the data, if there is one. The position is left unchanged.
+    pub fn interface_descriptor(&self) -> Option<String> {
+        let mut reader = Parcel::from_vec(Vec::new());
+        reader.data = ParcelData::Slice(self.data.as_slice());
+        reader.limits = self.limits;
+
+        let _strict_mode_policy = reader.read::<i32>().ok()?;
+        let _work_source = reader.read::<i32>().ok()?;
+        if reader.read::<u32>().ok()? != INTERFACE_HEADER {
+            return None;
+        }
+        reader.read::<String>().ok()
+    }
+
+    /// Read the interface token written by [`write_interface_token`](Self::write_interface_token)
+    /// and check it against the expected interface descriptor.
+    pub fn enforce_interface(&mut self, interface: &str) -> Result<()> {
+        let _strict_mode_policy = self.read::<i32>()?;
+        self.update_work_source_request_header_pos();
+        let _work_source = self.read::<i32>()?;
+
+        let header = self.read::<u32>()?;
+        if header != INTERFACE_HEADER {
+            error!("Parcel: expecting header {INTERFACE_HEADER:#X} but got {header:#X}");
+            return Err(BinderError::BadType);
+        }
+
+        let descriptor = self.read::<String>()?;
+        if descriptor != interface {
+            error!("Parcel: expecting interface '{interface}' but got '{descriptor}'");
+            return Err(BinderError::BadType);
+        }
+
+        Ok(())
+    }
+
     /// Perform a series of writes to the parcel, prepended with the length
     /// (in bytes) of the written data.
     ///
@@ -655,7 +1109,7 @@ impl Parcel {
     /// ```
     pub fn sized_write<F>(&mut self, f: F) -> Result<()>
     where
-        for<'b> F: FnOnce(&mut Parcel) -> Result<()>,
+        F: FnOnce(&mut Self) -> Result<()>,
     {
         let start = self.data_position();
         self.write(&0i32)?;
@@ -670,24 +1124,27 @@ impl Parcel {
         Ok(())
     }
 
-    pub(crate) fn append_all_from(&mut self, other: &mut Parcel) -> Result<()> {
+    pub(crate) fn append_all_from(&mut self, other: &mut Parcel<'_>) -> Result<()> {
         self.append_from(other, 0, other.data_size())
     }
 
     pub(crate) fn append_from(
         &mut self,
-        other: &mut Parcel,
+        other: &mut Parcel<'_>,
         offset: usize,
         size: usize,
     ) -> Result<()> {
         if size == 0 {
             return Ok(());
         }
+        if other.sensitive {
+            self.mark_sensitive();
+        }
         if size > i32::MAX as usize {
             error!("Parcel::append_from: the size is too large: {}", size);
             return Err(BinderError::BadValue);
         }
-        let other_len = other.data_size();
+        let other_len = other.data.len();
         if offset > other_len || size > other_len || (offset + size) > other_len {
             error!(
                 "Parcel::append_from: The given offset({}) and size({}) exceed the data range of the parcel.",
@@ -701,7 +1158,7 @@ impl Parcel {
         let mut last_idx: i32 = -2;
         {
             let object_size = std::mem::size_of::<BinderFlatObject>();
-            let objects = self.objects.as_slice();
+            let objects = other.objects.as_slice();
 
             for (i, &off) in objects.iter().enumerate() {
                 if off >= offset as _ && (off + object_size) <= (offset + size) {
@@ -715,30 +1172,16 @@ impl Parcel {
 
         let num_objects = last_idx - first_idx + 1;
 
-        self.data.reserve(self.pos + size);
-        unsafe {
-            std::ptr::copy_nonoverlapping::<u8>(
-                other.data.as_slice()[offset..offset + size].as_ptr() as _,
-                self.data.as_mut_ptr().add(self.pos) as _,
-                size,
-            );
-            if self.pos + size > self.data.len() {
-                self.data.set_len(self.pos + size);
-            }
-        }
-        self.set_data_position(self.pos + size);
+        let end = start_pos + size;
+        let buf = self.data_mut(end);
+        buf[start_pos..end].copy_from_slice(&other.data.as_slice()[offset..offset + size]);
+        self.set_data_position(end);
 
         if num_objects > 0 {
-            let mut idx = self.objects.len();
-            self.objects.resize(idx + (num_objects as usize));
-
-            let objects = self.objects.as_mut_slice();
             for i in first_idx..=last_idx {
-                let off = objects[i as usize] as usize - offset + start_pos;
-                objects[idx] = off as _;
-                idx += 1;
-                let flat: &mut BinderFlatObject =
-                    unsafe { BinderFlatObject::mut_from_raw(self.data.as_mut_ptr(), off) };
+                let off = other.objects.as_slice()[i as usize] - offset + start_pos;
+                self.objects.to_mut().push(off as _);
+                let mut flat = self.object_at(off)?;
                 flat.acquire()?;
                 if flat.header_type() == BinderType::Fd {
                     //                    flat.set_handle(nix::fcntl::fcntl(flat.handle() as _, nix::fcntl::FcntlArg::F_DUPFD_CLOEXEC(0))? as _);
@@ -748,6 +1191,8 @@ impl Parcel {
                     )? as _);
                     flat.set_cookie(1);
                 }
+                self.data.to_mut()[off..off + flat.as_bytes().len()]
+                    .copy_from_slice(flat.as_bytes());
             }
         }
 
@@ -759,9 +1204,10 @@ impl Parcel {
             return;
         }
 
-        for pos in self.objects.as_slice() {
-            let obj: &BinderFlatObject =
-                unsafe { BinderFlatObject::ref_from_raw(self.data.as_ptr(), *pos as usize) };
+        for &pos in self.objects.as_slice() {
+            let Ok(obj) = self.object_at(pos) else {
+                continue;
+            };
             obj.release()
                 .map_err(|e| error!("Parcel: unable to release object: {:?}", e))
                 .ok();
@@ -769,7 +1215,25 @@ impl Parcel {
     }
 }
 
-impl Drop for Parcel {
+/// Parcels are equal when their data and object offsets are, whatever their
+/// position.
+impl PartialEq for Parcel<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.data.as_slice() == other.data.as_slice()
+            && self.objects.as_slice() == other.objects.as_slice()
+    }
+}
+
+impl Eq for Parcel<'_> {}
+
+impl std::hash::Hash for Parcel<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.data.as_slice().hash(state);
+        self.objects.as_slice().hash(state);
+    }
+}
+
+impl Drop for Parcel<'_> {
     fn drop(&mut self) {
         match self.free_buffer {
             Some(free_buffer) => {
@@ -786,31 +1250,60 @@ impl Drop for Parcel {
                 self.release_objects();
             }
         }
+
+        if self.sensitive
+            && let ParcelData::Vec(data) = &mut self.data
+        {
+            zeroize(data);
+        }
     }
 }
 
-impl std::fmt::Debug for Parcel {
+impl std::fmt::Debug for Parcel<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        writeln!(f, "Parcel: pos {}, len {}", self.pos, self.data.len())?;
-        if self.objects.len() > 0 {
-            let bytes: &[u8] = unsafe {
-                std::slice::from_raw_parts(
-                    self.objects.as_ptr() as *const u8,
-                    self.objects.len() * std::mem::size_of::<usize>(),
-                )
-            };
-            writeln!(
-                f,
-                "Object count {}\n{}",
-                self.objects.len(),
-                pretty_hex(&bytes)
-            )?;
+        writeln!(
+            f,
+            "Parcel: pos {}, len {}, {} objects",
+            self.pos,
+            self.data.len(),
+            self.objects.len()
+        )?;
+        if self.sensitive {
+            return write!(f, "<sensitive data>");
         }
+        write!(f, "{}", self.inspect())?;
         write!(f, "{}", pretty_hex(&self.data.as_slice()))
     }
 }
 
-impl<const N: usize> TryFrom<&mut Parcel> for [u8; N] {
+/// A parcel limited to the size of a structured parcelable,
+/// given to the closure of [`Parcel::sized_read`].
+pub struct ReadableSubParcel<'a, 'p> {
+    parcel: &'a mut Parcel<'p>,
+    end_position: usize,
+}
+
+impl ReadableSubParcel<'_, '_> {
+    /// Read a type that implements [`Deserialize`] from the sub-parcel.
+    pub fn read<D: Deserialize>(&mut self) -> Result<D> {
+        let result = self.parcel.read()?;
+        if self.parcel.data_position() > self.end_position {
+            error!("Parcel: read past the end of the parcelable");
+            return Err(BinderError::NotEnoughData);
+        }
+        Ok(result)
+    }
+
+    /// Check if the sub-parcel has more data to read.
+    ///
+    /// Fields missing at the end were not written by an older peer
+    /// and keep their default value.
+    pub fn has_more_data(&self) -> bool {
+        self.parcel.data_position() < self.end_position
+    }
+}
+
+impl<const N: usize> TryFrom<&mut Parcel<'_>> for [u8; N] {
     type Error = BinderError;
 
     fn try_from(parcel: &mut Parcel) -> Result<Self> {
@@ -818,3 +1311,13 @@ impl<const N: usize> TryFrom<&mut Parcel> for [u8; N] {
         Ok(<[u8; N] as TryFrom<&[u8]>>::try_from(data)?)
     }
 }
+
+/// # Safety
+/// Same as [`std::slice::from_raw_parts`], except that `ptr` may be null
+/// when `len` is 0.
+unsafe fn raw_slice<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    if len == 0 {
+        return &[];
+    }
+    unsafe { std::slice::from_raw_parts(ptr, len) }
+}
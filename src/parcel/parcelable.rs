@@ -1,3 +1,5 @@
+use std::collections::{BTreeMap, HashMap};
+
 use crate::{
     binder::{flat_object::BinderFlatObject, transaction_data::BinderTransactionData},
     error::{BinderError, Result},
@@ -5,6 +7,11 @@ use crate::{
     stability::Stability,
 };
 
+#[cfg(feature = "derive")]
+pub use binder_rs_derive::{
+    Deserialize, DeserializeArray, DeserializeOption, Serialize, SerializeArray, SerializeOption,
+};
+
 /// Metadata that `ParcelableHolder` needs for all parcelables.
 ///
 /// The compiler auto-generates implementations of this trait
@@ -21,15 +28,266 @@ pub trait ParcelableMetadata {
     }
 }
 
+/// Structured parcelable, written with its size in front
+/// (see [`Parcel::sized_write`] and [`Parcel::sized_read`]).
+///
+/// The AIDL generator implements this trait for parcelables and unions,
+/// along with the `Serialize`/`Deserialize` family built on top of it.
+pub trait Parcelable {
+    /// Internal serialization function for parcelables.
+    ///
+    /// This method is mainly for internal use. `Serialize::serialize` and its
+    /// variants are generally preferred over this function, since the former
+    /// also prepend a header.
+    fn write_to_parcel(&self, parcel: &mut Parcel) -> Result<()>;
+
+    /// Internal deserialization function for parcelables.
+    ///
+    /// This method is mainly for internal use. `Deserialize::deserialize` and
+    /// its variants are generally preferred over this function, since the
+    /// former also parse the additional header.
+    fn read_from_parcel(&mut self, parcel: &mut Parcel) -> Result<()>;
+}
+
+/// Implement `Serialize`, `SerializeOption` and `SerializeArray` for a
+/// [`Parcelable`]: a null flag followed by the parcelable, as in
+/// `Parcel::writeParcelable` in Java.
+#[macro_export]
+macro_rules! impl_serialize_for_parcelable {
+    ($parcelable:ident) => {
+        impl $crate::parcel::parcelable::Serialize for $parcelable {
+            fn serialize(&self, parcel: &mut $crate::parcel::Parcel) -> $crate::error::Result<()> {
+                <Self as $crate::parcel::parcelable::SerializeOption>::serialize_option(
+                    Some(self),
+                    parcel,
+                )
+            }
+        }
+
+        impl $crate::parcel::parcelable::SerializeArray for $parcelable {}
+
+        impl $crate::parcel::parcelable::SerializeOption for $parcelable {
+            fn serialize_option(
+                this: Option<&Self>,
+                parcel: &mut $crate::parcel::Parcel,
+            ) -> $crate::error::Result<()> {
+                match this {
+                    Some(this) => {
+                        parcel.write(&$crate::parcel::parcelable::NON_NULL_PARCELABLE_FLAG)?;
+                        $crate::parcel::parcelable::Parcelable::write_to_parcel(this, parcel)
+                    }
+                    None => parcel.write(&$crate::parcel::parcelable::NULL_PARCELABLE_FLAG),
+                }
+            }
+        }
+    };
+}
+
+/// Implement `Deserialize`, `DeserializeOption` and `DeserializeArray`
+/// for a [`Parcelable`] which also implements `Default`.
+#[macro_export]
+macro_rules! impl_deserialize_for_parcelable {
+    ($parcelable:ident) => {
+        impl $crate::parcel::parcelable::Deserialize for $parcelable {
+            fn deserialize(parcel: &mut $crate::parcel::Parcel) -> $crate::error::Result<Self> {
+                <Self as $crate::parcel::parcelable::DeserializeOption>::deserialize_option(parcel)
+                    .transpose()
+                    .unwrap_or(Err($crate::error::BinderError::UnexpectedNull))
+            }
+
+            fn deserialize_from(
+                &mut self,
+                parcel: &mut $crate::parcel::Parcel,
+            ) -> $crate::error::Result<()> {
+                let status: i32 = parcel.read()?;
+                if status == $crate::parcel::parcelable::NULL_PARCELABLE_FLAG {
+                    Err($crate::error::BinderError::UnexpectedNull)
+                } else {
+                    $crate::parcel::parcelable::Parcelable::read_from_parcel(self, parcel)
+                }
+            }
+        }
+
+        impl $crate::parcel::parcelable::DeserializeArray for $parcelable {}
+
+        impl $crate::parcel::parcelable::DeserializeOption for $parcelable {
+            fn deserialize_option(
+                parcel: &mut $crate::parcel::Parcel,
+            ) -> $crate::error::Result<Option<Self>> {
+                let mut result = None;
+                Self::deserialize_option_from(&mut result, parcel)?;
+                Ok(result)
+            }
+
+            fn deserialize_option_from(
+                this: &mut Option<Self>,
+                parcel: &mut $crate::parcel::Parcel,
+            ) -> $crate::error::Result<()> {
+                let status: i32 = parcel.read()?;
+                if status == $crate::parcel::parcelable::NULL_PARCELABLE_FLAG {
+                    *this = None;
+                    Ok(())
+                } else {
+                    $crate::parcel::parcelable::Parcelable::read_from_parcel(
+                        this.get_or_insert_with(Self::default),
+                        parcel,
+                    )
+                }
+            }
+        }
+    };
+}
+
+/// AIDL union: the tag of the active field followed by its value, without
+/// the size header of structured parcelables.
+///
+/// The tag is the index of the field in the union declaration.
+/// [`impl_parcelable_for_union!`](crate::impl_parcelable_for_union) builds
+/// the [`Parcelable`] and `Serialize`/`Deserialize` implementations on top
+/// of this trait.
+pub trait ParcelableUnion: Sized {
+    /// Number of fields, the valid tags are `0..FIELD_COUNT`.
+    const FIELD_COUNT: i32;
+
+    /// Tag of the active field.
+    fn tag(&self) -> i32;
+
+    /// Write the value of the active field, without its tag.
+    fn write_field(&self, parcel: &mut Parcel) -> Result<()>;
+
+    /// Read the value of field `tag`, a valid tag.
+    fn read_field(tag: i32, parcel: &mut Parcel) -> Result<Self>;
+
+    /// Write the tag followed by the active field.
+    fn write_union(&self, parcel: &mut Parcel) -> Result<()> {
+        parcel.write(&self.tag())?;
+        self.write_field(parcel)
+    }
+
+    /// Read the tag and the field it selects.
+    /// Tags unknown to this version are rejected with [`BinderError::BadValue`].
+    fn read_union(parcel: &mut Parcel) -> Result<Self> {
+        let tag: i32 = parcel.read()?;
+        if !(0..Self::FIELD_COUNT).contains(&tag) {
+            error!("Unknown union tag: {tag}");
+            return Err(BinderError::BadValue);
+        }
+        Self::read_field(tag, parcel)
+    }
+}
+
+/// Implement [`Parcelable`], `Serialize`, `Deserialize` and their option
+/// and array variants for a [`ParcelableUnion`] which also implements
+/// `Default`.
+#[macro_export]
+macro_rules! impl_parcelable_for_union {
+    ($union:ident) => {
+        impl $crate::parcel::parcelable::Parcelable for $union {
+            fn write_to_parcel(
+                &self,
+                parcel: &mut $crate::parcel::Parcel,
+            ) -> $crate::error::Result<()> {
+                $crate::parcel::parcelable::ParcelableUnion::write_union(self, parcel)
+            }
+
+            fn read_from_parcel(
+                &mut self,
+                parcel: &mut $crate::parcel::Parcel,
+            ) -> $crate::error::Result<()> {
+                *self = $crate::parcel::parcelable::ParcelableUnion::read_union(parcel)?;
+                Ok(())
+            }
+        }
+
+        $crate::impl_serialize_for_parcelable!($union);
+        $crate::impl_deserialize_for_parcelable!($union);
+    };
+}
+
+/// AIDL `@Backing` enum, written as its backing integer.
+///
+/// Arrays of enums have the same layout as arrays of the backing type:
+/// packed bytes for `byte`, one `i32` or `i64` per element otherwise.
+/// [`impl_parcelable_for_enum!`](crate::impl_parcelable_for_enum) builds
+/// the `Serialize`/`Deserialize` family on top of this trait.
+pub trait ParcelableEnum: Copy {
+    /// `i8`, `i32` or `i64`.
+    type Backing: SerializeArray + DeserializeArray + Copy;
+
+    fn to_backing(self) -> Self::Backing;
+
+    /// Values unknown to this version should be rejected with
+    /// [`BinderError::BadValue`], unless the type can hold them.
+    fn from_backing(value: Self::Backing) -> Result<Self>;
+}
+
+/// Implement `Serialize`, `Deserialize` and their option and array
+/// variants for a [`ParcelableEnum`].
+#[macro_export]
+macro_rules! impl_parcelable_for_enum {
+    ($enum:ident) => {
+        impl $crate::parcel::parcelable::Serialize for $enum {
+            fn serialize(&self, parcel: &mut $crate::parcel::Parcel) -> $crate::error::Result<()> {
+                parcel.write(&$crate::parcel::parcelable::ParcelableEnum::to_backing(
+                    *self,
+                ))
+            }
+        }
+
+        impl $crate::parcel::parcelable::Deserialize for $enum {
+            fn deserialize(parcel: &mut $crate::parcel::Parcel) -> $crate::error::Result<Self> {
+                let value = parcel.read()?;
+                $crate::parcel::parcelable::ParcelableEnum::from_backing(value)
+            }
+        }
+
+        impl $crate::parcel::parcelable::SerializeArray for $enum {
+            fn serialize_array(
+                slice: &[Self],
+                parcel: &mut $crate::parcel::Parcel,
+            ) -> $crate::error::Result<()> {
+                let values: Vec<<Self as $crate::parcel::parcelable::ParcelableEnum>::Backing> =
+                    slice
+                        .iter()
+                        .map(|e| $crate::parcel::parcelable::ParcelableEnum::to_backing(*e))
+                        .collect();
+                $crate::parcel::parcelable::SerializeArray::serialize_array(&values, parcel)
+            }
+        }
+
+        impl $crate::parcel::parcelable::DeserializeArray for $enum {
+            fn deserialize_array(
+                parcel: &mut $crate::parcel::Parcel,
+            ) -> $crate::error::Result<Option<Vec<Self>>> {
+                let values: Option<
+                    Vec<<Self as $crate::parcel::parcelable::ParcelableEnum>::Backing>,
+                > = $crate::parcel::parcelable::DeserializeArray::deserialize_array(parcel)?;
+                values
+                    .map(|values| {
+                        values
+                            .into_iter()
+                            .map($crate::parcel::parcelable::ParcelableEnum::from_backing)
+                            .collect()
+                    })
+                    .transpose()
+            }
+        }
+
+        impl $crate::parcel::parcelable::SerializeOption for $enum {}
+
+        impl $crate::parcel::parcelable::DeserializeOption for $enum {}
+    };
+}
+
 /// A struct whose instances can be written to a [`Parcel`].
-// Might be able to hook this up as a serde backend in the future?
+// Types implementing serde's traits can use `parcel::serde::Serde` instead.
 pub trait Serialize {
     /// Serialize this instance into the given [`Parcel`].
     fn serialize(&self, parcel: &mut Parcel) -> Result<()>;
 }
 
 /// A struct whose instances can be restored from a [`Parcel`].
-// Might be able to hook this up as a serde backend in the future?
+// Types implementing serde's traits can use `parcel::serde::Serde` instead.
 pub trait Deserialize: Sized {
     /// Deserialize an instance from the given [`Parcel`].
     fn deserialize(parcel: &mut Parcel) -> Result<Self>;
@@ -288,18 +546,15 @@ impl SerializeOption for str {
             None => parcel.write::<i32>(&-1),
 
             Some(text) => {
-                let mut utf16 = Vec::with_capacity(text.len() * 3); // Due to surrogate pair.
-                utf16.extend(text.encode_utf16());
+                let mut utf16 = Vec::with_capacity((text.len() + 1) * 2);
+                utf16.extend(text.encode_utf16().flat_map(u16::to_ne_bytes));
 
-                let len = utf16.len();
+                let len = i32::try_from(utf16.len() / 2).map_err(|_| BinderError::BadValue)?;
 
-                utf16.push(0);
+                utf16.extend_from_slice(&0u16.to_ne_bytes());
 
-                parcel.write::<i32>(&(len as i32))?;
-                let pad_size = crate::parcel::pad_size(utf16.len() * std::mem::size_of::<u16>());
-                parcel.write_aligned_data(unsafe {
-                    std::slice::from_raw_parts(utf16.as_ptr() as *const u8, pad_size)
-                });
+                parcel.write::<i32>(&len)?;
+                parcel.write_aligned_data(&utf16);
 
                 Ok(())
             }
@@ -387,11 +642,13 @@ impl DeserializeOption for String {
         let len = parcel.read::<i32>()?;
 
         if (0..i32::MAX).contains(&len) {
+            parcel.check_alloc(len as usize + 1, std::mem::size_of::<u16>())?;
             let data = parcel.read_aligned_data((len as usize + 1) * std::mem::size_of::<u16>())?;
-            let res = String::from_utf16(unsafe {
-                std::slice::from_raw_parts(data.as_ptr() as *const u16, len as _)
-            })
-            .map_err(|e| {
+            let utf16: Vec<u16> = data[..len as usize * 2]
+                .chunks_exact(2)
+                .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+                .collect();
+            let res = String::from_utf16(&utf16).map_err(|e| {
                 error!("Deserialize for Option<String16>: {}", e.to_string());
                 BinderError::BadValue
             })?;
@@ -418,7 +675,7 @@ impl DeserializeArray for String {}
 
 impl Deserialize for BinderFlatObject {
     fn deserialize(parcel: &mut Parcel) -> Result<Self> {
-        parcel.read_object(false).map(|obj| *obj)
+        parcel.read_object(false)
     }
 }
 
@@ -572,6 +829,12 @@ impl<T: DeserializeOption> Deserialize for Option<T> {
     }
 }
 
+// Java `writeTypedList`/`writeStringList`: each element carries its own
+// null marker, a null flag for parcelables and -1 for strings.
+impl<T: SerializeOption> SerializeArray for Option<T> {}
+
+impl<T: DeserializeOption> DeserializeArray for Option<T> {}
+
 // We need these to support Option<&T> for all T
 impl<T: Serialize + ?Sized> Serialize for &T {
     fn serialize(&self, parcel: &mut Parcel) -> Result<()> {
@@ -643,7 +906,9 @@ pub trait DeserializeArray: Deserialize {
         if len <= 0 {
             return Ok(None);
         }
-        let mut res: Vec<Self> = Vec::with_capacity(len as _);
+        parcel.check_alloc(len as usize, std::mem::size_of::<Self>())?;
+        // every element takes some data, don't trust the length further
+        let mut res: Vec<Self> = Vec::with_capacity((len as usize).min(parcel.data_avail()));
 
         for _ in 0..len {
             res.push(parcel.read()?);
@@ -693,6 +958,9 @@ impl<T: DeserializeArray> DeserializeOption for Vec<T> {
     }
 }
 
+// AIDL `T[N]` is written like `T[]`: the length followed by the elements,
+// packed for bytes. A nested array `T[M][N]` is `[[T; N]; M]`, its inner
+// arrays each have their own length, as `Parcel::writeFixedArray` in C++.
 impl<T: SerializeArray, const N: usize> Serialize for [T; N] {
     fn serialize(&self, parcel: &mut Parcel) -> Result<()> {
         SerializeArray::serialize_array(self, parcel)
@@ -707,32 +975,109 @@ impl<T: SerializeArray, const N: usize> SerializeOption for [T; N] {
 
 impl<T: SerializeArray, const N: usize> SerializeArray for [T; N] {}
 
+/// Read an AIDL `T[N]`, `None` if null.
+///
+/// The length must be exactly `N` (`Parcel::readFixedArray` in C++),
+/// it is checked before the elements are read.
+fn deserialize_fixed_array<T: DeserializeArray, const N: usize>(
+    parcel: &mut Parcel,
+) -> Result<Option<[T; N]>> {
+    let start = parcel.data_position();
+    let len: i32 = parcel.read()?;
+    if len == -1 {
+        return Ok(None);
+    }
+    if usize::try_from(len) != Ok(N) {
+        error!("Fixed-size array of {N} elements, got {len}");
+        return Err(BinderError::BadValue);
+    }
+
+    parcel.set_data_position(start);
+    // an empty array reads as null
+    let vec = DeserializeArray::deserialize_array(parcel)?.unwrap_or_default();
+    vec.try_into().map(Some).map_err(|_| {
+        error!("Fixed-size array of {N} elements: failed to convert");
+        BinderError::BadValue
+    })
+}
+
 impl<T: DeserializeArray, const N: usize> Deserialize for [T; N] {
     fn deserialize(parcel: &mut Parcel) -> Result<Self> {
-        let vec = DeserializeArray::deserialize_array(parcel)
-            .transpose()
-            .unwrap_or_else(|| {
-                error!("Deserialize for [T; N]: UnexpectedNull");
-                Err(BinderError::UnexpectedNull)
-            })?;
-        vec.try_into().map_err(|_| {
-            error!("Deserialize: Failed to convert Vec<T> to [T; N]");
-            BinderError::BadValue
+        deserialize_fixed_array(parcel)?.ok_or_else(|| {
+            error!("Deserialize for [T; N]: UnexpectedNull");
+            BinderError::UnexpectedNull
         })
     }
 }
 
-impl<T: std::fmt::Debug + DeserializeArray, const N: usize> DeserializeOption for [T; N] {
+impl<T: DeserializeArray, const N: usize> DeserializeOption for [T; N] {
     fn deserialize_option(parcel: &mut Parcel) -> Result<Option<Self>> {
-        let vec = DeserializeArray::deserialize_array(parcel)?;
-        vec.map(|v| {
-            v.try_into().map_err(|_| {
-                error!("DeserializeOption: Failed to convert Vec<T> to [T; N]");
-                BinderError::BadValue
-            })
-        })
-        .transpose()
+        deserialize_fixed_array(parcel)
     }
 }
 
 impl<T: DeserializeArray, const N: usize> DeserializeArray for [T; N] {}
+
+// AIDL `Map<String, V>`, as in the Java code generated for it: the number
+// of entries (-1 if null), then each key followed by its value.
+// `Parcel.writeMap` instead tags keys and values with their type.
+macro_rules! impl_parcelable_map {
+    ($($map:ident)*) => {
+        $(
+            impl<V: Serialize> Serialize for $map<String, V> {
+                fn serialize(&self, parcel: &mut Parcel) -> Result<()> {
+                    let len = i32::try_from(self.len()).map_err(|_| BinderError::BadValue)?;
+                    parcel.write(&len)?;
+                    for (key, value) in self {
+                        parcel.write(key)?;
+                        parcel.write(value)?;
+                    }
+                    Ok(())
+                }
+            }
+
+            impl<V: Serialize> SerializeOption for $map<String, V> {
+                fn serialize_option(this: Option<&Self>, parcel: &mut Parcel) -> Result<()> {
+                    match this {
+                        Some(map) => parcel.write(map),
+                        None => parcel.write(&-1i32),
+                    }
+                }
+            }
+
+            impl<V: Serialize> SerializeArray for $map<String, V> {}
+
+            impl<V: Deserialize> Deserialize for $map<String, V> {
+                fn deserialize(parcel: &mut Parcel) -> Result<Self> {
+                    DeserializeOption::deserialize_option(parcel).map(|m| m.unwrap_or_default())
+                }
+            }
+
+            impl<V: Deserialize> DeserializeOption for $map<String, V> {
+                fn deserialize_option(parcel: &mut Parcel) -> Result<Option<Self>> {
+                    let len: i32 = parcel.read()?;
+                    if len == -1 {
+                        return Ok(None);
+                    }
+                    if len < 0 {
+                        error!("Negative map size given in parcel: {}", len);
+                        return Err(BinderError::BadValue);
+                    }
+
+                    parcel.check_alloc(len as usize, std::mem::size_of::<(String, V)>())?;
+                    let mut map = $map::new();
+                    for _ in 0..len {
+                        let key = parcel.read()?;
+                        let value = parcel.read()?;
+                        map.insert(key, value);
+                    }
+                    Ok(Some(map))
+                }
+            }
+
+            impl<V: Deserialize> DeserializeArray for $map<String, V> {}
+        )*
+    };
+}
+
+impl_parcelable_map! { HashMap BTreeMap }
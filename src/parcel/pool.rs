@@ -0,0 +1,99 @@
+//! A per-thread pool of parcel buffers, so that the parcels of the
+//! request/response path don't allocate once the pool is warm.
+
+use std::{
+    cell::RefCell,
+    ops::{Deref, DerefMut},
+};
+
+use super::{Parcel, ParcelData};
+
+/// Buffers kept per thread.
+const MAX_POOLED: usize = 8;
+/// Larger buffers are freed rather than kept.
+const MAX_POOLED_CAPACITY: usize = 64 << 10;
+
+thread_local! {
+    static POOL: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A parcel whose buffer is taken from the pool of the thread, and given
+/// back to it when dropped.
+pub struct PooledParcel {
+    parcel: Parcel<'static>,
+}
+
+impl PooledParcel {
+    /// A parcel with at least `capacity` bytes of buffer.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let data = POOL
+            .try_with(|pool| {
+                let mut pool = pool.borrow_mut();
+                let index = pool
+                    .iter()
+                    .rposition(|data| data.capacity() >= capacity)
+                    .or(pool.len().checked_sub(1))?;
+                Some(pool.swap_remove(index))
+            })
+            .ok()
+            .flatten();
+
+        let mut data = data.unwrap_or_default();
+        data.reserve(capacity);
+        Self {
+            parcel: Parcel::from_vec(data),
+        }
+    }
+
+    /// Number of buffers in the pool of this thread.
+    pub fn pooled() -> usize {
+        POOL.try_with(|pool| pool.borrow().len()).unwrap_or(0)
+    }
+}
+
+impl Default for PooledParcel {
+    fn default() -> Self {
+        Self::with_capacity(256)
+    }
+}
+
+impl Deref for PooledParcel {
+    type Target = Parcel<'static>;
+
+    fn deref(&self) -> &Parcel<'static> {
+        &self.parcel
+    }
+}
+
+impl DerefMut for PooledParcel {
+    fn deref_mut(&mut self) -> &mut Parcel<'static> {
+        &mut self.parcel
+    }
+}
+
+impl Drop for PooledParcel {
+    fn drop(&mut self) {
+        self.parcel.reset();
+        let ParcelData::Vec(data) = &mut self.parcel.data else {
+            return;
+        };
+        if data.capacity() == 0 || data.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+
+        let data = std::mem::take(data);
+        // the pool is gone when the thread is exiting
+        let _ = POOL.try_with(|pool| {
+            let mut pool = pool.borrow_mut();
+            if pool.len() < MAX_POOLED {
+                pool.push(data);
+            }
+        });
+    }
+}
+
+impl std::fmt::Debug for PooledParcel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.parcel.fmt(f)
+    }
+}
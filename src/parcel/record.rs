@@ -0,0 +1,277 @@
+//! Snapshots of transactions, to capture them on a device and replay them
+//! in tests.
+//!
+//! A record file is a sequence of records, all little-endian:
+//!
+//! ```text
+//! magic        b"BPRC"
+//! version      u16 (1)
+//! kind         u8 (RecordKind)
+//! reserved     u8
+//! code         u32
+//! flags        u32 (TransactionFlag)
+//! sender_pid   i32
+//! sender_euid  u32
+//! descriptor   i32 length in bytes (-1 for none), then the UTF-8 bytes
+//! data         u32 length, then the bytes
+//! objects      u32 count, then for each object:
+//!                  offset u64, type u32, flags u32, handle or pointer u64, cookie u64
+//! ```
+//!
+//! The objects are those of the data at their offsets, they are also
+//! written decoded so that a record can be read without a parcel.
+
+use std::io::{ErrorKind, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+
+use super::Parcel;
+use crate::{
+    binder::{binder_type::BinderType, transaction_data::BinderTransactionData},
+    error::{BinderError, Result},
+};
+
+const MAGIC: &[u8; 4] = b"BPRC";
+const VERSION: u16 = 1;
+
+/// Where a recorded transaction was seen.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+pub enum RecordKind {
+    /// Sent by `Binder::transaction`.
+    Outgoing = 0,
+    /// A `BR_TRANSACTION` received by `Binder::binder_parse`.
+    Incoming = 1,
+    /// A `BR_REPLY` received by `Binder::binder_parse`.
+    Reply = 2,
+}
+
+/// An object of a recorded parcel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedObject {
+    pub offset: u64,
+    pub binder_type: BinderType,
+    pub flags: u32,
+    /// The handle or the file descriptor, or the pointer of a local binder.
+    pub value: u64,
+    pub cookie: u64,
+}
+
+/// A transaction and its parcel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParcelRecord {
+    pub kind: RecordKind,
+    pub code: u32,
+    pub flags: u32,
+    pub sender_pid: i32,
+    pub sender_euid: u32,
+    /// The interface descriptor, if the data starts with an interface token.
+    pub descriptor: Option<String>,
+    pub data: Vec<u8>,
+    pub objects: Vec<RecordedObject>,
+}
+
+impl ParcelRecord {
+    /// Record `parcel`, the data of `tx`.
+    pub fn new(kind: RecordKind, tx: &BinderTransactionData, parcel: &Parcel) -> Result<Self> {
+        let objects = parcel
+            .objects
+            .as_slice()
+            .iter()
+            .map(|&offset| {
+                let obj = parcel.object_at(offset)?;
+                Ok(RecordedObject {
+                    offset: offset as u64,
+                    binder_type: obj.header_type(),
+                    flags: obj.flags(),
+                    value: obj.pointer() as u64,
+                    cookie: obj.cookie() as u64,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            kind,
+            code: tx.code,
+            flags: tx.flags.bits(),
+            sender_pid: tx.sender_pid,
+            sender_euid: tx.sender_euid,
+            descriptor: parcel.interface_descriptor(),
+            data: parcel.data.as_slice().to_vec(),
+            objects,
+        })
+    }
+
+    /// Record the transaction `tx` received from the driver, leaving its
+    /// objects to the parcel of its handler: the file descriptors it owns
+    /// stay open.
+    ///
+    /// # Safety
+    /// The buffer of `tx` must be valid, not freed yet.
+    pub unsafe fn from_transaction(kind: RecordKind, tx: &BinderTransactionData) -> Result<Self> {
+        // SAFETY: guaranteed by the caller.
+        let parcel = unsafe { tx.borrowed_parcel()? };
+        Self::new(kind, tx, &parcel)
+    }
+
+    /// A parcel of the recorded data and objects.
+    ///
+    /// The objects are restored as recorded: handles and file descriptors
+    /// are those of the recording process. The parcel doesn't own the file
+    /// descriptors, dropping it leaves them open.
+    pub fn to_parcel(&self) -> Result<Parcel<'static>> {
+        let mut parcel = Parcel::from_vec(self.data.clone());
+        let offsets = self
+            .objects
+            .iter()
+            .map(|obj| usize::try_from(obj.offset).map_err(|_| BinderError::BadValue))
+            .collect::<Result<_>>()?;
+        parcel.set_objects(offsets)?;
+
+        for (recorded, &offset) in self.objects.iter().zip(parcel.objects.as_slice()) {
+            let mut obj = parcel.object_at(offset)?;
+            if obj.header_type() != recorded.binder_type {
+                error!("ParcelRecord: object at {offset} doesn't match its record");
+                return Err(BinderError::BadValue);
+            }
+            // a descriptor now open in this process would be closed on drop
+            if obj.header_type() == BinderType::Fd && obj.cookie() != 0 {
+                obj.set_cookie(0);
+                parcel.data.to_mut()[offset..offset + obj.as_bytes().len()]
+                    .copy_from_slice(obj.as_bytes());
+            }
+        }
+
+        Ok(parcel)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_u16::<LittleEndian>(VERSION)?;
+        writer.write_u8(self.kind as u8)?;
+        writer.write_u8(0)?;
+        writer.write_u32::<LittleEndian>(self.code)?;
+        writer.write_u32::<LittleEndian>(self.flags)?;
+        writer.write_i32::<LittleEndian>(self.sender_pid)?;
+        writer.write_u32::<LittleEndian>(self.sender_euid)?;
+
+        match &self.descriptor {
+            Some(descriptor) => {
+                writer.write_i32::<LittleEndian>(len_i32(descriptor.len())?)?;
+                writer.write_all(descriptor.as_bytes())?;
+            }
+            None => writer.write_i32::<LittleEndian>(-1)?,
+        }
+
+        writer.write_u32::<LittleEndian>(len_i32(self.data.len())? as u32)?;
+        writer.write_all(&self.data)?;
+
+        writer.write_u32::<LittleEndian>(len_i32(self.objects.len())? as u32)?;
+        for obj in &self.objects {
+            writer.write_u64::<LittleEndian>(obj.offset)?;
+            writer.write_u32::<LittleEndian>(obj.binder_type as u32)?;
+            writer.write_u32::<LittleEndian>(obj.flags)?;
+            writer.write_u64::<LittleEndian>(obj.value)?;
+            writer.write_u64::<LittleEndian>(obj.cookie)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read the next record, `None` at the end of `reader`.
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Option<Self>> {
+        let mut magic = [0u8; 4];
+        match reader.read_exact(&mut magic) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        if &magic != MAGIC {
+            error!("ParcelRecord: bad magic {magic:X?}");
+            return Err(BinderError::BadType);
+        }
+        let version = reader.read_u16::<LittleEndian>()?;
+        if version != VERSION {
+            error!("ParcelRecord: unsupported version {version}");
+            return Err(BinderError::BadType);
+        }
+        let kind = reader.read_u8()?;
+        let kind = RecordKind::from_u8(kind).ok_or_else(|| {
+            error!("ParcelRecord: unknown kind {kind}");
+            BinderError::BadValue
+        })?;
+        let _reserved = reader.read_u8()?;
+
+        let code = reader.read_u32::<LittleEndian>()?;
+        let flags = reader.read_u32::<LittleEndian>()?;
+        let sender_pid = reader.read_i32::<LittleEndian>()?;
+        let sender_euid = reader.read_u32::<LittleEndian>()?;
+
+        let descriptor = match reader.read_i32::<LittleEndian>()? {
+            -1 => None,
+            len if len < 0 => {
+                error!("ParcelRecord: negative descriptor length {len}");
+                return Err(BinderError::BadValue);
+            }
+            len => Some(String::from_utf8(read_bytes(reader, len as u32)?)?),
+        };
+
+        let len = reader.read_u32::<LittleEndian>()?;
+        let data = read_bytes(reader, len)?;
+
+        let count = reader.read_u32::<LittleEndian>()?;
+        let objects = (0..count)
+            .map(|_| {
+                let offset = reader.read_u64::<LittleEndian>()?;
+                let raw_type = reader.read_u32::<LittleEndian>()?;
+                let binder_type = BinderType::from_u32(raw_type).ok_or_else(|| {
+                    error!("ParcelRecord: invalid object type {raw_type:#X}");
+                    BinderError::BadType
+                })?;
+                Ok(RecordedObject {
+                    offset,
+                    binder_type,
+                    flags: reader.read_u32::<LittleEndian>()?,
+                    value: reader.read_u64::<LittleEndian>()?,
+                    cookie: reader.read_u64::<LittleEndian>()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Some(Self {
+            kind,
+            code,
+            flags,
+            sender_pid,
+            sender_euid,
+            descriptor,
+            data,
+            objects,
+        }))
+    }
+
+    /// Read all the records of `reader`.
+    pub fn read_all<R: Read>(reader: &mut R) -> Result<Vec<Self>> {
+        std::iter::from_fn(|| Self::read_from(reader).transpose()).collect()
+    }
+}
+
+fn len_i32(len: usize) -> Result<i32> {
+    i32::try_from(len).map_err(|_| {
+        error!("ParcelRecord: length {len} too large");
+        BinderError::BadValue
+    })
+}
+
+/// Read `len` bytes, without trusting `len` for the allocation.
+fn read_bytes<R: Read>(reader: &mut R, len: u32) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        error!("ParcelRecord: truncated, {} of {len} bytes", bytes.len());
+        return Err(BinderError::NotEnoughData);
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,923 @@
+//! [serde](https://serde.rs) support: the values of serde types are written
+//! the way AIDL writes the equivalent types.
+//!
+//! - `bool`, `i8`, `u8`, `i16`, `u16` and `char` are written as an `i32`,
+//!   the other numbers as themselves.
+//! - Strings are UTF-16 (`String16`), bytes a `byte[]`.
+//! - Sequences and maps start with their `i32` length. Tuples have no length.
+//! - Structs are written as a parcelable: the non-null flag, then the size
+//!   of the fields and the fields. Fields missing at the end, written by an
+//!   older peer, need `#[serde(default)]`; for this reason the last field
+//!   can't be one written as nothing, like `()`.
+//! - `Option` is written as the null flag followed by the value, the flag
+//!   of a struct being its own non-null flag. As in AIDL, an `Option` of a
+//!   string, sequence or map has no flag: `None` is a `-1` length. That is
+//!   known from the type of a field, element, map value, newtype variant or
+//!   of the value given to [`to_parcel`], not inside another `Option` or a
+//!   newtype struct, where the flag is used.
+//! - Enums are written as the `i32` index of their variant followed by its
+//!   fields: an enum of unit variants is an AIDL enum, and the others are
+//!   the content of an AIDL union.
+//!
+//! The format is not self-describing, so `deserialize_any` and
+//! `deserialize_ignored_any` are not supported.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    marker::PhantomData,
+};
+
+use ::serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor},
+    ser,
+};
+
+use super::{
+    Parcel,
+    parcelable::{
+        Deserialize, DeserializeArray, DeserializeOption, NON_NULL_PARCELABLE_FLAG,
+        NULL_PARCELABLE_FLAG, Serialize, SerializeArray, SerializeOption,
+    },
+};
+use crate::error::{BinderError, Result};
+
+impl ser::Error for BinderError {
+    fn custom<T: Display>(msg: T) -> Self {
+        BinderError::FailedParseParcel(msg.to_string())
+    }
+}
+
+impl de::Error for BinderError {
+    fn custom<T: Display>(msg: T) -> Self {
+        BinderError::FailedParseParcel(msg.to_string())
+    }
+}
+
+/// Write a serde value into `parcel`.
+pub fn to_parcel<T: ser::Serialize + ?Sized>(value: &T, parcel: &mut Parcel) -> Result<()> {
+    Serializer::new(parcel).value(value)
+}
+
+/// Read a serde value from `parcel`.
+pub fn from_parcel<T: DeserializeOwned>(parcel: &mut Parcel) -> Result<T> {
+    Deserializer::new(parcel).value(PhantomData::<T>)
+}
+
+/// A serde value usable wherever the parcel traits are expected,
+/// e.g. as an argument of an AIDL method.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Serde<T>(pub T);
+
+impl<T: ser::Serialize> Serialize for Serde<T> {
+    fn serialize(&self, parcel: &mut Parcel) -> Result<()> {
+        to_parcel(&self.0, parcel)
+    }
+}
+
+impl<T: ser::Serialize> SerializeOption for Serde<T> {
+    fn serialize_option(this: Option<&Self>, parcel: &mut Parcel) -> Result<()> {
+        to_parcel(&this.map(|this| &this.0), parcel)
+    }
+}
+
+impl<T: ser::Serialize> SerializeArray for Serde<T> {}
+
+impl<T: DeserializeOwned> Deserialize for Serde<T> {
+    fn deserialize(parcel: &mut Parcel) -> Result<Self> {
+        from_parcel(parcel).map(Self)
+    }
+}
+
+impl<T: DeserializeOwned> DeserializeOption for Serde<T> {
+    fn deserialize_option(parcel: &mut Parcel) -> Result<Option<Self>> {
+        Ok(from_parcel::<Option<T>>(parcel)?.map(Self))
+    }
+}
+
+impl<T: DeserializeOwned> DeserializeArray for Serde<T> {}
+
+/// Whether AIDL writes a null `Self` as a `-1` length instead of a null flag.
+trait NullAsLength {
+    fn null_as_length() -> bool;
+}
+
+impl<T: ?Sized> NullAsLength for T {
+    default fn null_as_length() -> bool {
+        false
+    }
+}
+
+impl<T> NullAsLength for Option<T> {
+    fn null_as_length() -> bool {
+        T::has_length()
+    }
+}
+
+impl<T: ?Sized> NullAsLength for &T {
+    fn null_as_length() -> bool {
+        T::null_as_length()
+    }
+}
+
+/// The seed of a `Deserialize` type.
+impl<T: ?Sized> NullAsLength for PhantomData<T> {
+    fn null_as_length() -> bool {
+        T::null_as_length()
+    }
+}
+
+/// Whether `Self` is written after its length: strings, sequences and maps.
+trait HasLength {
+    fn has_length() -> bool;
+}
+
+impl<T: ?Sized> HasLength for T {
+    default fn has_length() -> bool {
+        false
+    }
+}
+
+impl HasLength for str {
+    fn has_length() -> bool {
+        true
+    }
+}
+
+impl HasLength for String {
+    fn has_length() -> bool {
+        true
+    }
+}
+
+impl<T> HasLength for [T] {
+    fn has_length() -> bool {
+        true
+    }
+}
+
+impl<T> HasLength for Vec<T> {
+    fn has_length() -> bool {
+        true
+    }
+}
+
+impl<K, V> HasLength for BTreeMap<K, V> {
+    fn has_length() -> bool {
+        true
+    }
+}
+
+impl<K, V, S> HasLength for HashMap<K, V, S> {
+    fn has_length() -> bool {
+        true
+    }
+}
+
+impl<T: ?Sized> HasLength for &T {
+    fn has_length() -> bool {
+        T::has_length()
+    }
+}
+
+impl<T: ?Sized> HasLength for Box<T> {
+    fn has_length() -> bool {
+        T::has_length()
+    }
+}
+
+/// Write the length of what is written after `start`, in front of it.
+fn patch_len(parcel: &mut Parcel, start: usize, len: usize) -> Result<()> {
+    let len = i32::try_from(len).map_err(|_| BinderError::BadValue)?;
+    let end = parcel.data_position();
+    parcel.set_data_position(start);
+    parcel.write(&len)?;
+    parcel.set_data_position(end);
+    Ok(())
+}
+
+pub struct Serializer<'p, 'a> {
+    parcel: &'p mut Parcel<'a>,
+    /// An `Option` is `Some`, its flag is written before the value.
+    pending_flag: bool,
+    /// The value is an `Option` written with a `-1` length for `None`.
+    null_as_length: bool,
+}
+
+impl<'p, 'a> Serializer<'p, 'a> {
+    pub fn new(parcel: &'p mut Parcel<'a>) -> Self {
+        Self {
+            parcel,
+            pending_flag: false,
+            null_as_length: false,
+        }
+    }
+
+    fn value<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.null_as_length = T::null_as_length();
+        value.serialize(self)
+    }
+
+    fn write<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        if std::mem::take(&mut self.pending_flag) {
+            self.parcel.write(&NON_NULL_PARCELABLE_FLAG)?;
+        }
+        self.parcel.write(value)
+    }
+
+    /// Start a value prefixed by its length, patched by [`Compound::end`].
+    fn compound(&mut self, len: Option<usize>, kind: CompoundKind) -> Result<Compound<'_, 'p, 'a>> {
+        let start = self.parcel.data_position();
+        self.write(&(len.unwrap_or(0) as i32))?;
+        Ok(Compound {
+            ser: self,
+            start,
+            count: 0,
+            kind,
+        })
+    }
+
+    fn struct_body(&mut self) -> Result<Compound<'_, 'p, 'a>> {
+        self.pending_flag = false;
+        self.compound(None, CompoundKind::Sized)
+    }
+}
+
+enum CompoundKind {
+    /// No length.
+    Tuple,
+    /// The number of elements.
+    Counted,
+    /// The size in bytes, itself included.
+    Sized,
+}
+
+pub struct Compound<'s, 'p, 'a> {
+    ser: &'s mut Serializer<'p, 'a>,
+    start: usize,
+    count: usize,
+    kind: CompoundKind,
+}
+
+impl Compound<'_, '_, '_> {
+    fn element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.count += 1;
+        self.ser.value(value)
+    }
+
+    fn end(self) -> Result<()> {
+        match self.kind {
+            CompoundKind::Tuple => Ok(()),
+            CompoundKind::Counted => patch_len(self.ser.parcel, self.start, self.count),
+            CompoundKind::Sized => {
+                let size = self.ser.parcel.data_position() - self.start;
+                patch_len(self.ser.parcel, self.start, size)
+            }
+        }
+    }
+}
+
+impl<'s, 'p, 'a> ser::Serializer for &'s mut Serializer<'p, 'a> {
+    type Ok = ();
+    type Error = BinderError;
+    type SerializeSeq = Compound<'s, 'p, 'a>;
+    type SerializeTuple = Compound<'s, 'p, 'a>;
+    type SerializeTupleStruct = Compound<'s, 'p, 'a>;
+    type SerializeTupleVariant = Compound<'s, 'p, 'a>;
+    type SerializeMap = Compound<'s, 'p, 'a>;
+    type SerializeStruct = Compound<'s, 'p, 'a>;
+    type SerializeStructVariant = Compound<'s, 'p, 'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.write(&v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.write(&v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.write(&v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.write(&v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.write(&v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.write(&(v as u128))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.write(&v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.write(&v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.write(&v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.write(&v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.write(&v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.write(&v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.write(&v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.write(&(v as u32))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.write(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.write(v)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        if std::mem::take(&mut self.null_as_length) {
+            self.write(&-1i32)
+        } else {
+            self.write(&NULL_PARCELABLE_FLAG)
+        }
+    }
+
+    fn serialize_some<T: ser::Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        // the flag of an outer `Some`
+        if std::mem::take(&mut self.pending_flag) {
+            self.parcel.write(&NON_NULL_PARCELABLE_FLAG)?;
+        }
+        // the length of the value tells it isn't null
+        self.pending_flag = !std::mem::take(&mut self.null_as_length);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        if std::mem::take(&mut self.pending_flag) {
+            self.parcel.write(&NON_NULL_PARCELABLE_FLAG)?;
+        }
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        self.write(&(variant_index as i32))
+    }
+
+    fn serialize_newtype_struct<T: ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.write(&(variant_index as i32))?;
+        self.value(value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'s, 'p, 'a>> {
+        self.compound(len, CompoundKind::Counted)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'s, 'p, 'a>> {
+        self.serialize_unit()?;
+        Ok(Compound {
+            start: self.parcel.data_position(),
+            ser: self,
+            count: 0,
+            kind: CompoundKind::Tuple,
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Compound<'s, 'p, 'a>> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'s, 'p, 'a>> {
+        self.write(&(variant_index as i32))?;
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Compound<'s, 'p, 'a>> {
+        self.compound(len, CompoundKind::Counted)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'s, 'p, 'a>> {
+        self.pending_flag = false;
+        self.parcel.write(&NON_NULL_PARCELABLE_FLAG)?;
+        self.struct_body()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'p, 'a>> {
+        self.write(&(variant_index as i32))?;
+        self.struct_body()
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_, '_, '_> {
+    type Ok = ();
+    type Error = BinderError;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_, '_, '_> {
+    type Ok = ();
+    type Error = BinderError;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_, '_, '_> {
+    type Ok = ();
+    type Error = BinderError;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_, '_, '_> {
+    type Ok = ();
+    type Error = BinderError;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeMap for Compound<'_, '_, '_> {
+    type Ok = ();
+    type Error = BinderError;
+
+    fn serialize_key<T: ser::Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.element(key)
+    }
+
+    fn serialize_value<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.ser.value(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_, '_, '_> {
+    type Ok = ();
+    type Error = BinderError;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_, '_, '_> {
+    type Ok = ();
+    type Error = BinderError;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+pub struct Deserializer<'p, 'a> {
+    parcel: &'p mut Parcel<'a>,
+    /// The non-null flag of a `Some` was read, it is also the flag of the
+    /// struct which may follow.
+    flag_read: bool,
+    /// The value is an `Option` written with a `-1` length for `None`.
+    null_as_length: bool,
+}
+
+impl<'p, 'a> Deserializer<'p, 'a> {
+    pub fn new(parcel: &'p mut Parcel<'a>) -> Self {
+        Self {
+            parcel,
+            flag_read: false,
+            null_as_length: false,
+        }
+    }
+
+    fn value<'de, T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value> {
+        self.null_as_length = T::null_as_length();
+        seed.deserialize(self)
+    }
+
+    fn read<T: Deserialize>(&mut self) -> Result<T> {
+        self.flag_read = false;
+        self.parcel.read()
+    }
+
+    /// Read the length of a sequence or map, `-1` (null) being empty.
+    fn read_len(&mut self) -> Result<usize> {
+        let len: i32 = self.read()?;
+        if len < -1 {
+            error!("Negative length given in parcel: {len}");
+            return Err(BinderError::BadValue);
+        }
+        let len = len.max(0) as usize;
+        self.parcel.check_alloc(len, 0)?;
+        Ok(len)
+    }
+
+    /// Read the size of a struct and its fields, skipping the fields
+    /// written by a newer peer.
+    fn struct_body<'de, V: Visitor<'de>>(&mut self, fields: usize, visitor: V) -> Result<V::Value> {
+        let start = self.parcel.data_position();
+        let size: i32 = self.read()?;
+        let end = usize::try_from(size)
+            .ok()
+            .filter(|size| *size >= size_of::<i32>())
+            .and_then(|size| start.checked_add(size))
+            .filter(|end| *end <= self.parcel.data_size())
+            .ok_or_else(|| {
+                error!("Parcel: bad struct size: {size}");
+                BinderError::BadValue
+            })?;
+
+        let value = self.parcel.nested(|parcel| {
+            visitor.visit_seq(Access {
+                de: &mut Deserializer::new(parcel),
+                remaining: fields,
+                end: Some(end),
+            })
+        })?;
+        if self.parcel.data_position() > end {
+            error!("Parcel: struct read past its size");
+            return Err(BinderError::BadValue);
+        }
+        self.parcel.set_data_position(end);
+        Ok(value)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'_, '_> {
+    type Error = BinderError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(de::Error::custom("parcels are not self-describing"))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_bool(self.read()?)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i8(self.read()?)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i16(self.read()?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i32(self.read()?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64(self.read()?)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i128(self.read::<u128>()? as i128)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u8(self.read()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u16(self.read()?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u32(self.read()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.read()?)
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u128(self.read()?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f32(self.read()?)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f64(self.read()?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let code: u32 = self.read()?;
+        let c = char::from_u32(code).ok_or_else(|| {
+            error!("Invalid char in parcel: {code:#X}");
+            BinderError::BadValue
+        })?;
+        visitor.visit_char(c)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.read()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.read()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_byte_buf(self.read()?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_byte_buf(self.read()?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if std::mem::take(&mut self.null_as_length) {
+            if self.parcel.peek::<i32>()? == -1 {
+                self.parcel.skip::<i32>()?;
+                return visitor.visit_none();
+            }
+            self.flag_read = false;
+            return visitor.visit_some(self);
+        }
+        let flag: i32 = self.read()?;
+        if flag == NULL_PARCELABLE_FLAG {
+            return visitor.visit_none();
+        }
+        self.flag_read = true;
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.flag_read = false;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.read_len()?;
+        visitor.visit_seq(Access {
+            de: self,
+            remaining: len,
+            end: None,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        self.flag_read = false;
+        visitor.visit_seq(Access {
+            de: self,
+            remaining: len,
+            end: None,
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.read_len()?;
+        visitor.visit_map(Access {
+            de: self,
+            remaining: len,
+            end: None,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        if !std::mem::take(&mut self.flag_read) {
+            let flag: i32 = self.read()?;
+            if flag == NULL_PARCELABLE_FLAG {
+                error!("Deserialize for struct: UnexpectedNull");
+                return Err(BinderError::UnexpectedNull);
+            }
+        }
+        self.struct_body(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.parcel
+            .nested(|parcel| visitor.visit_enum(&mut Deserializer::new(parcel)))
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(de::Error::custom("parcels have no field names"))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(de::Error::custom("parcels are not self-describing"))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// The elements of a sequence, map, tuple or struct.
+struct Access<'d, 'p, 'a> {
+    de: &'d mut Deserializer<'p, 'a>,
+    remaining: usize,
+    /// The end of a struct: the fields after it were not written.
+    end: Option<usize>,
+}
+
+impl<'de> de::SeqAccess<'de> for Access<'_, '_, '_> {
+    type Error = BinderError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.remaining == 0
+            || self
+                .end
+                .is_some_and(|end| self.de.parcel.data_position() >= end)
+        {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        self.de.value(seed).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for Access<'_, '_, '_> {
+    type Error = BinderError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        self.de.value(seed).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        self.de.value(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for &mut Deserializer<'_, '_> {
+    type Error = BinderError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let index: i32 = self.read()?;
+        let index = u32::try_from(index).map_err(|_| {
+            error!("Negative enum variant: {index}");
+            BinderError::BadValue
+        })?;
+        let value = seed.deserialize(IntoDeserializer::<BinderError>::into_deserializer(index))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Deserializer<'_, '_> {
+    type Error = BinderError;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        self.value(seed)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.struct_body(fields.len(), visitor)
+    }
+}
@@ -0,0 +1,91 @@
+//! UTF-8 strings, as written by `Parcel::writeString8` in libbinder.
+//!
+//! `String` is written as UTF-16 (`String16`), which is also what AIDL
+//! `@utf8InCpp String` is on the wire: the C++ backend converts it with
+//! `writeUtf8AsUtf16`. [`String8`] is for hand written native interfaces
+//! which use `readString8`/`writeString8`.
+
+use std::{fmt, ops::Deref};
+
+use super::{
+    Parcel,
+    parcelable::{
+        Deserialize, DeserializeArray, DeserializeOption, Serialize, SerializeArray,
+        SerializeOption,
+    },
+};
+use crate::error::{BinderError, Result};
+
+/// A string written in UTF-8: its length in bytes (`-1` for null), then the
+/// nul terminated bytes, padded to 4 bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct String8(pub String);
+
+impl String8 {
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for String8 {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for String8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<String> for String8 {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for String8 {
+    fn from(value: &str) -> Self {
+        Self(value.to_owned())
+    }
+}
+
+impl From<String8> for String {
+    fn from(value: String8) -> Self {
+        value.0
+    }
+}
+
+impl Serialize for String8 {
+    fn serialize(&self, parcel: &mut Parcel) -> Result<()> {
+        parcel.write_string8(Some(self))
+    }
+}
+
+impl SerializeOption for String8 {
+    fn serialize_option(this: Option<&Self>, parcel: &mut Parcel) -> Result<()> {
+        parcel.write_string8(this.map(|s| s.0.as_str()))
+    }
+}
+
+impl SerializeArray for String8 {}
+
+impl Deserialize for String8 {
+    fn deserialize(parcel: &mut Parcel) -> Result<Self> {
+        parcel.read_string8()?.map(Self).ok_or_else(|| {
+            error!("Deserialize for String8: UnexpectedNull");
+            BinderError::UnexpectedNull
+        })
+    }
+}
+
+impl DeserializeOption for String8 {
+    fn deserialize_option(parcel: &mut Parcel) -> Result<Option<Self>> {
+        Ok(parcel.read_string8()?.map(Self))
+    }
+}
+
+impl DeserializeArray for String8 {}
@@ -0,0 +1,492 @@
+//! Values of Java `Parcel.writeValue`: a `VAL_*` type tag followed by the value.
+//!
+//! http://aospxref.com/android-14.0.0_r2/xref/frameworks/base/core/java/android/os/Parcel.java
+
+use super::{
+    Parcel,
+    bundle::Bundle,
+    parcelable::{Deserialize, Parcelable, Serialize},
+};
+use crate::{
+    binder::flat_object::BinderFlatObject,
+    error::{BinderError, Result},
+};
+
+pub const VAL_NULL: i32 = -1;
+pub const VAL_STRING: i32 = 0;
+pub const VAL_INTEGER: i32 = 1;
+pub const VAL_MAP: i32 = 2;
+pub const VAL_BUNDLE: i32 = 3;
+pub const VAL_PARCELABLE: i32 = 4;
+pub const VAL_SHORT: i32 = 5;
+pub const VAL_LONG: i32 = 6;
+pub const VAL_FLOAT: i32 = 7;
+pub const VAL_DOUBLE: i32 = 8;
+pub const VAL_BOOLEAN: i32 = 9;
+pub const VAL_CHARSEQUENCE: i32 = 10;
+pub const VAL_LIST: i32 = 11;
+pub const VAL_SPARSEARRAY: i32 = 12;
+pub const VAL_BYTEARRAY: i32 = 13;
+pub const VAL_STRINGARRAY: i32 = 14;
+pub const VAL_IBINDER: i32 = 15;
+pub const VAL_PARCELABLEARRAY: i32 = 16;
+pub const VAL_OBJECTARRAY: i32 = 17;
+pub const VAL_INTARRAY: i32 = 18;
+pub const VAL_LONGARRAY: i32 = 19;
+pub const VAL_BYTE: i32 = 20;
+pub const VAL_SERIALIZABLE: i32 = 21;
+pub const VAL_SPARSEBOOLEANARRAY: i32 = 22;
+pub const VAL_BOOLEANARRAY: i32 = 23;
+pub const VAL_CHARSEQUENCEARRAY: i32 = 24;
+pub const VAL_PERSISTABLEBUNDLE: i32 = 25;
+pub const VAL_SIZE: i32 = 26;
+pub const VAL_SIZEF: i32 = 27;
+pub const VAL_DOUBLEARRAY: i32 = 28;
+pub const VAL_CHAR: i32 = 29;
+pub const VAL_SHORTARRAY: i32 = 30;
+pub const VAL_CHARARRAY: i32 = 31;
+pub const VAL_FLOATARRAY: i32 = 32;
+
+/// Since Android 13, these values are written with their length in front,
+/// so a reader can skip them without knowing their class.
+fn is_length_prefixed(tag: i32) -> bool {
+    matches!(
+        tag,
+        VAL_MAP
+            | VAL_PARCELABLE
+            | VAL_LIST
+            | VAL_SPARSEARRAY
+            | VAL_PARCELABLEARRAY
+            | VAL_OBJECTARRAY
+            | VAL_SERIALIZABLE
+    )
+}
+
+/// A value written by Java `Parcel.writeValue`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParcelValue {
+    Null,
+    String(String),
+    Int(i32),
+    Long(i64),
+    Short(i16),
+    Byte(i8),
+    Char(u16),
+    Float(f32),
+    Double(f64),
+    Boolean(bool),
+    ByteArray(Vec<u8>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+    DoubleArray(Vec<f64>),
+    BooleanArray(Vec<bool>),
+    StringArray(Vec<Option<String>>),
+    /// `VAL_BUNDLE`, or `VAL_PERSISTABLEBUNDLE` for a persistable bundle.
+    Bundle(Bundle),
+    ShortArray(Vec<i16>),
+    CharArray(Vec<u16>),
+    FloatArray(Vec<f32>),
+    /// A `CharSequence` without spans.
+    CharSequence(Option<String>),
+    CharSequenceArray(Vec<Option<String>>),
+    Map(Vec<(ParcelValue, ParcelValue)>),
+    List(Vec<ParcelValue>),
+    ObjectArray(Vec<ParcelValue>),
+    SparseArray(Vec<(i32, ParcelValue)>),
+    SparseBooleanArray(Vec<(i32, bool)>),
+    /// `android.util.Size`
+    Size(i32, i32),
+    /// `android.util.SizeF`
+    SizeF(f32, f32),
+    Binder(Option<BinderFlatObject>),
+    /// A parcelable we don't know: its class name and content.
+    /// Binder objects and file descriptors inside are not kept.
+    Parcelable {
+        class: String,
+        data: Vec<u8>,
+    },
+    /// A Java serialized object: its class name and serialized bytes.
+    Serializable {
+        class: String,
+        data: Vec<u8>,
+    },
+    /// A length-prefixed value kept undecoded (`VAL_PARCELABLEARRAY`):
+    /// its `VAL_*` tag and content.
+    Lazy {
+        tag: i32,
+        data: Vec<u8>,
+    },
+}
+
+impl ParcelValue {
+    /// `VAL_*` tag of this value.
+    pub fn tag(&self) -> i32 {
+        match self {
+            Self::Null => VAL_NULL,
+            Self::String(_) => VAL_STRING,
+            Self::Int(_) => VAL_INTEGER,
+            Self::Long(_) => VAL_LONG,
+            Self::Short(_) => VAL_SHORT,
+            Self::Byte(_) => VAL_BYTE,
+            Self::Char(_) => VAL_CHAR,
+            Self::Float(_) => VAL_FLOAT,
+            Self::Double(_) => VAL_DOUBLE,
+            Self::Boolean(_) => VAL_BOOLEAN,
+            Self::ByteArray(_) => VAL_BYTEARRAY,
+            Self::IntArray(_) => VAL_INTARRAY,
+            Self::LongArray(_) => VAL_LONGARRAY,
+            Self::DoubleArray(_) => VAL_DOUBLEARRAY,
+            Self::BooleanArray(_) => VAL_BOOLEANARRAY,
+            Self::StringArray(_) => VAL_STRINGARRAY,
+            Self::Bundle(bundle) if bundle.is_persistable() => VAL_PERSISTABLEBUNDLE,
+            Self::Bundle(_) => VAL_BUNDLE,
+            Self::ShortArray(_) => VAL_SHORTARRAY,
+            Self::CharArray(_) => VAL_CHARARRAY,
+            Self::FloatArray(_) => VAL_FLOATARRAY,
+            Self::CharSequence(_) => VAL_CHARSEQUENCE,
+            Self::CharSequenceArray(_) => VAL_CHARSEQUENCEARRAY,
+            Self::Map(_) => VAL_MAP,
+            Self::List(_) => VAL_LIST,
+            Self::ObjectArray(_) => VAL_OBJECTARRAY,
+            Self::SparseArray(_) => VAL_SPARSEARRAY,
+            Self::SparseBooleanArray(_) => VAL_SPARSEBOOLEANARRAY,
+            Self::Size(..) => VAL_SIZE,
+            Self::SizeF(..) => VAL_SIZEF,
+            Self::Binder(_) => VAL_IBINDER,
+            Self::Parcelable { .. } => VAL_PARCELABLE,
+            Self::Serializable { .. } => VAL_SERIALIZABLE,
+            Self::Lazy { tag, .. } => *tag,
+        }
+    }
+
+    /// Whether a `PersistableBundle` can hold this value.
+    pub fn is_persistable(&self) -> bool {
+        match self {
+            Self::Null
+            | Self::String(_)
+            | Self::Int(_)
+            | Self::Long(_)
+            | Self::Double(_)
+            | Self::Boolean(_)
+            | Self::IntArray(_)
+            | Self::LongArray(_)
+            | Self::DoubleArray(_)
+            | Self::BooleanArray(_)
+            | Self::StringArray(_) => true,
+            Self::Bundle(bundle) => bundle.is_persistable(),
+            _ => false,
+        }
+    }
+
+    fn write_content(&self, parcel: &mut Parcel) -> Result<()> {
+        match self {
+            Self::Null => Ok(()),
+            Self::String(v) => parcel.write(v),
+            Self::Int(v) => parcel.write(v),
+            Self::Long(v) => parcel.write(v),
+            Self::Short(v) => parcel.write(v),
+            Self::Byte(v) => parcel.write(v),
+            Self::Char(v) => parcel.write(&(*v as i32)),
+            Self::Float(v) => parcel.write(v),
+            Self::Double(v) => parcel.write(v),
+            Self::Boolean(v) => parcel.write(v),
+            Self::ByteArray(v) => parcel.write(v),
+            Self::IntArray(v) => parcel.write(v),
+            Self::LongArray(v) => parcel.write(v),
+            Self::DoubleArray(v) => parcel.write(v),
+            Self::BooleanArray(v) => parcel.write(v),
+            Self::StringArray(v) => parcel.write(v),
+            Self::Bundle(v) => v.write_to_parcel(parcel),
+            Self::ShortArray(v) => parcel.write(v),
+            Self::CharArray(v) => parcel.write(v),
+            Self::FloatArray(v) => parcel.write(v),
+            Self::CharSequence(v) => write_char_sequence(parcel, v.as_deref()),
+            Self::CharSequenceArray(v) => {
+                write_len(parcel, v.len())?;
+                v.iter()
+                    .try_for_each(|cs| write_char_sequence(parcel, cs.as_deref()))
+            }
+            Self::Map(v) => {
+                write_len(parcel, v.len())?;
+                v.iter().try_for_each(|(key, value)| {
+                    parcel.write(key)?;
+                    parcel.write(value)
+                })
+            }
+            Self::List(v) | Self::ObjectArray(v) => {
+                write_len(parcel, v.len())?;
+                v.iter().try_for_each(|value| parcel.write(value))
+            }
+            Self::SparseArray(v) => {
+                write_len(parcel, v.len())?;
+                v.iter().try_for_each(|(key, value)| {
+                    parcel.write(key)?;
+                    parcel.write(value)
+                })
+            }
+            Self::SparseBooleanArray(v) => {
+                write_len(parcel, v.len())?;
+                v.iter().try_for_each(|(key, value)| {
+                    parcel.write(key)?;
+                    parcel.write(value)
+                })
+            }
+            Self::Size(width, height) => {
+                parcel.write(width)?;
+                parcel.write(height)
+            }
+            Self::SizeF(width, height) => {
+                parcel.write(width)?;
+                parcel.write(height)
+            }
+            Self::Binder(v) => parcel.write_strong_binder(v.as_ref()),
+            // Parcel.writeParcelableCreator, then the parcelable
+            Self::Parcelable { class, data } => {
+                parcel.write(class)?;
+                parcel.write_aligned_data(data);
+                Ok(())
+            }
+            Self::Serializable { class, data } => {
+                parcel.write(class)?;
+                parcel.write(data)
+            }
+            Self::Lazy { data, .. } => {
+                parcel.write_aligned_data(data);
+                Ok(())
+            }
+        }
+    }
+
+    fn read_value(parcel: &mut Parcel) -> Result<Self> {
+        let tag: i32 = parcel.read()?;
+        if !is_length_prefixed(tag) {
+            return Self::read_content(tag, parcel, None);
+        }
+
+        let len: i32 = parcel.read()?;
+        let end = usize::try_from(len)
+            .ok()
+            .and_then(|len| parcel.data_position().checked_add(len))
+            .filter(|end| *end <= parcel.data_size())
+            .ok_or_else(|| {
+                error!("Bad length {len} for value of type {tag}");
+                BinderError::BadValue
+            })?;
+
+        let value = Self::read_content(tag, parcel, Some(end))?;
+        if parcel.data_position() > end {
+            error!("Value of type {tag} overflows its length {len}");
+            return Err(BinderError::BadValue);
+        }
+        parcel.set_data_position(end);
+        Ok(value)
+    }
+
+    /// Read a value of type `tag`, `end` is where a length-prefixed value ends.
+    fn read_content(tag: i32, parcel: &mut Parcel, end: Option<usize>) -> Result<Self> {
+        Ok(match tag {
+            VAL_NULL => Self::Null,
+            VAL_STRING => Self::String(parcel.read()?),
+            VAL_INTEGER => Self::Int(parcel.read()?),
+            VAL_LONG => Self::Long(parcel.read()?),
+            VAL_SHORT => Self::Short(parcel.read()?),
+            VAL_BYTE => Self::Byte(parcel.read()?),
+            VAL_CHAR => Self::Char(parcel.read::<i32>()? as u16),
+            VAL_FLOAT => Self::Float(parcel.read()?),
+            VAL_DOUBLE => Self::Double(parcel.read()?),
+            VAL_BOOLEAN => Self::Boolean(parcel.read()?),
+            VAL_BYTEARRAY => Self::ByteArray(parcel.read()?),
+            VAL_INTARRAY => Self::IntArray(parcel.read()?),
+            VAL_LONGARRAY => Self::LongArray(parcel.read()?),
+            VAL_DOUBLEARRAY => Self::DoubleArray(parcel.read()?),
+            VAL_BOOLEANARRAY => Self::BooleanArray(parcel.read()?),
+            VAL_STRINGARRAY => Self::StringArray(parcel.read()?),
+            VAL_SHORTARRAY => Self::ShortArray(parcel.read()?),
+            VAL_CHARARRAY => Self::CharArray(parcel.read()?),
+            VAL_FLOATARRAY => Self::FloatArray(parcel.read()?),
+            VAL_BUNDLE | VAL_PERSISTABLEBUNDLE => {
+                // `Parcel.writeBundle` writes a null bundle as -1
+                let start = parcel.data_position();
+                if parcel.read::<i32>()? == -1 {
+                    return Ok(Self::Null);
+                }
+                parcel.set_data_position(start);
+
+                let mut bundle = if tag == VAL_PERSISTABLEBUNDLE {
+                    Bundle::new_persistable()
+                } else {
+                    Bundle::new()
+                };
+                bundle.read_from_parcel(parcel)?;
+                Self::Bundle(bundle)
+            }
+            VAL_CHARSEQUENCE => Self::CharSequence(read_char_sequence(parcel)?),
+            VAL_CHARSEQUENCEARRAY => Self::CharSequenceArray(
+                (0..read_len::<Option<String>>(parcel)?)
+                    .map(|_| read_char_sequence(parcel))
+                    .collect::<Result<_>>()?,
+            ),
+            VAL_MAP => Self::Map(
+                (0..read_len::<(Self, Self)>(parcel)?)
+                    .map(|_| Ok((parcel.read()?, parcel.read()?)))
+                    .collect::<Result<_>>()?,
+            ),
+            VAL_LIST => Self::List(
+                (0..read_len::<Self>(parcel)?)
+                    .map(|_| parcel.read())
+                    .collect::<Result<_>>()?,
+            ),
+            VAL_OBJECTARRAY => Self::ObjectArray(
+                (0..read_len::<Self>(parcel)?)
+                    .map(|_| parcel.read())
+                    .collect::<Result<_>>()?,
+            ),
+            VAL_SPARSEARRAY => Self::SparseArray(
+                (0..read_len::<(i32, Self)>(parcel)?)
+                    .map(|_| Ok((parcel.read()?, parcel.read()?)))
+                    .collect::<Result<_>>()?,
+            ),
+            VAL_SPARSEBOOLEANARRAY => Self::SparseBooleanArray(
+                (0..read_len::<(i32, bool)>(parcel)?)
+                    .map(|_| Ok((parcel.read()?, parcel.read()?)))
+                    .collect::<Result<_>>()?,
+            ),
+            VAL_SIZE => Self::Size(parcel.read()?, parcel.read()?),
+            VAL_SIZEF => Self::SizeF(parcel.read()?, parcel.read()?),
+            VAL_IBINDER => Self::Binder(parcel.read_strong_binder()?),
+            VAL_PARCELABLE => {
+                let class: String = parcel.read()?;
+                let len = end
+                    .and_then(|end| end.checked_sub(parcel.data_position()))
+                    .ok_or_else(|| {
+                        error!("Parcelable {class:?} overflows its value");
+                        BinderError::BadValue
+                    })?;
+                let data = parcel.read_aligned_data(len)?.to_vec();
+                Self::Parcelable { class, data }
+            }
+            VAL_SERIALIZABLE => Self::Serializable {
+                class: parcel.read()?,
+                data: parcel.read()?,
+            },
+            VAL_PARCELABLEARRAY => {
+                let len = end.map_or(0, |end| end.saturating_sub(parcel.data_position()));
+                let data = parcel.read_aligned_data(len)?.to_vec();
+                Self::Lazy { tag, data }
+            }
+            tag => {
+                error!("Unsupported value type: {tag}");
+                return Err(BinderError::BadType);
+            }
+        })
+    }
+}
+
+impl Serialize for ParcelValue {
+    fn serialize(&self, parcel: &mut Parcel) -> Result<()> {
+        let tag = self.tag();
+        parcel.write(&tag)?;
+        if is_length_prefixed(tag) {
+            write_length_prefixed(parcel, |parcel| self.write_content(parcel))
+        } else {
+            self.write_content(parcel)
+        }
+    }
+}
+
+impl Deserialize for ParcelValue {
+    fn deserialize(parcel: &mut Parcel) -> Result<Self> {
+        parcel.nested(Self::read_value)
+    }
+}
+
+macro_rules! impl_from_for_value {
+    ($($ty:ty => $variant:ident,)*) => {
+        $(
+            impl From<$ty> for ParcelValue {
+                fn from(value: $ty) -> Self {
+                    Self::$variant(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_value! {
+    String => String,
+    i32 => Int,
+    i64 => Long,
+    i16 => Short,
+    i8 => Byte,
+    f32 => Float,
+    f64 => Double,
+    bool => Boolean,
+    Vec<u8> => ByteArray,
+    Vec<i32> => IntArray,
+    Vec<i64> => LongArray,
+    Vec<f64> => DoubleArray,
+    Vec<bool> => BooleanArray,
+    Vec<Option<String>> => StringArray,
+    Vec<i16> => ShortArray,
+    Vec<u16> => CharArray,
+    Vec<f32> => FloatArray,
+    Bundle => Bundle,
+}
+
+impl From<&str> for ParcelValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
+fn write_len(parcel: &mut Parcel, len: usize) -> Result<()> {
+    let len = i32::try_from(len).map_err(|_| BinderError::BadValue)?;
+    parcel.write(&len)
+}
+
+/// Length of a container of `T`, a null one is read as empty.
+fn read_len<T>(parcel: &mut Parcel) -> Result<usize> {
+    let len: i32 = parcel.read()?;
+    if len < -1 {
+        error!("Bad container length: {len}");
+        return Err(BinderError::BadValue);
+    }
+    let len = len.max(0) as usize;
+    parcel.check_alloc(len, size_of::<T>())?;
+    Ok(len)
+}
+
+// TextUtils.writeToParcel: 1 for a plain string, then the string in UTF-8
+fn write_char_sequence(parcel: &mut Parcel, cs: Option<&str>) -> Result<()> {
+    parcel.write(&1i32)?;
+    parcel.write_string8(cs)
+}
+
+fn read_char_sequence(parcel: &mut Parcel) -> Result<Option<String>> {
+    let kind: i32 = parcel.read()?;
+    let string = parcel.read_string8()?;
+    if kind != 1 {
+        error!("Spanned CharSequence is not supported");
+        return Err(BinderError::BadType);
+    }
+    Ok(string)
+}
+
+/// Write the length of what `f` writes in front of it, the length itself
+/// excluded (unlike [`Parcel::sized_write`]).
+pub(crate) fn write_length_prefixed<F>(parcel: &mut Parcel, f: F) -> Result<()>
+where
+    F: FnOnce(&mut Parcel) -> Result<()>,
+{
+    let len_pos = parcel.data_position();
+    parcel.write(&-1i32)?;
+    let start = parcel.data_position();
+    f(parcel)?;
+
+    let end = parcel.data_position();
+    let len = i32::try_from(end - start).map_err(|_| BinderError::BadValue)?;
+    parcel.set_data_position(len_pos);
+    parcel.write(&len)?;
+    parcel.set_data_position(end);
+    Ok(())
+}
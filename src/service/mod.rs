@@ -1,72 +1,258 @@
+use std::os::fd::BorrowedFd;
+
+use nix::libc;
+
+pub use crate::binder::{
+    flat_object::BinderFlatObject,
+    transaction::{Transaction, TransactionFlag},
+    transaction_data::{BinderTransactionData, TargetUnion},
+};
 use crate::{
-    binder::{
-        command_protocol::BinderReturn, transaction::TransactionFlag,
-        transaction_data::BinderTransactionData,
-    },
+    binder::{clear_buf_if_sensitive, command_protocol::BinderReturn},
     error::*,
+    status::Status,
 };
 use service_manager::ServiceManager;
+use shell_command::ShellCommand;
 
-use crate::parcel::Parcel;
+use crate::parcel::{Parcel, ParcelLimits, pool::PooledParcel};
 
 pub mod service_listener;
 pub mod service_manager;
+pub mod shell_command;
+pub mod shutdown;
+
+/// Identity of the process which sent a transaction.
+#[derive(Debug, Clone, Copy)]
+pub struct CallingContext {
+    pub pid: libc::pid_t,
+    pub euid: libc::uid_t,
+}
+
+impl From<&BinderTransactionData> for CallingContext {
+    fn from(tx: &BinderTransactionData) -> Self {
+        Self {
+            pid: tx.sender_pid,
+            euid: tx.sender_euid,
+        }
+    }
+}
 
 pub trait BinderService {
-    fn progress_request(&self, code: u32, data: &mut Parcel) -> Parcel;
+    /// Called on user transactions (`FIRST_CALL_TRANSACTION..=LAST_CALL_TRANSACTION`).
+    ///
+    /// `data` is the transaction data, starting with the interface token.
+    /// The listener writes the reply status in front of `reply`, or replaces
+    /// `reply` by the returned exception. Nothing is sent for oneway calls.
+    fn progress_request(
+        &self,
+        code: u32,
+        data: &mut Parcel,
+        reply: &mut Parcel,
+        ctx: &CallingContext,
+        flags: TransactionFlag,
+    ) -> std::result::Result<(), Status>;
+
+    /// Called on `DUMP_TRANSACTION` (`dumpsys <service>`).
+    /// The service state should be written into `fd`.
+    fn dump(&self, _fd: BorrowedFd<'_>, _args: &[String]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called on `SHELL_COMMAND_TRANSACTION` (`cmd <service>`).
+    /// Returns the result code sent back through the caller's result receiver.
+    fn shell_command(&self, _cmd: &ShellCommand) -> Result<i32> {
+        Err(BinderError::InvalidOperation)
+    }
+
+    /// Extension binder returned on `EXTENSION_TRANSACTION`.
+    fn extension(&self) -> Option<BinderFlatObject> {
+        None
+    }
+
+    /// Limits applied when reading the data of the transactions received.
+    fn parcel_limits(&self) -> ParcelLimits {
+        ParcelLimits::default()
+    }
+}
+
+/// Call user transaction `code` of `service` with `data`, the reply
+/// starting with the status, or replaced by the exception returned.
+pub(crate) fn dispatch_request<BS: BinderService + ?Sized>(
+    service: &BS,
+    code: u32,
+    data: &mut Parcel,
+    reply: &mut Parcel,
+    ctx: &CallingContext,
+    flags: TransactionFlag,
+) -> Result<()> {
+    // the reply of sensitive data is as sensitive
+    if data.is_sensitive() {
+        reply.mark_sensitive();
+    }
+    reply.write(&Status::ok())?;
+
+    if let Err(status) = service.progress_request(code, data, reply, ctx, flags) {
+        if let Some(e) = status.transaction_error() {
+            return Err(BinderError::from(e));
+        }
+
+        warn!("[Service] Transaction {code:#X} failed: {status}");
+        *reply = Parcel::new();
+        if data.is_sensitive() {
+            reply.mark_sensitive();
+        }
+        reply.write(&status)?;
+    }
+    Ok(())
 }
 
 pub struct Service<'a> {
-    mgr: &'a ServiceManager,
-    // handle from resolve service interface
-    handle: u32,
+    target: Target<'a>,
     interface_name: &'a str,
 }
 
+enum Target<'a> {
+    Remote {
+        mgr: &'a ServiceManager,
+        // handle from resolve service interface
+        handle: u32,
+    },
+    /// A service of this process, called without going through the driver.
+    Local(&'a dyn BinderService),
+}
+
 impl<'a> Service<'a> {
     pub fn new(mgr: &'a ServiceManager, interface_name: &'a str, handle: u32) -> Self {
         Self {
-            mgr,
-            handle,
+            target: Target::Remote { mgr, handle },
+            interface_name,
+        }
+    }
+
+    /// A client of `service`, living in this process. Transactions are
+    /// given to it directly, with a copy of the data as the driver would.
+    pub fn local(service: &'a dyn BinderService, interface_name: &'a str) -> Self {
+        Self {
+            target: Target::Local(service),
             interface_name,
         }
     }
 
-    pub fn call(&self, function_idx: u32, data: &mut Parcel) -> Result<()> {
+    pub fn interface_name(&self) -> &str {
+        self.interface_name
+    }
+
+    /// New transaction data, starting with the interface token.
+    pub fn prepare_transaction(&self) -> Result<Parcel<'static>> {
         let mut parcel = Parcel::new();
         parcel.write_interface_token(self.interface_name)?;
-        if !data.is_empty() {
-            parcel.append_all_from(data)?;
-        }
+        Ok(parcel)
+    }
+
+    /// Send `data` (see [`prepare_transaction`](Self::prepare_transaction))
+    /// and wait for the reply.
+    ///
+    /// The reply is copied out of the binder buffer, positioned at its start.
+    /// A oneway transaction returns an empty parcel once the driver took it.
+    pub fn transact(
+        &self,
+        code: u32,
+        data: &mut Parcel,
+        flags: TransactionFlag,
+    ) -> Result<Parcel<'static>> {
+        let (mgr, handle) = match self.target {
+            Target::Remote { mgr, handle } => (mgr, handle),
+            Target::Local(service) => return Self::transact_local(service, code, data, flags),
+        };
+        let oneway = flags.contains(TransactionFlag::OneWay);
+        let mut reply = Parcel::new();
 
-        // we transaction request
-        // so we expect service reply
-        self.mgr.binder().transaction_with_parse(
-            self.handle,
-            function_idx,
-            TransactionFlag::AcceptFds | TransactionFlag::CollectNotedAppOps,
-            &mut parcel,
-            |_binder, cmd, in_parcel| {
-                if matches!(cmd, BinderReturn::Reply) {
+        mgr.binder().transaction_with_parse(
+            handle,
+            code,
+            flags | TransactionFlag::AcceptFds,
+            data,
+            |binder, cmd, in_parcel| match cmd {
+                BinderReturn::TransactionComplete if oneway => Ok(true),
+                BinderReturn::Reply => {
                     let tx = in_parcel.read::<BinderTransactionData>()?;
-                    info!("Transaction data: \n{tx:#?}");
-                    let mut parcel = tx.to_parcel(None);
+                    // SAFETY: the reply was just read from `binder`.
+                    let mut parcel = unsafe { tx.to_parcel(binder, None)? };
 
-                    let status = parcel.read::<u32>()?;
-                    if status != 0 {
-                        panic!("Service call failed: {parcel:#?}");
+                    if tx.flags.contains(TransactionFlag::StatusCode) {
+                        let status = parcel.read::<i32>()?;
+                        error!("Transaction {code:#X} failed with status: {status}");
+                        return Err(BinderError::from(status));
                     }
 
-                    info!("FlatObject in Parcel: \n{parcel:#?}");
-                    info!("Parsing flat object");
-                    let obj = parcel.read_object(false)?;
-                    info!("FlatObject: \n{obj:#?}");
-                    info!("Parsing ok");
+                    reply.append_all_from(&mut parcel)?;
+                    Ok(true)
                 }
-                Ok(false)
+                BinderReturn::DeadReply => Err(BinderError::DeadObject),
+                BinderReturn::FailedReply => Err(BinderError::FailedTransaction),
+                _ => Ok(false),
             },
         )?;
 
-        Ok(())
+        reply.set_data_position(0);
+        Ok(reply)
+    }
+
+    fn transact_local(
+        service: &dyn BinderService,
+        code: u32,
+        data: &mut Parcel,
+        flags: TransactionFlag,
+    ) -> Result<Parcel<'static>> {
+        let mut request = Parcel::new();
+        request.set_limits(service.parcel_limits());
+        request.append_all_from(data)?;
+        request.set_data_position(0);
+
+        let ctx = CallingContext {
+            pid: std::process::id() as _,
+            // SAFETY: geteuid can't fail.
+            euid: unsafe { libc::geteuid() },
+        };
+        // as the driver would deliver it
+        let flags = clear_buf_if_sensitive(flags, &request);
+        let mut reply = Parcel::new();
+        dispatch_request(service, code, &mut request, &mut reply, &ctx, flags)?;
+
+        if flags.contains(TransactionFlag::OneWay) {
+            return Ok(Parcel::new());
+        }
+        reply.set_data_position(0);
+        Ok(reply)
+    }
+
+    /// Call method `code` with the arguments in `data`, the interface
+    /// token is written in front of them.
+    ///
+    /// Returns the reply positioned after its status header, or the
+    /// exception thrown by the service. Oneway calls return an empty parcel.
+    pub fn call(
+        &self,
+        code: u32,
+        data: &mut Parcel,
+        flags: TransactionFlag,
+    ) -> std::result::Result<Parcel<'static>, Status> {
+        let mut parcel = PooledParcel::default();
+        parcel.write_interface_token(self.interface_name)?;
+        // appends all of `data`, wherever its position is
+        parcel.append_all_from(data)?;
+
+        let mut reply = self.transact(code, &mut parcel, flags)?;
+        if flags.contains(TransactionFlag::OneWay) {
+            return Ok(reply);
+        }
+
+        let status: Status = reply.read()?;
+        if !status.is_ok() {
+            error!("Service call {code:#X} failed: {status}");
+            return Err(status);
+        }
+        Ok(reply)
     }
 }
@@ -1,41 +1,66 @@
+use std::{
+    os::fd::AsFd,
+    panic::{self, AssertUnwindSafe},
+};
+
+use nix::{
+    errno::Errno,
+    poll::{PollFd, PollFlags, PollTimeout, poll},
+};
 use num_traits::FromPrimitive;
 
-use super::{BinderService, service_manager::ServiceManager};
+use super::{
+    BinderService, CallingContext, dispatch_request,
+    service_manager::ServiceManager,
+    shell_command::{ShellCommand, read_args},
+    shutdown::ShutdownHandle,
+};
 use crate::{
     binder::{
+        Binder,
         command_protocol::BinderReturn,
         transaction::{Transaction, TransactionFlag},
         transaction_data::BinderTransactionData,
     },
     error::*,
-    parcel::Parcel,
+    parcel::{Parcel, pool::PooledParcel},
 };
 
 pub struct ServiceListener<'a, BS: BinderService> {
     service_delegate: &'a BS,
     mgr: &'a ServiceManager,
     interface_name: &'a str,
+    shutdown: ShutdownHandle,
 }
 
 impl<'a, BS: BinderService> ServiceListener<'a, BS> {
-    pub fn new(service_delegate: &'a BS, mgr: &'a ServiceManager, interface_name: &'a str) -> Self {
-        Self {
+    pub fn new(
+        service_delegate: &'a BS,
+        mgr: &'a ServiceManager,
+        interface_name: &'a str,
+    ) -> Result<Self> {
+        Ok(Self {
             service_delegate,
             mgr,
             interface_name,
-        }
+            shutdown: ShutdownHandle::new()?,
+        })
     }
 
+    /// Handle used to stop [`binder_loop`](Self::binder_loop) from another thread.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Serve transactions until [`ShutdownHandle::shutdown`] is called.
     pub fn binder_loop(&self) -> Result<()> {
         // self.mgr.binder().enter_loop()?;
         let mut in_parcel = Parcel::default();
-        let mut out_parcel = Parcel::default();
 
         info!("\n\n\n[BinderLoop] Enter\n\n\n");
 
-        loop {
+        while self.wait_for_work()? {
             info!("Read Read");
-            // self.mgr.binder().binder_write(&mut out_parcel)?;
             self.mgr.binder().binder_read(&mut in_parcel)?;
 
             // waiting for transaction request
@@ -43,59 +68,164 @@ impl<'a, BS: BinderService> ServiceListener<'a, BS> {
             self.mgr
                 .binder()
                 .binder_parse(&mut in_parcel, |binder, cmd, in_parcel| {
-                    match cmd {
-                        BinderReturn::Transaction => {
-                            let tx = in_parcel.read::<BinderTransactionData>()?;
-                            info!("[BinderLoop] Transaction data: \n{tx:#?}");
-                            let mut parcel = tx.to_parcel(None);
-
-                            let status = parcel.read::<u32>()?;
-
-                            info!("[BinderLoop] Transaction status: {status}");
-
-                            info!("[BinderLoop] FlatObject in Parcel: \n{parcel:#?}");
-                            let obj = parcel.read_object(false)?;
-                            info!("[BinderLoop] FlatObject: \n{obj:#?}");
-
-                            let transaction_code = Transaction::from_u32(tx.code);
-                            if let Some(transaction_code) = transaction_code {
-                                info!("[BinderLoop] We recieved transaction code: {transaction_code:?}");
-                                match transaction_code {
-                                    Transaction::Interface => {
-                                        out_parcel.write(&0u32)?;
-                                        out_parcel.write(self.interface_name)?;
-                                        binder.reply(
-                                            &mut out_parcel,
-                                            tx.flags | TransactionFlag::AcceptFds,
-                                        )?;
-                                        return Ok(true);
-                                    }
-                                    _ => {
-                                        warn!("[BinderLoop] Unhandled transaction code.");
-                                    }
-                                }
-                            }
-
-                            // calling resolver
-                            if tx.code >= Transaction::FirstCall.into()
-                                && tx.code <= Transaction::LastCall.into()
-                            {
-                                info!("[BinderLoop] Progress RPC...");
-                                // let interface = parcel.write_interface_token(interface)
-                                binder.reply(
-                                    &mut self.service_delegate.progress_request(tx.code, in_parcel),
-                                    tx.flags,
-                                )?;
-                                return Ok(true);
-                            }
-                        }
-                        _ => {}
+                    if matches!(cmd, BinderReturn::Transaction) {
+                        let tx = in_parcel.read::<BinderTransactionData>()?;
+                        info!("[BinderLoop] Transaction data: \n{tx:#?}");
+                        return self.on_transaction(binder, &tx);
                     }
-                    //
                     Ok(false)
                 })?;
         }
 
-        // self.mgr.binder().exit_loop()?;
+        info!("[BinderLoop] Shutdown");
+        self.drain(&mut in_parcel)?;
+        self.mgr.binder().exit_loop()
+    }
+
+    /// Block until the driver has work for us.
+    /// Returns `false` once shutdown is requested.
+    fn wait_for_work(&self) -> Result<bool> {
+        let mut fds = [
+            PollFd::new(self.mgr.binder().as_fd(), PollFlags::POLLIN),
+            PollFd::new(self.shutdown.as_fd(), PollFlags::POLLIN),
+        ];
+
+        loop {
+            match poll(&mut fds, PollTimeout::NONE) {
+                Ok(_) => return Ok(!self.shutdown.is_shutdown()),
+                Err(Errno::EINTR) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Fail the transactions already queued for us, so their callers don't hang.
+    fn drain(&self, in_parcel: &mut Parcel) -> Result<()> {
+        let binder = self.mgr.binder();
+
+        loop {
+            let mut fds = [PollFd::new(binder.as_fd(), PollFlags::POLLIN)];
+            if poll(&mut fds, PollTimeout::ZERO)? == 0 {
+                return Ok(());
+            }
+
+            binder.binder_read(in_parcel)?;
+            binder.binder_parse(in_parcel, |binder, cmd, in_parcel| {
+                if matches!(cmd, BinderReturn::Transaction) {
+                    let tx = in_parcel.read::<BinderTransactionData>()?;
+                    warn!("[BinderLoop] Drop transaction {:#X} on shutdown", tx.code);
+                    if !tx.flags.contains(TransactionFlag::OneWay) {
+                        binder.reply_status(i32::from(&BinderError::DeadObject))?;
+                    }
+                    return Ok(true);
+                }
+                Ok(false)
+            })?;
+        }
+    }
+
+    fn on_transaction(&self, binder: &Binder, tx: &BinderTransactionData) -> Result<bool> {
+        // a panicking handler must not take down the binder thread
+        // and leave the caller waiting for a reply that never comes
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.dispatch(binder, tx)))
+            .unwrap_or_else(|panic| {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("unknown");
+                error!(
+                    "[BinderLoop] Transaction {:#X} panicked: {message}",
+                    tx.code
+                );
+                Err(BinderError::FailedTransaction)
+            });
+
+        if tx.flags.contains(TransactionFlag::OneWay) {
+            if let Err(e) = result {
+                error!("[BinderLoop] Oneway transaction {:#X} failed: {e}", tx.code);
+            }
+            return Ok(true);
+        }
+
+        match result {
+            Ok(mut reply) => binder.reply(&mut reply, tx.flags | TransactionFlag::AcceptFds)?,
+            Err(e) => {
+                error!("[BinderLoop] Transaction {:#X} failed: {e}", tx.code);
+                binder.reply_status(i32::from(&e))?;
+            }
+        }
+        Ok(true)
+    }
+
+    fn dispatch(&self, binder: &Binder, tx: &BinderTransactionData) -> Result<PooledParcel> {
+        // SAFETY: the transaction was just read from `binder`.
+        let mut data = unsafe { tx.to_parcel(binder, None)? };
+        data.set_limits(self.service_delegate.parcel_limits());
+        let mut reply = PooledParcel::default();
+
+        let transaction_code = Transaction::from_u32(tx.code);
+        info!("[BinderLoop] We recieved transaction code: {transaction_code:?}");
+
+        match transaction_code {
+            Some(Transaction::Interface) => {
+                reply.write(self.interface_name)?;
+            }
+            Some(Transaction::Ping) => {}
+            Some(Transaction::Dump) => self.dump(&mut data)?,
+            Some(Transaction::ShellCommand) => self.shell_command(binder, &mut data)?,
+            Some(Transaction::Sysprops) => crate::report_sysprop_change(),
+            Some(Transaction::DebugPid) => {
+                reply.write(&(std::process::id() as i32))?;
+            }
+            Some(Transaction::Extension) => {
+                reply.write_strong_binder(self.service_delegate.extension().as_ref())?;
+            }
+            // calling resolver
+            _ if tx.code >= Transaction::FirstCall.into()
+                && tx.code <= Transaction::LastCall.into() =>
+            {
+                info!("[BinderLoop] Progress RPC...");
+                self.progress_request(tx, &mut data, &mut reply)?;
+            }
+            _ => {
+                warn!("[BinderLoop] Unhandled transaction code: {:#X}", tx.code);
+                return Err(BinderError::UnknownTransaction);
+            }
+        }
+
+        Ok(reply)
+    }
+
+    fn progress_request(
+        &self,
+        tx: &BinderTransactionData,
+        data: &mut Parcel,
+        reply: &mut Parcel,
+    ) -> Result<()> {
+        let ctx = CallingContext::from(tx);
+        dispatch_request(self.service_delegate, tx.code, data, reply, &ctx, tx.flags)
+    }
+
+    fn dump(&self, data: &mut Parcel) -> Result<()> {
+        let fd = data.read_file_descriptor()?;
+        let args = read_args(data)?;
+
+        info!("[BinderLoop] Dump: {args:?}");
+        self.service_delegate.dump(fd.as_fd(), &args)
+    }
+
+    fn shell_command(&self, binder: &Binder, data: &mut Parcel) -> Result<()> {
+        let cmd = ShellCommand::read_from_parcel(data)?;
+
+        info!("[BinderLoop] ShellCommand: {:?}", cmd.args);
+        let code = self
+            .service_delegate
+            .shell_command(&cmd)
+            .unwrap_or_else(|e| {
+                error!("[BinderLoop] ShellCommand failed: {e}");
+                i32::from(&e)
+            });
+        cmd.send_result(binder, code)
     }
 }
@@ -68,19 +68,20 @@ impl ServiceManager {
             ServiceManagerFunctions::GetService as _,
             TransactionFlag::empty(),
             &mut parcel,
-            |_, br, d| {
+            |binder, br, d| {
                 if matches!(br, BinderReturn::Reply) {
                     let transacion_data = d.read::<BinderTransactionData>()?;
                     info!("[GetService] Transaction data: \n{transacion_data:#?}");
-                    let mut parcel = transacion_data.to_parcel(None);
+                    // SAFETY: the reply was just read from `binder`.
+                    let mut parcel = unsafe { transacion_data.to_parcel(binder, None)? };
 
-                    if !parcel.can_read::<u32>() {
+                    let status = parcel.peek::<u32>()?;
+                    info!("[GetService] [Status] {status}");
+                    parcel.skip::<u32>()?;
+                    if !parcel.has_unread_data() {
                         return Ok(true);
                     }
 
-                    let status = parcel.read::<u32>()?;
-                    info!("[GetService] [Status] {status}");
-
                     info!("[GetService] FlatObject in Parcel: \n{parcel:#?}");
                     let obj = parcel.read_object(false)?;
                     handle = Some(obj.handle());
@@ -128,11 +129,12 @@ impl ServiceManager {
             ServiceManagerFunctions::AddService as _,
             TransactionFlag::empty(),
             &mut parcel,
-            |_binder, c, p| {
+            |binder, c, p| {
                 if matches!(c, BinderReturn::Reply) {
                     let transacion_data = p.read::<BinderTransactionData>()?;
                     info!("[AddService] Transaction data: \n{transacion_data:#?}");
-                    let parcel = transacion_data.to_parcel(None);
+                    // SAFETY: the reply was just read from `binder`.
+                    let parcel = unsafe { transacion_data.to_parcel(binder, None)? };
                     info!("[AddService] Parcel: {parcel:#?}");
                     // we just extract this
                     // no data require for this now
@@ -142,7 +144,7 @@ impl ServiceManager {
             },
         )?;
 
-        Ok(ServiceListener::new(service_delegate, self, interface_name))
+        ServiceListener::new(service_delegate, self, interface_name)
     }
 
     pub fn binder(&self) -> &Binder {
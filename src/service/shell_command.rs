@@ -0,0 +1,78 @@
+use std::os::fd::OwnedFd;
+
+use crate::{
+    binder::{
+        Binder,
+        flat_object::BinderFlatObject,
+        transaction::{Transaction, TransactionFlag},
+    },
+    error::*,
+    parcel::{Parcel, bundle::Bundle},
+};
+
+const RESULT_RECEIVER_INTERFACE_TOKEN: &str = "com.android.internal.os.IResultReceiver";
+
+/// A `cmd <service> ...` invocation received through `SHELL_COMMAND_TRANSACTION`.
+pub struct ShellCommand {
+    pub in_fd: OwnedFd,
+    pub out_fd: OwnedFd,
+    pub err_fd: OwnedFd,
+    pub args: Vec<String>,
+    /// `android.os.IShellCallback` of the caller, used to open files on its side.
+    pub shell_callback: Option<BinderFlatObject>,
+    result_receiver: Option<BinderFlatObject>,
+}
+
+impl ShellCommand {
+    pub(crate) fn read_from_parcel(data: &mut Parcel) -> Result<Self> {
+        let in_fd = data.read_file_descriptor()?;
+        let out_fd = data.read_file_descriptor()?;
+        let err_fd = data.read_file_descriptor()?;
+        let args = read_args(data)?;
+        let shell_callback = data.read_strong_binder()?;
+        let result_receiver = data.read_strong_binder()?;
+
+        Ok(Self {
+            in_fd,
+            out_fd,
+            err_fd,
+            args,
+            shell_callback,
+            result_receiver,
+        })
+    }
+
+    /// Send the result code back to the caller through `IResultReceiver::send`.
+    pub(crate) fn send_result(&self, binder: &Binder, code: i32) -> Result<()> {
+        let Some(receiver) = &self.result_receiver else {
+            warn!("[ShellCommand] No result receiver for result: {code}");
+            return Ok(());
+        };
+
+        let mut parcel = Parcel::new();
+        parcel.write_interface_token(RESULT_RECEIVER_INTERFACE_TOKEN)?;
+        parcel.write(&code)?;
+        parcel.write(&None::<Bundle>)?;
+
+        // oneway void send(int resultCode, in Bundle resultData);
+        binder.transaction(
+            receiver.handle(),
+            Transaction::FirstCall.into(),
+            TransactionFlag::OneWay,
+            &mut parcel,
+        )
+    }
+}
+
+/// Read the `argc` + `String16[argc]` argument list used by dump and shell commands.
+pub(crate) fn read_args(data: &mut Parcel) -> Result<Vec<String>> {
+    let argc = data.read::<i32>()?;
+    let mut args = Vec::new();
+    for _ in 0..argc {
+        if !data.has_unread_data() {
+            break;
+        }
+        args.push(data.read::<String>()?);
+    }
+    Ok(args)
+}
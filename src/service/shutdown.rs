@@ -0,0 +1,62 @@
+use std::{
+    os::fd::{AsFd, BorrowedFd},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use nix::sys::eventfd::{EfdFlags, EventFd};
+
+use crate::error::*;
+
+/// Stops a [`ServiceListener`](super::service_listener::ServiceListener)
+/// loop, possibly from another thread.
+///
+/// The handle can be cloned and outlive the listener.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    inner: Arc<ShutdownState>,
+}
+
+struct ShutdownState {
+    requested: AtomicBool,
+    // wakes up the listener blocked in poll()
+    event: EventFd,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new() -> Result<Self> {
+        let event =
+            EventFd::from_value_and_flags(0, EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK)?;
+
+        Ok(Self {
+            inner: Arc::new(ShutdownState {
+                requested: AtomicBool::new(false),
+                event,
+            }),
+        })
+    }
+
+    /// Ask the listener to stop. It will fail the pending transactions,
+    /// leave the looper and return from `binder_loop`.
+    pub fn shutdown(&self) -> Result<()> {
+        if self.inner.requested.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        info!("[Shutdown] Requested");
+        self.inner.event.write(1)?;
+        Ok(())
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.inner.requested.load(Ordering::SeqCst)
+    }
+}
+
+impl AsFd for ShutdownHandle {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.event.as_fd()
+    }
+}
@@ -0,0 +1,210 @@
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+
+use crate::{
+    error::{BinderError, Result},
+    parcel::{
+        Parcel,
+        parcelable::{Deserialize, Serialize},
+    },
+};
+
+/// Exception codes of the AIDL reply header.
+///
+/// http://aospxref.com/android-14.0.0_r2/xref/frameworks/native/libs/binder/include/binder/Status.h
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+pub enum ExceptionCode {
+    None = 0,
+    Security = -1,
+    BadParcelable = -2,
+    IllegalArgument = -3,
+    NullPointer = -4,
+    IllegalState = -5,
+    NetworkMainThread = -6,
+    UnsupportedOperation = -7,
+    ServiceSpecific = -8,
+    Parcelable = -9,
+    /// The reply header is followed by a fat reply header we skip.
+    HasReplyHeader = -128,
+    /// Never written to the wire: the transaction failed with a `status_t`.
+    TransactionFailed = -129,
+}
+
+/// Result of an AIDL call: success, an exception, or a transport error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    exception: ExceptionCode,
+    /// `status_t` for [`ExceptionCode::TransactionFailed`], or the service
+    /// specific error code for [`ExceptionCode::ServiceSpecific`].
+    error_code: i32,
+    message: Option<String>,
+}
+
+impl Status {
+    pub fn ok() -> Self {
+        Self {
+            exception: ExceptionCode::None,
+            error_code: 0,
+            message: None,
+        }
+    }
+
+    pub fn new_exception(exception: ExceptionCode, message: Option<&str>) -> Self {
+        Self {
+            exception,
+            error_code: 0,
+            message: message.map(str::to_owned),
+        }
+    }
+
+    pub fn new_service_specific_error(error_code: i32, message: Option<&str>) -> Self {
+        Self {
+            exception: ExceptionCode::ServiceSpecific,
+            error_code,
+            message: message.map(str::to_owned),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.exception == ExceptionCode::None
+    }
+
+    pub fn exception_code(&self) -> ExceptionCode {
+        self.exception
+    }
+
+    /// The `status_t` of a failed transaction.
+    pub fn transaction_error(&self) -> Option<i32> {
+        (self.exception == ExceptionCode::TransactionFailed).then_some(self.error_code)
+    }
+
+    pub fn service_specific_error(&self) -> Option<i32> {
+        (self.exception == ExceptionCode::ServiceSpecific).then_some(self.error_code)
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Status::ok()
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.exception {
+            ExceptionCode::None => write!(f, "No error"),
+            ExceptionCode::TransactionFailed => {
+                write!(f, "Transaction failed: {}", self.error_code)
+            }
+            ExceptionCode::ServiceSpecific => write!(
+                f,
+                "Service specific error {}: {}",
+                self.error_code,
+                self.message().unwrap_or_default()
+            ),
+            exception => write!(f, "{exception:?}: {}", self.message().unwrap_or_default()),
+        }
+    }
+}
+
+impl std::error::Error for Status {}
+
+impl From<BinderError> for Status {
+    fn from(error: BinderError) -> Self {
+        Self {
+            exception: ExceptionCode::TransactionFailed,
+            error_code: i32::from(&error),
+            message: Some(error.to_string()),
+        }
+    }
+}
+
+impl From<ExceptionCode> for Status {
+    fn from(exception: ExceptionCode) -> Self {
+        Status::new_exception(exception, None)
+    }
+}
+
+// Status::writeToParcel
+impl Serialize for Status {
+    fn serialize(&self, parcel: &mut Parcel) -> Result<()> {
+        if self.exception == ExceptionCode::TransactionFailed {
+            // only sent as a status code reply
+            return Err(BinderError::from(self.error_code));
+        }
+
+        parcel.write(&(self.exception as i32))?;
+        if self.exception == ExceptionCode::None {
+            return Ok(());
+        }
+
+        parcel.write(&self.message)?;
+        // empty remote stack trace header
+        parcel.write(&0i32)?;
+        match self.exception {
+            ExceptionCode::ServiceSpecific => parcel.write(&self.error_code),
+            // empty parcelable
+            ExceptionCode::Parcelable => parcel.write(&0i32),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Status::readFromParcel
+impl Deserialize for Status {
+    fn deserialize(parcel: &mut Parcel) -> Result<Self> {
+        let mut exception = parcel.read::<i32>()?;
+
+        if exception == ExceptionCode::HasReplyHeader as i32 {
+            skip_header(parcel)?;
+            // fat reply headers are only used for successful calls
+            exception = ExceptionCode::None as i32;
+        }
+
+        let Some(exception) = ExceptionCode::from_i32(exception) else {
+            error!("Status: unknown exception code: {exception}");
+            return Err(BinderError::BadValue);
+        };
+        if exception == ExceptionCode::None {
+            return Ok(Status::ok());
+        }
+
+        let message = parcel.read::<Option<String>>()?;
+        // remote stack trace
+        skip_header(parcel)?;
+
+        let error_code = match exception {
+            ExceptionCode::ServiceSpecific => parcel.read::<i32>()?,
+            ExceptionCode::Parcelable => {
+                skip_header(parcel)?;
+                0
+            }
+            _ => 0,
+        };
+
+        Ok(Self {
+            exception,
+            error_code,
+            message,
+        })
+    }
+}
+
+/// Skip a block prefixed with its size, the size field included.
+fn skip_header(parcel: &mut Parcel) -> Result<()> {
+    let start = parcel.data_position();
+    let size = parcel.read::<i32>()?;
+    if size < 0 || size as usize > parcel.data_avail() + size_of::<i32>() {
+        error!("Status: bad header size: {size}");
+        return Err(BinderError::BadValue);
+    }
+    if size as usize > size_of::<i32>() {
+        parcel.set_data_position(start + size as usize);
+    }
+    Ok(())
+}
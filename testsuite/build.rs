@@ -0,0 +1,8 @@
+fn main() {
+    binder_rs_aidl::Builder::new()
+        .source("aidl/android/test/ITest.aidl")
+        .source("aidl/android/test/Cell.aidl")
+        .include_dir("aidl")
+        .generate()
+        .unwrap();
+}
@@ -0,0 +1,19 @@
+//! Code generated from the `.aidl` files of the tests, and helpers to
+//! compare parcels with the words written by AOSP.
+
+use binder_rs::parcel::Parcel;
+
+include!(concat!(env!("OUT_DIR"), "/aidl.rs"));
+
+/// The data of `parcel` as 32-bit words.
+pub fn words(parcel: &mut Parcel) -> Vec<i32> {
+    parcel.set_data_position(0);
+    (0..parcel.data_size() / 4)
+        .map(|_| parcel.read().unwrap())
+        .collect()
+}
+
+/// A parcel holding `words`, to be read.
+pub fn parcel_of(words: &[i32]) -> Parcel<'static> {
+    Parcel::from_vec(words.iter().flat_map(|w| w.to_le_bytes()).collect())
+}
@@ -0,0 +1,256 @@
+//! Calls through the generated client `BpTest` to the generated server
+//! `BnTest`, over a local service.
+
+use std::{cell::RefCell, collections::HashMap, sync::Mutex};
+
+use binder_rs::{
+    parcel::Parcel,
+    service::{BinderService, CallingContext, Service, TransactionFlag},
+    status::{ExceptionCode, Status},
+};
+use binder_rs_testsuite::android::test::{
+    Color::Color,
+    ITest::{self as itest, BnTest, BpTest, ITest},
+    Point::{self as point, Point},
+    Shape::Shape,
+};
+
+#[derive(Default)]
+struct TestService {
+    notified: Mutex<Vec<String>>,
+}
+
+impl ITest for TestService {
+    fn add(&self, a: i32, b: i32) -> Result<i32, Status> {
+        a.checked_add(b)
+            .ok_or_else(|| Status::new_exception(ExceptionCode::IllegalArgument, Some("overflow")))
+    }
+
+    fn echo(&self, s: Option<&str>) -> Result<Option<String>, Status> {
+        Ok(s.map(str::to_owned))
+    }
+
+    fn r#move(&self, p: &Point, dx: i32) -> Result<Point, Status> {
+        Ok(Point {
+            x: p.x + dx,
+            ..p.clone()
+        })
+    }
+
+    fn grow(&self, shape: &Shape) -> Result<Shape, Status> {
+        Ok(match shape {
+            Shape::Radius(r) => Shape::Radius(r * 2),
+            other => other.clone(),
+        })
+    }
+
+    fn next(&self, color: Color) -> Result<Color, Status> {
+        Ok(Color(color.0 + 1))
+    }
+
+    fn fill(&self, values: &mut Vec<i32>) -> Result<(), Status> {
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = i as i32 * 10;
+        }
+        Ok(())
+    }
+
+    fn fill_nullable(&self, values: &mut Option<Vec<i32>>) -> Result<(), Status> {
+        assert!(values.is_none());
+        *values = Some(vec![7]);
+        Ok(())
+    }
+
+    fn fill_list(&self, items: &mut Vec<String>) -> Result<(), Status> {
+        assert!(items.is_empty());
+        items.extend(["a".to_owned(), "b".to_owned()]);
+        Ok(())
+    }
+
+    fn fill_fixed(&self, values: &mut [i32; 3]) -> Result<(), Status> {
+        assert_eq!(*values, [0; 3]);
+        *values = [1, 2, 3];
+        Ok(())
+    }
+
+    fn bump(&self, p: &mut Point, values: &mut Vec<i32>) -> Result<(), Status> {
+        p.y += 1;
+        values.push(p.y);
+        Ok(())
+    }
+
+    fn notify(&self, message: &str) -> Result<(), Status> {
+        self.notified.lock().unwrap().push(message.to_owned());
+        Ok(())
+    }
+
+    fn reverse(&self, items: &[String]) -> Result<Vec<String>, Status> {
+        Ok(items.iter().rev().cloned().collect())
+    }
+
+    fn shift(&self, items: &[Option<String>]) -> Result<Vec<Option<String>>, Status> {
+        Ok(std::iter::once(None).chain(items.iter().cloned()).collect())
+    }
+
+    fn nulls(
+        &self,
+        map: &HashMap<String, Option<String>>,
+    ) -> Result<HashMap<String, Option<String>>, Status> {
+        Ok(map
+            .iter()
+            .filter(|(_, value)| value.is_none())
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+/// Keeps the size of the arguments of the last transaction.
+struct Spy<S> {
+    inner: S,
+    args_size: RefCell<Option<usize>>,
+}
+
+impl<S: BinderService> BinderService for Spy<S> {
+    fn progress_request(
+        &self,
+        code: u32,
+        data: &mut Parcel,
+        reply: &mut Parcel,
+        ctx: &CallingContext,
+        flags: TransactionFlag,
+    ) -> Result<(), Status> {
+        data.enforce_interface(itest::DESCRIPTOR)?;
+        *self.args_size.borrow_mut() = Some(data.data_size() - data.data_position());
+        data.set_data_position(0);
+        self.inner.progress_request(code, data, reply, ctx, flags)
+    }
+}
+
+fn spy() -> Spy<BnTest<TestService>> {
+    Spy {
+        inner: BnTest(TestService::default()),
+        args_size: RefCell::new(None),
+    }
+}
+
+#[test]
+fn parcelable_defaults() {
+    let p = Point::default();
+    assert_eq!(p.x, point::ORIGIN);
+    assert_eq!(p.y, 6);
+    assert_eq!(p.label, None);
+    assert_eq!(p.color, Color::GREEN);
+    assert_eq!(p.weights, vec![1.0, 2.5]);
+    assert_eq!(p.name, "origin");
+
+    assert_eq!(Shape::default(), Shape::Radius(4));
+    assert_eq!(Color::default(), Color(0));
+    assert_eq!(
+        (Color::RED, Color::GREEN, Color::BLUE),
+        (Color(0), Color(3), Color(4))
+    );
+}
+
+#[test]
+fn in_and_return() {
+    let server = spy();
+    let client = BpTest::new(Service::local(&server, itest::DESCRIPTOR));
+
+    assert_eq!(client.add(3, 4).unwrap(), 7);
+    let status = client.add(i32::MAX, 1).unwrap_err();
+    assert_eq!(status.exception_code(), ExceptionCode::IllegalArgument);
+    assert_eq!(status.message(), Some("overflow"));
+
+    assert_eq!(client.echo(Some("hi")).unwrap().as_deref(), Some("hi"));
+    assert_eq!(client.echo(None).unwrap(), None);
+
+    let p = Point {
+        label: Some("p".to_owned()),
+        ..Point::default()
+    };
+    let moved = client.r#move(&p, 5).unwrap();
+    assert_eq!(moved.x, p.x + 5);
+    assert_eq!(moved.label, p.label);
+    assert_eq!(moved.weights, p.weights);
+
+    assert_eq!(client.grow(&Shape::Radius(3)).unwrap(), Shape::Radius(6));
+    assert_eq!(
+        client.grow(&Shape::Rect(p.clone())).unwrap(),
+        Shape::Rect(p)
+    );
+    assert_eq!(client.next(Color::GREEN).unwrap(), Color::BLUE);
+
+    let items = ["a".to_owned(), "b".to_owned(), "c".to_owned()];
+    assert_eq!(client.reverse(&items).unwrap(), ["c", "b", "a"]);
+}
+
+#[test]
+fn nullable_elements() {
+    let server = spy();
+    let client = BpTest::new(Service::local(&server, itest::DESCRIPTOR));
+
+    let items = [Some("a".to_owned()), None];
+    assert_eq!(
+        client.shift(&items).unwrap(),
+        [None, Some("a".to_owned()), None]
+    );
+
+    let map = HashMap::from([
+        ("a".to_owned(), Some("1".to_owned())),
+        ("b".to_owned(), None),
+    ]);
+    assert_eq!(
+        client.nulls(&map).unwrap(),
+        HashMap::from([("b".to_owned(), None)])
+    );
+}
+
+#[test]
+fn out_and_inout() {
+    let server = spy();
+    let client = BpTest::new(Service::local(&server, itest::DESCRIPTOR));
+
+    // only the size of a dynamic array is sent
+    let mut values = vec![0; 3];
+    client.fill(&mut values).unwrap();
+    assert_eq!(values, [0, 10, 20]);
+    assert_eq!(server.args_size.take(), Some(4));
+
+    let mut values = None;
+    client.fill_nullable(&mut values).unwrap();
+    assert_eq!(values, Some(vec![7]));
+    assert_eq!(server.args_size.take(), Some(4));
+
+    let mut items = vec!["old".to_owned()];
+    client.fill_list(&mut items).unwrap();
+    assert_eq!(items, ["a", "b"]);
+    assert_eq!(server.args_size.take(), Some(0));
+
+    let mut fixed = [9; 3];
+    client.fill_fixed(&mut fixed).unwrap();
+    assert_eq!(fixed, [1, 2, 3]);
+    assert_eq!(server.args_size.take(), Some(0));
+
+    let mut p = Point::default();
+    let mut values = vec![1];
+    client.bump(&mut p, &mut values).unwrap();
+    assert_eq!(p.y, 7);
+    assert_eq!(values, [1, 7]);
+}
+
+#[test]
+fn oneway() {
+    let server = spy();
+    let client = BpTest::new(Service::local(&server, itest::DESCRIPTOR));
+
+    client.notify("hello").unwrap();
+    assert_eq!(*server.inner.0.notified.lock().unwrap(), ["hello"]);
+}
+
+#[test]
+fn wrong_interface() {
+    let server = BnTest(TestService::default());
+    let client = BpTest::new(Service::local(&server, "android.test.IOther"));
+
+    assert!(client.add(1, 2).is_err());
+}
@@ -0,0 +1,146 @@
+//! Blobs in shared memory are only mapped when the sender can't change
+//! them, memfds sealed against writes. Those of libcutils, which the sender
+//! may still write through an existing mapping, are copied.
+
+use std::{
+    fs::File,
+    io::Write,
+    num::NonZero,
+    os::fd::{AsRawFd, IntoRawFd},
+};
+
+use binder_rs::{
+    error::BinderError,
+    parcel::{Parcel, blob::BLOB_ASHMEM_IMMUTABLE},
+    service::BinderFlatObject,
+};
+use nix::{
+    fcntl::{FcntlArg, SealFlag, fcntl},
+    sys::{
+        memfd::{MemFdCreateFlag, memfd_create},
+        mman::{MapFlags, ProtFlags, mmap, munmap},
+    },
+};
+
+const LEN: usize = 32 * 1024;
+
+/// A memfd of `LEN` bytes.
+fn memfd() -> File {
+    let fd = memfd_create(
+        c"blob",
+        MemFdCreateFlag::MFD_CLOEXEC | MemFdCreateFlag::MFD_ALLOW_SEALING,
+    )
+    .unwrap();
+    let mut file = File::from(fd);
+    file.write_all(&[7; LEN]).unwrap();
+    file
+}
+
+/// A memfd of `LEN` bytes with `seals`, as a parcel of a blob.
+fn blob_parcel(seals: SealFlag) -> Parcel<'static> {
+    let file = memfd();
+    fcntl(file.as_raw_fd(), FcntlArg::F_ADD_SEALS(seals)).unwrap();
+    parcel_of(file)
+}
+
+fn parcel_of(file: File) -> Parcel<'static> {
+    let mut parcel = Parcel::new();
+    parcel.write(&(LEN as i32)).unwrap();
+    parcel.write(&BLOB_ASHMEM_IMMUTABLE).unwrap();
+    parcel
+        .write(&BinderFlatObject::new_with_fd(file.into_raw_fd(), true))
+        .unwrap();
+    parcel.set_data_position(0);
+    parcel
+}
+
+#[test]
+fn written_blob() {
+    let mut parcel = Parcel::new();
+    parcel.write_blob(Some(&[7; LEN])).unwrap();
+    parcel.set_data_position(0);
+
+    let blob = parcel.read_blob().unwrap().unwrap();
+    assert!(blob.is_mapped());
+    assert_eq!(*blob, [7; LEN]);
+}
+
+#[test]
+fn libcutils_memfd() {
+    // ashmem_create_region seals GROW and SHRINK, then
+    // ashmem_set_prot_region(PROT_READ) adds FUTURE_WRITE
+    let mut parcel = blob_parcel(
+        SealFlag::F_SEAL_GROW | SealFlag::F_SEAL_SHRINK | SealFlag::F_SEAL_FUTURE_WRITE,
+    );
+    let blob = parcel.read_blob().unwrap().unwrap();
+    assert!(!blob.is_mapped());
+    assert_eq!(*blob, [7; LEN]);
+
+    // growing doesn't change the mapped bytes
+    let mut parcel = blob_parcel(SealFlag::F_SEAL_SHRINK | SealFlag::F_SEAL_WRITE);
+    let blob = parcel.read_blob().unwrap().unwrap();
+    assert!(blob.is_mapped());
+    assert_eq!(*blob, [7; LEN]);
+}
+
+#[test]
+fn written_after_read() {
+    // the sender keeps a writable mapping, made before the memfd is
+    // sealed against new ones
+    let file = memfd();
+    // SAFETY: a new shared mapping, unmapped below.
+    let ptr = unsafe {
+        mmap(
+            None,
+            NonZero::new(LEN).unwrap(),
+            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            MapFlags::MAP_SHARED,
+            &file,
+            0,
+        )
+    }
+    .unwrap();
+    let seals = SealFlag::F_SEAL_GROW | SealFlag::F_SEAL_SHRINK | SealFlag::F_SEAL_FUTURE_WRITE;
+    fcntl(file.as_raw_fd(), FcntlArg::F_ADD_SEALS(seals)).unwrap();
+
+    let mut parcel = parcel_of(file);
+    let blob = parcel.read_blob().unwrap().unwrap();
+    // SAFETY: `LEN` bytes mapped above.
+    unsafe {
+        ptr.cast::<u8>().write_bytes(9, LEN);
+        munmap(ptr, LEN).unwrap();
+    }
+    assert_eq!(*blob, [7; LEN]);
+}
+
+#[test]
+fn mutable_memfd() {
+    for seals in [
+        SealFlag::empty(),
+        SealFlag::F_SEAL_GROW | SealFlag::F_SEAL_SHRINK,
+        SealFlag::F_SEAL_GROW | SealFlag::F_SEAL_WRITE,
+    ] {
+        let mut parcel = blob_parcel(seals);
+        assert!(
+            matches!(parcel.read_blob(), Err(BinderError::InvalidOperation)),
+            "{seals:?}"
+        );
+    }
+}
+
+#[test]
+fn not_shared_memory() {
+    let file = File::open("/dev/null").unwrap();
+    let mut parcel = Parcel::new();
+    parcel.write(&0i32).unwrap();
+    parcel.write(&BLOB_ASHMEM_IMMUTABLE).unwrap();
+    parcel
+        .write(&BinderFlatObject::new_with_fd(file.into_raw_fd(), true))
+        .unwrap();
+    parcel.set_data_position(0);
+
+    assert!(matches!(
+        parcel.read_blob(),
+        Err(BinderError::InvalidOperation)
+    ));
+}
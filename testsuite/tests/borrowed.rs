@@ -0,0 +1,58 @@
+//! Borrowed slices read at both alignments a parcel can give them: values
+//! are only aligned to 4 bytes, so an element after an odd number of words
+//! isn't aligned to 8.
+
+use binder_rs::parcel::Parcel;
+
+/// A buffer aligned to 8 bytes, like the binder mapping.
+#[repr(C, align(8))]
+struct Aligned([u8; 64]);
+
+fn aligned(words: &[i32]) -> Aligned {
+    let mut buf = Aligned([0; 64]);
+    for (chunk, word) in buf.0.chunks_exact_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    buf
+}
+
+#[test]
+fn both_alignments() {
+    let a = 1.5f32.to_bits() as i32;
+    let b = (-2.0f32).to_bits() as i32;
+    // int[], float[], byte[] and the string "hi"
+    let slices = [
+        [2, 10, -20],
+        [2, a, b],
+        [5, 0x0403_0201, 0x05],
+        [2, 0x0069_0068, 0],
+    ]
+    .concat();
+
+    for skip in [0, 1] {
+        let mut words = vec![0x7777; skip];
+        words.extend_from_slice(&slices);
+        let buf = aligned(&words);
+        let mut parcel = Parcel::from_slice(&buf.0[..words.len() * 4]);
+        parcel.set_data_position(skip * 4);
+
+        let ints: &[i32] = parcel.read_borrowed().unwrap();
+        assert_eq!(ints, [10, -20]);
+        assert_eq!(ints.as_ptr() as usize % 8, (skip + 1) % 2 * 4);
+
+        let floats: &[f32] = parcel.read_borrowed().unwrap();
+        assert_eq!(floats, [1.5, -2.0]);
+        let bytes: &[u8] = parcel.read_borrowed().unwrap();
+        assert_eq!(bytes, [1, 2, 3, 4, 5]);
+        let string: &[u16] = parcel.read_borrowed().unwrap();
+        assert_eq!(String::from_utf16(string).unwrap(), "hi");
+        assert_eq!(parcel.data_position(), words.len() * 4);
+    }
+}
+
+#[test]
+fn null_slice() {
+    let buf = aligned(&[-1]);
+    let mut parcel = Parcel::from_slice(&buf.0[..4]);
+    assert_eq!(parcel.read_borrowed::<Option<&[i32]>>().unwrap(), None);
+}
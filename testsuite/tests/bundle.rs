@@ -0,0 +1,199 @@
+//! The layout of `Bundle`, against words transcribed by hand from
+//! `BaseBundle.writeToParcelInner` and `Parcel.writeValue` of Android 14,
+//! not captured from a device.
+
+use binder_rs::{
+    error::{BinderError, Result},
+    parcel::{
+        Parcel,
+        bundle::Bundle,
+        parcelable::Parcelable,
+        value::{ParcelValue, VAL_BUNDLE, VAL_INTEGER, VAL_PARCELABLE, VAL_PARCELABLEARRAY},
+    },
+};
+use binder_rs_testsuite::{parcel_of, words};
+
+/// 'BNDL'
+const BUNDLE_MAGIC: i32 = 0x4C44_4E42;
+/// 'BNDN'
+const BUNDLE_MAGIC_NATIVE: i32 = 0x4C44_4E44;
+
+/// `{"a": 5, "p": com.example.Foo}`, the parcelable holding two ints.
+/// `ArrayMap` orders the keys by hash code, "a" then "p".
+const BUNDLE: [i32; 22] = [
+    80, // length, from the count to the end
+    BUNDLE_MAGIC,
+    2, // count
+    1, // "a", length then UTF-16 with a terminator
+    0x0000_0061,
+    VAL_INTEGER,
+    5,
+    1, // "p"
+    0x0000_0070,
+    VAL_PARCELABLE,
+    44, // length of the value, from the class name to the end
+    15, // "com.example.Foo"
+    0x006f_0063,
+    0x002e_006d,
+    0x0078_0065,
+    0x006d_0061,
+    0x006c_0070,
+    0x002e_0065,
+    0x006f_0046,
+    0x0000_006f,
+    1, // content of the parcelable
+    2,
+];
+
+/// `Bundle.writeToParcel`, without the non-null flag of an AIDL argument.
+fn write_bundle(bundle: &Bundle) -> Vec<i32> {
+    let mut parcel = Parcel::new();
+    bundle.write_to_parcel(&mut parcel).unwrap();
+    words(&mut parcel)
+}
+
+fn read_bundle(parcel: &mut Parcel) -> Result<Bundle> {
+    let mut bundle = Bundle::new();
+    bundle.read_from_parcel(parcel)?;
+    Ok(bundle)
+}
+
+fn foo() -> ParcelValue {
+    ParcelValue::Parcelable {
+        class: "com.example.Foo".to_owned(),
+        data: [1i32, 2].iter().flat_map(|w| w.to_le_bytes()).collect(),
+    }
+}
+
+#[test]
+fn layout() {
+    let mut bundle = Bundle::new();
+    bundle.insert("a", 5).unwrap();
+    bundle.insert("p", foo()).unwrap();
+
+    assert_eq!(write_bundle(&bundle), BUNDLE);
+
+    let mut fixture = BUNDLE.to_vec();
+    fixture.push(7);
+    let mut parcel = parcel_of(&fixture);
+    assert_eq!(read_bundle(&mut parcel).unwrap(), bundle);
+    assert_eq!(parcel.read::<i32>().unwrap(), 7);
+
+    // as an AIDL argument, with a non-null flag
+    let mut parcel = Parcel::new();
+    parcel.write(&bundle).unwrap();
+    assert_eq!(words(&mut parcel)[..2], [1, 80]);
+    parcel.set_data_position(0);
+    assert_eq!(parcel.read::<Bundle>().unwrap(), bundle);
+}
+
+#[test]
+fn hash_order() {
+    // "b" hashes to 98 and "ab" to 3105, against their lexical order
+    let mut bundle = Bundle::new();
+    bundle.insert("ab", 2).unwrap();
+    bundle.insert("b", 1).unwrap();
+    assert_eq!(
+        write_bundle(&bundle),
+        [
+            40,
+            BUNDLE_MAGIC,
+            2,
+            1, // "b"
+            0x0000_0062,
+            VAL_INTEGER,
+            1,
+            2, // "ab"
+            0x0062_0061,
+            0,
+            VAL_INTEGER,
+            2,
+        ]
+    );
+}
+
+#[test]
+fn native_magic() {
+    let mut fixture = BUNDLE;
+    fixture[1] = BUNDLE_MAGIC_NATIVE;
+    let bundle = read_bundle(&mut parcel_of(&fixture)).unwrap();
+    assert_eq!(bundle.get_int("a"), Some(5));
+    assert_eq!(bundle.get("p"), Some(&foo()));
+}
+
+#[test]
+fn bad_magic_and_length() {
+    let mut fixture = BUNDLE;
+    fixture[1] = 0x1234;
+    assert!(matches!(
+        read_bundle(&mut parcel_of(&fixture)),
+        Err(BinderError::BadType)
+    ));
+
+    let mut fixture = BUNDLE;
+    fixture[0] = 200;
+    assert!(matches!(
+        read_bundle(&mut parcel_of(&fixture)),
+        Err(BinderError::NotEnoughData)
+    ));
+}
+
+#[test]
+fn empty() {
+    assert_eq!(write_bundle(&Bundle::new()), [0]);
+    assert!(read_bundle(&mut parcel_of(&[0])).unwrap().is_empty());
+}
+
+#[test]
+fn nested_bundles() {
+    // `Parcel.writeBundle` writes a null bundle as -1
+    let value: ParcelValue = parcel_of(&[VAL_BUNDLE, -1]).read().unwrap();
+    assert_eq!(value, ParcelValue::Null);
+
+    let value: ParcelValue = parcel_of(&[VAL_BUNDLE, 0]).read().unwrap();
+    assert_eq!(value, ParcelValue::Bundle(Bundle::new()));
+
+    let mut inner = Bundle::new();
+    inner.insert("a", 5).unwrap();
+    let mut parcel = Parcel::new();
+    parcel.write(&ParcelValue::Bundle(inner)).unwrap();
+    assert_eq!(
+        words(&mut parcel),
+        [VAL_BUNDLE, 20, BUNDLE_MAGIC, 1, 1, 0x61, VAL_INTEGER, 5]
+    );
+}
+
+#[test]
+fn lazy_value() {
+    // `Parcel.writeParcelableArray` of one null parcelable: the count,
+    // then a null class name
+    let fixture = [VAL_PARCELABLEARRAY, 8, 1, -1];
+    let value: ParcelValue = parcel_of(&fixture).read().unwrap();
+    assert_eq!(
+        value,
+        ParcelValue::Lazy {
+            tag: VAL_PARCELABLEARRAY,
+            data: [1i32, -1].iter().flat_map(|w| w.to_le_bytes()).collect(),
+        }
+    );
+
+    let mut bundle = Bundle::new();
+    bundle.insert("key", value).unwrap();
+    let written = write_bundle(&bundle);
+    assert_eq!(
+        written,
+        [
+            32,
+            BUNDLE_MAGIC,
+            1,
+            3, // "key"
+            0x0065_006b,
+            0x0000_0079,
+            VAL_PARCELABLEARRAY,
+            8,
+            1,
+            -1,
+        ]
+    );
+    assert_eq!(read_bundle(&mut parcel_of(&written)).unwrap(), bundle);
+}
@@ -0,0 +1,150 @@
+//! The layout of the derived parcel traits, and how they read data of an
+//! older or a newer version of a struct.
+
+use binder_rs::{
+    error::BinderError,
+    parcel::{
+        Parcel,
+        parcelable::{Deserialize, DeserializeOption, Serialize, SerializeOption},
+    },
+};
+use binder_rs_testsuite::words;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, SerializeOption, DeserializeOption)]
+struct Point {
+    x: i32,
+    y: i32,
+    #[parcel(default = String::from("origin"))]
+    label: String,
+    scale: i64,
+}
+
+/// `Point` as known by an older peer.
+#[derive(Serialize)]
+struct PointV1 {
+    x: i32,
+    y: i32,
+}
+
+/// `Point` as known by a newer peer.
+#[derive(Serialize)]
+struct PointV3 {
+    x: i32,
+    y: i32,
+    label: String,
+    scale: i64,
+    z: i32,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[repr(i32)]
+enum Mode {
+    Off = 1,
+    On = 5,
+}
+
+#[test]
+fn struct_layout() {
+    let point = Point {
+        x: 1,
+        y: 2,
+        label: "ab".to_owned(),
+        scale: -1,
+    };
+    let mut parcel = Parcel::new();
+    parcel.write(&point).unwrap();
+
+    assert_eq!(
+        words(&mut parcel),
+        [
+            1,  // non-null flag
+            32, // size, from the size field to the end
+            1,
+            2,
+            2, // label: length in UTF-16 units, then "ab\0" padded
+            0x0062_0061,
+            0,
+            -1, // scale
+            -1,
+        ]
+    );
+
+    parcel.set_data_position(0);
+    assert_eq!(parcel.read::<Point>().unwrap(), point);
+    assert!(!parcel.has_unread_data());
+}
+
+#[test]
+fn null_struct() {
+    let mut parcel = Parcel::new();
+    parcel.write(&None::<Point>).unwrap();
+    assert_eq!(words(&mut parcel), [0]);
+
+    parcel.set_data_position(0);
+    assert_eq!(parcel.read::<Option<Point>>().unwrap(), None);
+    parcel.set_data_position(0);
+    assert!(matches!(
+        parcel.read::<Point>(),
+        Err(BinderError::UnexpectedNull)
+    ));
+}
+
+#[test]
+fn missing_trailing_fields() {
+    let mut parcel = Parcel::new();
+    parcel.write(&PointV1 { x: 1, y: 2 }).unwrap();
+    parcel.write(&7i32).unwrap();
+
+    parcel.set_data_position(0);
+    assert_eq!(
+        parcel.read::<Point>().unwrap(),
+        Point {
+            x: 1,
+            y: 2,
+            label: "origin".to_owned(),
+            scale: 0,
+        }
+    );
+    assert_eq!(parcel.read::<i32>().unwrap(), 7);
+}
+
+#[test]
+fn unknown_trailing_fields() {
+    let mut parcel = Parcel::new();
+    parcel
+        .write(&PointV3 {
+            x: 1,
+            y: 2,
+            label: "p".to_owned(),
+            scale: 3,
+            z: 4,
+        })
+        .unwrap();
+    parcel.write(&7i32).unwrap();
+
+    parcel.set_data_position(0);
+    assert_eq!(
+        parcel.read::<Point>().unwrap(),
+        Point {
+            x: 1,
+            y: 2,
+            label: "p".to_owned(),
+            scale: 3,
+        }
+    );
+    assert_eq!(parcel.read::<i32>().unwrap(), 7);
+}
+
+#[test]
+fn enum_values() {
+    let mut parcel = Parcel::new();
+    parcel.write(&Mode::On).unwrap();
+    parcel.write(&Mode::Off).unwrap();
+    parcel.write(&3i32).unwrap();
+    assert_eq!(words(&mut parcel), [5, 1, 3]);
+
+    parcel.set_data_position(0);
+    assert_eq!(parcel.read::<Mode>().unwrap(), Mode::On);
+    assert_eq!(parcel.read::<Mode>().unwrap(), Mode::Off);
+    assert!(matches!(parcel.read::<Mode>(), Err(BinderError::BadValue)));
+}
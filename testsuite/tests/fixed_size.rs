@@ -0,0 +1,96 @@
+//! The layout of AIDL fixed-size arrays and `@FixedSize` parcelables.
+//!
+//! The expected words are transcribed from the AOSP sources, not captured
+//! from a device: `Parcel::writeFixedArray` in libbinder and
+//! `Parcel.writeFixedArrayInternal` in Java write the length of the array
+//! at every level, and a `@FixedSize` parcelable is written like any other
+//! parcelable, its non-null flag then its size. Only the fast message queue
+//! drops those headers, parcels keep them.
+
+use binder_rs::{error::BinderError, parcel::Parcel};
+use binder_rs_testsuite::{android::test::Cell::Cell, words};
+
+fn cell() -> Cell {
+    Cell {
+        id: 1,
+        stamp: 2,
+        tag: [0x0a, 0x0b, 0x0c],
+        grid: [[1, 2, 3], [4, 5, 6]],
+    }
+}
+
+const CELL: [i32; 16] = [
+    1,  // non-null flag
+    60, // size, from the size field to the end
+    1,  // id
+    2,  // stamp
+    0,
+    3, // tag: length, then the bytes padded to 4
+    0x000c_0b0a,
+    2, // grid: length of the outer array
+    3, // length of grid[0]
+    1,
+    2,
+    3,
+    3, // length of grid[1]
+    4,
+    5,
+    6,
+];
+
+#[test]
+fn nested_array() {
+    let grid: [[i32; 3]; 2] = [[1, 2, 3], [4, 5, 6]];
+    let mut parcel = Parcel::new();
+    parcel.write(&grid).unwrap();
+    assert_eq!(words(&mut parcel), [2, 3, 1, 2, 3, 3, 4, 5, 6]);
+
+    parcel.set_data_position(0);
+    assert_eq!(parcel.read::<[[i32; 3]; 2]>().unwrap(), grid);
+
+    // the length is checked at every level
+    parcel.set_data_position(0);
+    assert!(matches!(
+        parcel.read::<[[i32; 3]; 3]>(),
+        Err(BinderError::BadValue)
+    ));
+    parcel.set_data_position(0);
+    assert!(matches!(
+        parcel.read::<[[i32; 2]; 2]>(),
+        Err(BinderError::BadValue)
+    ));
+}
+
+#[test]
+fn byte_array() {
+    let mut parcel = Parcel::new();
+    parcel.write(&[0x0au8, 0x0b, 0x0c]).unwrap();
+    assert_eq!(words(&mut parcel), [3, 0x000c_0b0a]);
+
+    parcel.set_data_position(0);
+    assert_eq!(parcel.read::<[u8; 3]>().unwrap(), [0x0a, 0x0b, 0x0c]);
+}
+
+#[test]
+fn fixed_size_parcelable() {
+    let mut parcel = Parcel::new();
+    parcel.write(&cell()).unwrap();
+    assert_eq!(words(&mut parcel), CELL);
+
+    parcel.set_data_position(0);
+    assert_eq!(parcel.read::<Cell>().unwrap(), cell());
+}
+
+#[test]
+fn array_of_fixed_size_parcelables() {
+    let mut parcel = Parcel::new();
+    parcel.write(&[cell(), cell()]).unwrap();
+
+    let mut expected = vec![2];
+    expected.extend_from_slice(&CELL);
+    expected.extend_from_slice(&CELL);
+    assert_eq!(words(&mut parcel), expected);
+
+    parcel.set_data_position(0);
+    assert_eq!(parcel.read::<[Cell; 2]>().unwrap(), [cell(), cell()]);
+}
@@ -0,0 +1,64 @@
+//! The inspector finds the objects of a parcel at their offsets, alone, in
+//! arrays and in parcelables.
+
+use binder_rs::{parcel::Parcel, service::BinderFlatObject};
+
+/// The fd objects don't own their descriptors.
+fn fd(fd: i32) -> BinderFlatObject {
+    BinderFlatObject::new_with_fd(fd, false)
+}
+
+const EXPECTED: &str = "\
+0x0000..0x0018 interface token \"a.B\"
+  0x0000..0x0004 strict mode policy 0xc2000004
+  0x0004..0x0008 work source -1
+  0x0008..0x000c interface header 0x53595354
+  0x000c..0x0018 string16 \"a.B\"
+0x0018..0x001c int 7 (0x7)
+0x001c..0x0034 Fd object 0x5, cookie 0x0, flags 0x0
+0x0034..0x0068 array of 2
+  0x0038..0x0050 Fd object 0x6, cookie 0x0, flags 0x0
+  0x0050..0x0068 Fd object 0x7, cookie 0x0, flags 0x0
+0x0068..0x0088 parcelable
+  0x0068..0x006c non-null flag
+  0x006c..0x0070 size 28
+  0x0070..0x0088 Fd object 0x8, cookie 0x0, flags 0x0
+";
+
+#[test]
+fn objects() {
+    let mut parcel = Parcel::new();
+    parcel.write_interface_token("a.B").unwrap();
+    parcel.write(&7i32).unwrap();
+    parcel.write(&fd(5)).unwrap();
+    // an array of two objects
+    parcel.write(&2i32).unwrap();
+    parcel.write(&fd(6)).unwrap();
+    parcel.write(&fd(7)).unwrap();
+    // a parcelable holding an object
+    parcel.write(&1i32).unwrap();
+    parcel.write(&28i32).unwrap();
+    parcel.write(&fd(8)).unwrap();
+    assert_eq!(parcel.inspect().to_string(), EXPECTED);
+
+    // the same data and offsets, as received from the driver
+    // SAFETY: the data is valid for its size.
+    let data = unsafe { std::slice::from_raw_parts(parcel.as_ptr(), parcel.data_size()) };
+    let offsets = [0x1c, 0x38, 0x50, 0x70];
+    // SAFETY: both slices outlive the parcel.
+    let received = unsafe {
+        Parcel::from_ipc_parts(
+            data.as_ptr(),
+            data.len(),
+            offsets.as_ptr(),
+            offsets.len(),
+            None,
+        )
+    }
+    .unwrap();
+    assert_eq!(received.inspect().to_string(), EXPECTED);
+
+    // without the offsets, the objects are only words
+    let words = Parcel::from_slice(data).inspect().to_string();
+    assert!(!words.contains("object"), "{words}");
+}
@@ -0,0 +1,87 @@
+//! Lengths and nesting read from a parcel are checked against its limits
+//! before anything is allocated for them.
+
+use binder_rs::{
+    error::BinderError,
+    parcel::{Parcel, ParcelLimits, value::ParcelValue},
+};
+use binder_rs_testsuite::parcel_of;
+
+fn limited(words: &[i32], limits: ParcelLimits) -> Parcel<'static> {
+    let mut parcel = parcel_of(words);
+    parcel.set_limits(limits);
+    parcel
+}
+
+#[test]
+fn element_count() {
+    // over the default limit of elements
+    assert!(matches!(
+        parcel_of(&[i32::MAX]).read::<Vec<i32>>(),
+        Err(BinderError::BadValue)
+    ));
+    assert!(matches!(
+        parcel_of(&[i32::MAX - 1]).read::<String>(),
+        Err(BinderError::BadValue)
+    ));
+    // within the limits, but not in the data
+    assert!(parcel_of(&[1 << 20]).read::<Vec<i32>>().is_err());
+
+    let limits = ParcelLimits {
+        max_elements: 2,
+        ..Default::default()
+    };
+    assert_eq!(
+        limited(&[2, 1, 2], limits).read::<Vec<i32>>().unwrap(),
+        [1, 2]
+    );
+    assert!(matches!(
+        limited(&[3, 1, 2, 3], limits).read::<Vec<i32>>(),
+        Err(BinderError::BadValue)
+    ));
+    assert!(matches!(
+        limited(&[3, 0x0062_0061, 0x0000_0063], limits).read::<String>(),
+        Err(BinderError::BadValue)
+    ));
+}
+
+#[test]
+fn allocation() {
+    // the allocations add up over the life of the parcel
+    let limits = ParcelLimits {
+        max_allocation: 16,
+        ..Default::default()
+    };
+    let mut parcel = limited(&[3, 1, 2, 3, 3, 4, 5, 6], limits);
+    assert_eq!(parcel.read::<Vec<i32>>().unwrap(), [1, 2, 3]);
+    assert!(matches!(
+        parcel.read::<Vec<i32>>(),
+        Err(BinderError::BadValue)
+    ));
+}
+
+/// `depth` lists, each holding the next one.
+fn nested_lists(depth: usize) -> ParcelValue {
+    (0..depth).fold(ParcelValue::Null, |value, _| ParcelValue::List(vec![value]))
+}
+
+#[test]
+fn nesting_depth() {
+    let mut parcel = Parcel::new();
+    parcel.write_value(&nested_lists(10)).unwrap();
+    parcel.set_data_position(0);
+    assert_eq!(parcel.read_value().unwrap(), nested_lists(10));
+
+    parcel.set_data_position(0);
+    parcel.set_limits(ParcelLimits {
+        max_depth: 5,
+        ..Default::default()
+    });
+    assert!(matches!(parcel.read_value(), Err(BinderError::BadValue)));
+
+    // over the default limit
+    let mut parcel = Parcel::new();
+    parcel.write_value(&nested_lists(100)).unwrap();
+    parcel.set_data_position(0);
+    assert!(matches!(parcel.read_value(), Err(BinderError::BadValue)));
+}
@@ -0,0 +1,57 @@
+//! Interfaces declared with `declare_binder_interface!`.
+
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use binder_rs::{declare_binder_interface, service::Service, status::Status};
+
+declare_binder_interface! {
+    /// Adds numbers.
+    pub trait ICalculator["android.test.ICalculator"] {
+        fn add(a: i32, b: i32) -> i32 = 0;
+        fn check(value: i32) = 1;
+        oneway fn reset(value: i32) = 2;
+    }
+    proxy BpCalculator;
+    native BnCalculator;
+}
+
+#[derive(Default)]
+struct Calculator {
+    value: AtomicI32,
+}
+
+impl ICalculator for Calculator {
+    fn add(&self, a: i32, b: i32) -> Result<i32, Status> {
+        Ok(self.value.load(Ordering::Relaxed) + a + b)
+    }
+
+    fn check(&self, value: i32) -> Result<(), Status> {
+        if value == self.value.load(Ordering::Relaxed) {
+            Ok(())
+        } else {
+            Err(Status::from(binder_rs::error::BinderError::BadValue))
+        }
+    }
+
+    fn reset(&self, value: i32) -> Result<(), Status> {
+        self.value.store(value, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[test]
+fn local_round_trip() {
+    let server = BnCalculator(Calculator::default());
+    let proxy = BpCalculator::new(Service::local(
+        &server,
+        BnCalculator::<Calculator>::DESCRIPTOR,
+    ));
+
+    assert_eq!(proxy.add(2, 3).unwrap(), 5);
+    proxy.check(0).unwrap();
+
+    proxy.reset(10).unwrap();
+    assert_eq!(server.0.value.load(Ordering::Relaxed), 10);
+    assert_eq!(proxy.add(2, 3).unwrap(), 15);
+    assert!(proxy.check(0).is_err());
+}
@@ -0,0 +1,69 @@
+//! Object offsets received from the driver are checked before any object
+//! is read at them.
+
+use binder_rs::{error::BinderError, parcel::Parcel, service::BinderFlatObject};
+
+/// An `i32` then two objects, at 4 and 28: 52 bytes.
+fn data() -> Vec<u8> {
+    let mut parcel = Parcel::new();
+    parcel.write(&7i32).unwrap();
+    for _ in 0..2 {
+        parcel
+            .write(&BinderFlatObject::new_with_fd(-1, false))
+            .unwrap();
+    }
+    // SAFETY: the data is valid for its size.
+    unsafe { std::slice::from_raw_parts(parcel.as_ptr(), parcel.data_size()) }.to_vec()
+}
+
+fn from_ipc_parts(data: &[u8], offsets: &[usize]) -> Result<(), BinderError> {
+    // SAFETY: both slices outlive the parcel.
+    unsafe {
+        Parcel::from_ipc_parts(
+            data.as_ptr(),
+            data.len(),
+            offsets.as_ptr(),
+            offsets.len(),
+            None,
+        )
+    }
+    .map(drop)
+}
+
+#[test]
+fn valid() {
+    let data = data();
+    assert_eq!(data.len(), 52);
+    from_ipc_parts(&data, &[4, 28]).unwrap();
+    from_ipc_parts(&data, &[28]).unwrap();
+    from_ipc_parts(&data, &[]).unwrap();
+}
+
+#[test]
+fn rejected() {
+    let data = data();
+    for offsets in [
+        &[6][..],          // unaligned
+        &[28, 4],          // unsorted
+        &[4, 20],          // overlapping
+        &[32],             // past the end of the data
+        &[usize::MAX - 3], // overflowing
+        &[4, 28, 52],      // at the end of the data
+    ] {
+        assert!(
+            matches!(from_ipc_parts(&data, offsets), Err(BinderError::BadValue)),
+            "{offsets:?}"
+        );
+    }
+}
+
+#[test]
+fn bad_type() {
+    let mut data = data();
+    data[28..32].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+    from_ipc_parts(&data, &[4]).unwrap();
+    assert!(matches!(
+        from_ipc_parts(&data, &[4, 28]),
+        Err(BinderError::BadType)
+    ));
+}
@@ -0,0 +1,81 @@
+//! Parcel operations shared by the generated code and the services.
+
+use binder_rs::{
+    error::BinderError,
+    parcel::Parcel,
+    service::{BinderService, CallingContext, Service, TransactionFlag},
+    status::Status,
+};
+
+const INTERFACE: &str = "android.test.IBlob";
+
+/// Replies with the length and the sum of the blob it receives.
+struct BlobService;
+
+impl BinderService for BlobService {
+    fn progress_request(
+        &self,
+        _code: u32,
+        data: &mut Parcel,
+        reply: &mut Parcel,
+        _ctx: &CallingContext,
+        _flags: TransactionFlag,
+    ) -> Result<(), Status> {
+        data.enforce_interface(INTERFACE)?;
+        let blob = data.read_blob()?.ok_or(BinderError::UnexpectedNull)?;
+        assert!(blob.is_mapped());
+        reply.write(&(blob.len() as i32))?;
+        reply.write(&blob.iter().map(|&b| b as i64).sum::<i64>())?;
+        Ok(())
+    }
+}
+
+#[test]
+fn append_keeps_objects() {
+    // a local transaction copies the data with `append_all_from`, the
+    // file descriptor of the blob must come along
+    let server = BlobService;
+    let service = Service::local(&server, INTERFACE);
+    let mut data = service.prepare_transaction().unwrap();
+    data.write_blob(Some(&[3; 64 * 1024])).unwrap();
+
+    let mut reply = service
+        .transact(1, &mut data, TransactionFlag::empty())
+        .unwrap();
+    assert!(reply.read::<Status>().unwrap().is_ok());
+    assert_eq!(reply.read::<i32>().unwrap(), 64 * 1024);
+    assert_eq!(reply.read::<i64>().unwrap(), 3 * 64 * 1024);
+}
+
+#[test]
+fn sized_read_bounds() {
+    let mut parcel = Parcel::new();
+    parcel
+        .sized_write(|parcel| {
+            parcel.write(&1i32)?;
+            parcel.write(&2i32)
+        })
+        .unwrap();
+    parcel.write(&7i32).unwrap();
+
+    // reads past the end of the parcelable fail
+    parcel.set_data_position(0);
+    let result = parcel.sized_read(|sub| {
+        assert_eq!(sub.read::<i32>()?, 1);
+        assert_eq!(sub.read::<i32>()?, 2);
+        assert!(!sub.has_more_data());
+        sub.read::<i32>().map(drop)
+    });
+    assert!(matches!(result, Err(BinderError::NotEnoughData)));
+
+    // what isn't read is skipped
+    parcel.set_data_position(0);
+    parcel
+        .sized_read(|sub| {
+            assert_eq!(sub.read::<i32>()?, 1);
+            assert!(sub.has_more_data());
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(parcel.read::<i32>().unwrap(), 7);
+}
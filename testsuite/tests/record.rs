@@ -0,0 +1,76 @@
+//! Recording and replaying a parcel leave the file descriptors of the
+//! process alone. Alone in their test binary, so that no other test opens
+//! files concurrently and the descriptor numbers are predictable.
+
+use std::{
+    fs::File,
+    os::fd::{AsRawFd, FromRawFd, IntoRawFd},
+};
+
+use binder_rs::{
+    parcel::{
+        Parcel,
+        record::{ParcelRecord, RecordKind},
+    },
+    service::{BinderFlatObject, BinderTransactionData},
+};
+use nix::fcntl::{FcntlArg, fcntl};
+
+#[test]
+fn replay_keeps_fds_open() {
+    let mut parcel = Parcel::new();
+    parcel.write_blob(Some(&vec![7; 64 * 1024])).unwrap();
+
+    // SAFETY: only integers and pointers, none dereferenced by `new`.
+    let tx: BinderTransactionData = unsafe { std::mem::zeroed() };
+    let record = ParcelRecord::new(RecordKind::Outgoing, &tx, &parcel).unwrap();
+    let [recorded] = record.objects.as_slice() else {
+        panic!("one object expected: {:?}", record.objects);
+    };
+    assert_ne!(recorded.cookie, 0, "the blob owns its memfd");
+
+    // the memfd is closed, its number is reused by an unrelated file
+    drop(parcel);
+    let file = File::open("/dev/null").unwrap();
+    assert_eq!(file.as_raw_fd() as u64, recorded.value);
+
+    drop(record.to_parcel().unwrap());
+    assert!(file.metadata().is_ok(), "the replay closed an unrelated fd");
+}
+
+#[test]
+fn record_keeps_fds_open() {
+    // a received transaction carrying a descriptor owned by its parcel
+    let fd = File::open("/dev/null").unwrap().into_raw_fd();
+    let mut parcel = Parcel::new();
+    parcel
+        .write(&BinderFlatObject::new_with_fd(fd, true))
+        .unwrap();
+    // SAFETY: the data is valid for its size.
+    let mut data =
+        unsafe { std::slice::from_raw_parts(parcel.as_ptr(), parcel.data_size()) }.to_vec();
+    std::mem::forget(parcel);
+    let mut offsets = [0usize];
+
+    // SAFETY: only integers and pointers.
+    let mut tx: BinderTransactionData = unsafe { std::mem::zeroed() };
+    tx.data_size = data.len();
+    tx.data = data.as_mut_ptr();
+    tx.offsets_size = size_of_val(&offsets);
+    tx.offsets = offsets.as_mut_ptr();
+
+    // SAFETY: the buffers outlive the record.
+    let record = unsafe { ParcelRecord::from_transaction(RecordKind::Incoming, &tx) }.unwrap();
+    let [recorded] = record.objects.as_slice() else {
+        panic!("one object expected: {:?}", record.objects);
+    };
+    assert_eq!(recorded.value, fd as u64);
+    assert_ne!(recorded.cookie, 0);
+
+    assert!(
+        fcntl(fd, FcntlArg::F_GETFD).is_ok(),
+        "the record closed the fd of the handler"
+    );
+    // SAFETY: owned by the transaction, which is never released.
+    drop(unsafe { File::from_raw_fd(fd) });
+}
@@ -0,0 +1,152 @@
+//! Sensitive parcels: their memory is zeroed before it is freed, and the
+//! transactions and replies carrying them are cleared by the driver.
+//!
+//! The allocator of this test binary looks for a secret in the memory
+//! freed by each thread.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use binder_rs::{
+    parcel::Parcel,
+    service::{BinderService, CallingContext, Service, TransactionFlag},
+    status::{ExceptionCode, Status},
+};
+
+const SECRET: &str = "hunter2-hunter2!";
+const INTERFACE: &str = "android.test.ISecret";
+
+thread_local! {
+    static LEAKED: Cell<usize> = const { Cell::new(0) };
+}
+
+struct Scanning;
+
+// SAFETY: the allocations are those of `System`.
+unsafe impl GlobalAlloc for Scanning {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: the allocation is still valid for its size.
+        let bytes = unsafe { std::slice::from_raw_parts(ptr, layout.size()) };
+        if bytes.windows(SECRET.len()).any(|w| w == SECRET.as_bytes()) {
+            let _ = LEAKED.try_with(|leaked| leaked.set(leaked.get() + 1));
+        }
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Scanning = Scanning;
+
+/// Allocations freed by `f` with the secret in them.
+fn leaked(f: impl FnOnce()) -> usize {
+    let before = LEAKED.get();
+    f();
+    LEAKED.get() - before
+}
+
+fn secret(sensitive: bool) -> Parcel<'static> {
+    let mut parcel = Parcel::new();
+    if sensitive {
+        parcel.mark_sensitive();
+    }
+    parcel.write_string8(Some(SECRET)).unwrap();
+    parcel
+}
+
+#[test]
+fn zeroed_on_drop() {
+    assert_eq!(leaked(|| drop(secret(false))), 1);
+    assert_eq!(leaked(|| drop(secret(true))), 0);
+
+    // marked once written
+    assert_eq!(
+        leaked(|| {
+            let mut parcel = secret(false);
+            parcel.mark_sensitive();
+        }),
+        0
+    );
+}
+
+#[test]
+fn zeroed_on_reallocation() {
+    let grow = |sensitive| {
+        leaked(|| {
+            let mut parcel = secret(sensitive);
+            parcel.write(&[0u8; 64 << 10][..]).unwrap();
+            // only the buffer left behind so far
+            std::mem::forget(parcel);
+        })
+    };
+    assert_eq!(grow(false), 1);
+    assert_eq!(grow(true), 0);
+}
+
+/// Replies whether the request was sensitive and sent with `ClearBuf`.
+struct SecretService;
+
+impl BinderService for SecretService {
+    fn progress_request(
+        &self,
+        code: u32,
+        data: &mut Parcel,
+        reply: &mut Parcel,
+        _ctx: &CallingContext,
+        flags: TransactionFlag,
+    ) -> Result<(), Status> {
+        data.enforce_interface(INTERFACE)?;
+        if code == 2 {
+            return Err(Status::new_exception(ExceptionCode::IllegalArgument, None));
+        }
+        reply.write(&data.is_sensitive())?;
+        reply.write(&flags.contains(TransactionFlag::ClearBuf))?;
+        Ok(())
+    }
+}
+
+#[test]
+fn clear_buf_to_replies() {
+    let server = SecretService;
+    let service = Service::local(&server, INTERFACE);
+
+    for sensitive in [false, true] {
+        let mut data = service.prepare_transaction().unwrap();
+        if sensitive {
+            data.mark_sensitive();
+        }
+        let mut reply = service
+            .transact(1, &mut data, TransactionFlag::empty())
+            .unwrap();
+        assert!(reply.read::<Status>().unwrap().is_ok());
+        assert_eq!(reply.read::<bool>().unwrap(), sensitive);
+        assert_eq!(reply.read::<bool>().unwrap(), sensitive);
+        assert_eq!(reply.is_sensitive(), sensitive);
+
+        // also the reply of an exception
+        let reply = service
+            .transact(2, &mut data, TransactionFlag::empty())
+            .unwrap();
+        assert_eq!(reply.is_sensitive(), sensitive);
+    }
+}
+
+#[test]
+fn clear_buf_through_append() {
+    // `call` appends the arguments after its interface token
+    let server = SecretService;
+    let service = Service::local(&server, INTERFACE);
+    let mut args = secret(true);
+
+    let mut reply = service
+        .call(1, &mut args, TransactionFlag::empty())
+        .unwrap();
+    assert!(reply.read::<bool>().unwrap());
+    assert!(reply.read::<bool>().unwrap());
+    assert!(reply.is_sensitive());
+}
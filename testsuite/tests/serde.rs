@@ -0,0 +1,88 @@
+//! serde types written as AIDL writes the equivalent types: nullable
+//! strings and arrays with a `-1` length, nullable parcelables with a flag.
+#![cfg(feature = "serde")]
+
+use std::fmt::Debug;
+
+use binder_rs::parcel::{
+    Parcel,
+    serde::{from_parcel, to_parcel},
+};
+use binder_rs_testsuite::{parcel_of, words};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Inner {
+    x: i32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Nullable {
+    name: Option<String>,
+    values: Option<Vec<i32>>,
+    inner: Option<Inner>,
+    count: Option<i32>,
+}
+
+/// `value` is written as `fixture`, which reads as `value`.
+fn check<T: Serialize + DeserializeOwned + PartialEq + Debug>(fixture: &[i32], value: T) {
+    let mut parcel = Parcel::new();
+    to_parcel(&value, &mut parcel).unwrap();
+    assert_eq!(words(&mut parcel), fixture);
+
+    let mut parcel = parcel_of(fixture);
+    assert_eq!(from_parcel::<T>(&mut parcel).unwrap(), value);
+    assert_eq!(parcel.data_position(), fixture.len() * 4);
+}
+
+#[test]
+fn null_fields() {
+    check(
+        &[
+            1,  // non-null
+            20, // size
+            -1, // name
+            -1, // values
+            0,  // inner
+            0,  // count
+        ],
+        Nullable {
+            name: None,
+            values: None,
+            inner: None,
+            count: None,
+        },
+    );
+}
+
+#[test]
+fn present_fields() {
+    check(
+        &[
+            1,  // non-null
+            40, // size
+            2,  // name, "ab"
+            0x0062_0061,
+            0,
+            0, // values, empty
+            1, // inner, non-null
+            8,
+            5,
+            1, // count, non-null
+            3,
+        ],
+        Nullable {
+            name: Some("ab".into()),
+            values: Some(vec![]),
+            inner: Some(Inner { x: 5 }),
+            count: Some(3),
+        },
+    );
+}
+
+#[test]
+fn null_elements() {
+    check(&[-1], None::<String>);
+    check(&[0, 0], Some(String::new()));
+    check(&[3, -1, 0, 1, 7], vec![None, Some(vec![]), Some(vec![7])]);
+}
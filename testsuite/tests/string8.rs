@@ -0,0 +1,78 @@
+//! The layout of `String8`, against words transcribed by hand from
+//! `Parcel::writeString8` of Android 14: the length in bytes, then the nul
+//! terminated bytes padded to 4 bytes.
+
+use binder_rs::{
+    error::BinderError,
+    parcel::{Parcel, string8::String8},
+};
+use binder_rs_testsuite::{parcel_of, words};
+
+#[test]
+fn round_trip() {
+    let fixture = [
+        6, // "héllo" in bytes
+        0x6c_a9_c3_68,
+        0x00_00_6f_6c,
+    ];
+    let mut parcel = Parcel::new();
+    parcel.write(&String8::from("héllo")).unwrap();
+    assert_eq!(words(&mut parcel), fixture);
+
+    let mut parcel = parcel_of(&fixture);
+    assert_eq!(parcel.read::<String8>().unwrap().0, "héllo");
+    assert!(!parcel.has_unread_data());
+
+    // the terminator alone takes a word
+    let mut parcel = Parcel::new();
+    parcel.write_string8(Some("")).unwrap();
+    assert_eq!(words(&mut parcel), [0, 0]);
+    parcel.set_data_position(0);
+    assert_eq!(parcel.read_string8().unwrap().as_deref(), Some(""));
+
+    let strings = vec![String8::from("a"), String8::from("bcd")];
+    let mut parcel = Parcel::new();
+    parcel.write(&strings).unwrap();
+    assert_eq!(words(&mut parcel), [2, 1, 0x61, 3, 0x00_64_63_62]);
+    parcel.set_data_position(0);
+    assert_eq!(parcel.read::<Vec<String8>>().unwrap(), strings);
+}
+
+#[test]
+fn null() {
+    let mut parcel = Parcel::new();
+    parcel.write(&None::<String8>).unwrap();
+    assert_eq!(words(&mut parcel), [-1]);
+
+    parcel.set_data_position(0);
+    assert_eq!(parcel.read::<Option<String8>>().unwrap(), None);
+    parcel.set_data_position(0);
+    assert!(matches!(
+        parcel.read::<String8>(),
+        Err(BinderError::UnexpectedNull)
+    ));
+}
+
+#[test]
+fn bad_length() {
+    // negative
+    assert!(matches!(
+        parcel_of(&[-2]).read_string8(),
+        Err(BinderError::BadValue)
+    ));
+    // longer than the data
+    assert!(matches!(
+        parcel_of(&[9, 0x6c_6c_65_68]).read_string8(),
+        Err(BinderError::NotEnoughData)
+    ));
+    // no terminator after the 4 bytes
+    assert!(matches!(
+        parcel_of(&[4, 0x6c_6c_65_68, 0x01]).read_string8(),
+        Err(BinderError::BadValue)
+    ));
+    // not UTF-8
+    assert!(matches!(
+        parcel_of(&[1, 0xff]).read_string8(),
+        Err(BinderError::BadValue)
+    ));
+}
@@ -0,0 +1,125 @@
+//! The layout of `Parcel.writeValue`, against words transcribed by hand
+//! from `Parcel.java` and `TextUtils.writeToParcel` of Android 14, not
+//! captured from a device. Since Android 13, maps, lists and parcelables
+//! are written with the length of their content in front.
+
+use binder_rs::parcel::{Parcel, value::*};
+use binder_rs_testsuite::{parcel_of, words};
+
+/// `fixture` reads as `value`, which is written as `fixture`.
+fn check(fixture: &[i32], value: ParcelValue) {
+    let mut parcel = parcel_of(fixture);
+    assert_eq!(parcel.read::<ParcelValue>().unwrap(), value);
+    assert_eq!(parcel.data_position(), fixture.len() * 4);
+
+    let mut parcel = Parcel::new();
+    parcel.write(&value).unwrap();
+    assert_eq!(words(&mut parcel), fixture);
+}
+
+#[test]
+fn map() {
+    // writeMapInternal: the count, then each key and value by writeValue
+    check(
+        &[
+            VAL_MAP,
+            24, // length, from the count to the end
+            1,
+            VAL_STRING,
+            1, // "k"
+            0x0000_006b,
+            VAL_INTEGER,
+            1,
+        ],
+        ParcelValue::Map(vec![("k".into(), ParcelValue::Int(1))]),
+    );
+}
+
+#[test]
+fn list() {
+    // writeList: the count, then each value by writeValue
+    check(
+        &[
+            VAL_LIST,
+            40,
+            3,
+            VAL_INTEGER,
+            7,
+            VAL_NULL,
+            VAL_LIST, // a nested list has its own length
+            16,
+            1,
+            VAL_LONG,
+            -1,
+            -1,
+        ],
+        ParcelValue::List(vec![
+            ParcelValue::Int(7),
+            ParcelValue::Null,
+            ParcelValue::List(vec![ParcelValue::Long(-1)]),
+        ]),
+    );
+}
+
+#[test]
+fn parcelable() {
+    // writeParcelable: the class name, then the content of the parcelable
+    check(
+        &[
+            VAL_PARCELABLE,
+            16,
+            3, // "a.B"
+            0x002e_0061,
+            0x0000_0042,
+            5,
+        ],
+        ParcelValue::Parcelable {
+            class: "a.B".to_owned(),
+            data: 5i32.to_le_bytes().to_vec(),
+        },
+    );
+}
+
+#[test]
+fn bad_length() {
+    // past the end of the parcel
+    let mut parcel = parcel_of(&[VAL_LIST, 48, 1, VAL_INTEGER, 7]);
+    assert!(parcel.read::<ParcelValue>().is_err());
+
+    // shorter than the value
+    let mut parcel = parcel_of(&[VAL_LIST, 4, 1, VAL_INTEGER, 7]);
+    assert!(parcel.read::<ParcelValue>().is_err());
+}
+
+#[test]
+fn char_sequence() {
+    // 1 for a plain string, then the string in UTF-8 with a terminator
+    check(
+        &[VAL_CHARSEQUENCE, 1, 2, 0x0000_6968],
+        ParcelValue::CharSequence(Some("hi".to_owned())),
+    );
+    check(&[VAL_CHARSEQUENCE, 1, -1], ParcelValue::CharSequence(None));
+
+    // spans are not supported
+    let mut parcel = parcel_of(&[VAL_CHARSEQUENCE, 0, 2, 0x0000_6968, 0]);
+    assert!(parcel.read::<ParcelValue>().is_err());
+}
+
+#[test]
+fn sparse_boolean_array() {
+    // writeSparseBooleanArray: the count, then each key and a byte as an int
+    check(
+        &[VAL_SPARSEBOOLEANARRAY, 2, 3, 1, 9, 0],
+        ParcelValue::SparseBooleanArray(vec![(3, true), (9, false)]),
+    );
+}
+
+#[test]
+fn size() {
+    // writeSize: the width then the height
+    check(&[VAL_SIZE, 640, 480], ParcelValue::Size(640, 480));
+    check(
+        &[VAL_SIZEF, 1.5f32.to_bits() as i32, 0.25f32.to_bits() as i32],
+        ParcelValue::SizeF(1.5, 0.25),
+    );
+}